@@ -0,0 +1,147 @@
+//! Throughput benchmarks for the scanning hot paths, reported in lines per second.
+//!
+//! Each corpus is generated deterministically so results are comparable between runs:
+//! * `source` - source code with the occasional credential assignment
+//! * `logs` - application log lines with request IDs, emails and URLs
+//! * `base64` - long base64 blobs, the worst case for `entropy_findings`
+//!
+//! Run with `cargo bench -p rusty_hog_scanner`, optionally filtered by group name, e.g.
+//! `cargo bench -p rusty_hog_scanner -- matches_entropy`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rusty_hog_scanner::{SecretScanner, SecretScannerBuilder};
+
+const LINES: usize = 2000;
+
+/// Small deterministic PRNG (xorshift) so the corpora don't depend on a `rand` crate
+struct XorShift(u64);
+
+impl XorShift {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn string(&mut self, alphabet: &[u8], len: usize) -> String {
+        (0..len)
+            .map(|_| alphabet[(self.next() % alphabet.len() as u64) as usize] as char)
+            .collect()
+    }
+}
+
+const ALPHANUMERIC: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789";
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn source_corpus(rng: &mut XorShift) -> Vec<String> {
+    (0..LINES)
+        .map(|i| match i % 50 {
+            0 => format!(
+                "    let aws_key = \"AKIA{}\";",
+                rng.string(b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567", 16)
+            ),
+            25 => format!("    password = \"{}\"", rng.string(ALPHANUMERIC, 20)),
+            n if n % 7 == 0 => String::from("    }"),
+            n if n % 5 == 0 => format!(
+                "    // {} the {} before returning",
+                "normalize",
+                rng.string(ALPHANUMERIC, 8)
+            ),
+            _ => format!(
+                "    let {} = self.{}(&{}, {})?;",
+                rng.string(b"abcdefghijklmnopqrstuvwxyz_", 10),
+                rng.string(b"abcdefghijklmnopqrstuvwxyz_", 12),
+                rng.string(b"abcdefghijklmnopqrstuvwxyz_", 6),
+                rng.next() % 1000
+            ),
+        })
+        .collect()
+}
+
+fn logs_corpus(rng: &mut XorShift) -> Vec<String> {
+    (0..LINES)
+        .map(|i| {
+            format!(
+                "2023-04-{:02}T12:{:02}:{:02}.{:03}Z INFO request_id={} user={}@example.com GET https://api.example.com/v1/items/{} status=200",
+                i % 28 + 1,
+                i % 60,
+                (i * 7) % 60,
+                i % 1000,
+                rng.string(b"0123456789abcdef", 32),
+                rng.string(b"abcdefghijklmnopqrstuvwxyz", 8),
+                rng.next() % 100000
+            )
+        })
+        .collect()
+}
+
+fn base64_corpus(rng: &mut XorShift) -> Vec<String> {
+    (0..LINES / 10).map(|_| rng.string(BASE64, 1000)).collect()
+}
+
+fn corpora() -> Vec<(&'static str, Vec<String>)> {
+    let mut rng = XorShift(0x5eed_1234_abcd_ef01);
+    vec![
+        ("source", source_corpus(&mut rng)),
+        ("logs", logs_corpus(&mut rng)),
+        ("base64", base64_corpus(&mut rng)),
+    ]
+}
+
+fn bench_matches(c: &mut Criterion) {
+    let ss = SecretScannerBuilder::new().build();
+    let mut group = c.benchmark_group("matches");
+    for (name, lines) in corpora() {
+        group.throughput(Throughput::Elements(lines.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &lines, |b, lines| {
+            b.iter(|| {
+                for line in lines {
+                    for (_, matches) in ss.matches(line.as_bytes()) {
+                        black_box(matches.len());
+                    }
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_matches_entropy(c: &mut Criterion) {
+    let ss = SecretScannerBuilder::new().build();
+    let mut group = c.benchmark_group("matches_entropy");
+    for (name, lines) in corpora() {
+        group.throughput(Throughput::Elements(lines.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &lines, |b, lines| {
+            b.iter(|| {
+                for line in lines {
+                    black_box(ss.matches_entropy(line.as_bytes()));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_entropy_findings(c: &mut Criterion) {
+    let mut group = c.benchmark_group("entropy_findings");
+    for (name, lines) in corpora() {
+        group.throughput(Throughput::Elements(lines.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &lines, |b, lines| {
+            b.iter(|| {
+                for line in lines {
+                    black_box(SecretScanner::entropy_findings(line.as_bytes(), 4.5));
+                }
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_matches,
+    bench_matches_entropy,
+    bench_entropy_findings
+);
+criterion_main!(benches);
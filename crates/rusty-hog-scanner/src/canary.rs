@@ -0,0 +1,164 @@
+//! Fake secrets for `--generate-canaries`, to seed test repositories and check that a scanning
+//! pipeline reports what it should from end to end.
+//!
+//! A value is generated for a rule by walking the syntax tree of its pattern and picking random
+//! printable ASCII wherever the pattern allows a choice. It is then scanned, and only kept if that
+//! rule finds it (after its entropy, validator and allowlist checks) and no other rule does.
+//! Values that don't qualify are regenerated up to `MAX_ATTEMPTS` times, and rules without a
+//! value after that, such as two rules with the same pattern, are left out.
+//!
+//! ```
+//! use rusty_hog_scanner::SecretScannerBuilder;
+//! let scanner = SecretScannerBuilder::new().build();
+//! let mut rng = fastrand::Rng::with_seed(7);
+//! let token = rusty_hog_scanner::canary::generate(&scanner, "GitHub Personal Access Token", &mut rng).unwrap();
+//! assert!(token.starts_with("ghp_"));
+//! let found = scanner.matches_entropy(token.as_bytes());
+//! assert_eq!(found.keys().collect::<Vec<_>>(), vec!["GitHub Personal Access Token"]);
+//! ```
+
+use crate::SecretScanner;
+use log::{self, debug};
+use regex_syntax::hir::{Class, Hir, HirKind};
+use std::collections::BTreeMap;
+
+/// How many values are tried for a rule before giving up on it
+pub const MAX_ATTEMPTS: usize = 1000;
+
+// How many more than its minimum an open-ended repetition like `+` or `{32,}` repeats at most
+const MAX_EXTRA_REPETITIONS: u32 = 16;
+
+/// Picks a random character of a class, printable ASCII if the class has any
+fn pick_class(class: &Class, rng: &mut fastrand::Rng, out: &mut Vec<u8>) {
+    let ranges: Vec<(u32, u32)> = match class {
+        Class::Unicode(c) => c
+            .ranges()
+            .iter()
+            .map(|r| (r.start() as u32, r.end() as u32))
+            .collect(),
+        Class::Bytes(c) => c
+            .ranges()
+            .iter()
+            .map(|r| (r.start() as u32, r.end() as u32))
+            .collect(),
+    };
+    let printable: Vec<(u32, u32)> = ranges
+        .iter()
+        .filter_map(|&(start, end)| {
+            let (start, end) = (start.max(0x20), end.min(0x7e));
+            (start <= end).then_some((start, end))
+        })
+        .collect();
+    let ranges = if printable.is_empty() {
+        ranges
+    } else {
+        printable
+    };
+    let total: u32 = ranges.iter().map(|(start, end)| end - start + 1).sum();
+    if total == 0 {
+        return;
+    }
+    let mut n = rng.u32(0..total);
+    for (start, end) in ranges {
+        if n <= end - start {
+            let c = start + n;
+            match class {
+                Class::Bytes(_) => out.push(c as u8),
+                Class::Unicode(_) => {
+                    let mut buf = [0; 4];
+                    let c = char::from_u32(c).unwrap_or('?');
+                    out.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                }
+            }
+            return;
+        }
+        n -= end - start + 1;
+    }
+}
+
+/// Appends a random string that `hir` matches, ignoring look-arounds like `\b`
+fn sample(hir: &Hir, rng: &mut fastrand::Rng, out: &mut Vec<u8>) {
+    match hir.kind() {
+        HirKind::Empty | HirKind::Look(_) => {}
+        HirKind::Literal(literal) => out.extend_from_slice(&literal.0),
+        HirKind::Class(class) => pick_class(class, rng, out),
+        HirKind::Repetition(repetition) => {
+            let max = repetition
+                .max
+                .unwrap_or(u32::MAX)
+                .min(repetition.min.saturating_add(MAX_EXTRA_REPETITIONS));
+            for _ in 0..rng.u32(repetition.min..=max) {
+                sample(&repetition.sub, rng, out);
+            }
+        }
+        HirKind::Capture(capture) => sample(&capture.sub, rng, out),
+        HirKind::Concat(hirs) => {
+            for hir in hirs {
+                sample(hir, rng, out);
+            }
+        }
+        HirKind::Alternation(hirs) => sample(&hirs[rng.usize(0..hirs.len())], rng, out),
+    }
+}
+
+/// A value that only `rule` finds, if one turns up in `MAX_ATTEMPTS` tries
+pub fn generate(scanner: &SecretScanner, rule: &str, rng: &mut fastrand::Rng) -> Option<String> {
+    let entropy_regex = scanner.regex_map.get(rule)?;
+    let hir = regex_syntax::ParserBuilder::new()
+        .utf8(false)
+        .build()
+        .parse(entropy_regex.pattern_str())
+        .ok()?;
+    for _ in 0..MAX_ATTEMPTS {
+        let mut value = Vec::new();
+        sample(&hir, rng, &mut value);
+        let Ok(value) = String::from_utf8(value) else {
+            continue;
+        };
+        let found = scanner.matches_entropy(value.as_bytes());
+        if found.keys().filter(|name| *name != "Entropy").eq([rule]) {
+            return Some(value);
+        }
+    }
+    None
+}
+
+/// A value for each rule of `scanner`, leaving out those none could be found for
+pub fn generate_all(scanner: &SecretScanner, rng: &mut fastrand::Rng) -> BTreeMap<String, String> {
+    let mut canaries = BTreeMap::new();
+    for rule in scanner.regex_map.keys() {
+        match generate(scanner, rule, rng) {
+            Some(value) => {
+                canaries.insert(rule.clone(), value);
+            }
+            None => debug!(
+                "No value only {} finds turned up in {} attempts, leaving it out",
+                rule, MAX_ATTEMPTS
+            ),
+        }
+    }
+    canaries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SecretScannerBuilder;
+
+    #[test]
+    fn generates_values_only_their_rule_finds() {
+        let scanner = SecretScannerBuilder::new().set_pii(true).build();
+        let mut rng = fastrand::Rng::with_seed(2958);
+        let canaries = generate_all(&scanner, &mut rng);
+        for (rule, value) in &canaries {
+            let found = scanner.matches_entropy(value.as_bytes());
+            assert_eq!(found.keys().collect::<Vec<_>>(), vec![rule], "{}", value);
+        }
+        assert!(canaries.contains_key("Amazon AWS Access Key ID"));
+        assert!(canaries.contains_key("Credit Card Number"));
+        assert!(canaries.contains_key("IBAN"));
+        // Rules sharing a pattern always find each other's values
+        assert!(!canaries.contains_key("Google API Key"));
+        assert!(generate(&scanner, "No Such Rule", &mut rng).is_none());
+    }
+}
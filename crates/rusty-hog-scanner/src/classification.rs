@@ -0,0 +1,172 @@
+//! Data categories and match validators used to turn the hogs into lightweight DLP scanners.
+//!
+//! Rules can carry a `category` (`credentials`, `pii` or `financial`; rules without one are
+//! `credentials`) and a `validator` that a match has to pass before it becomes a finding:
+//!
+//! * `luhn` - the digits of the match pass the Luhn checksum (credit cards, Canadian SINs)
+//! * `iban` - the match is an IBAN with a valid ISO 7064 mod 97 check digit
+//! * `ssn` - the match is a US Social Security Number in an issued range
+//!
+//! The PII rule pack in `pii_rules.json` is loaded on top of the regular rules with `--pii`, and
+//! `--classify` adds each finding's `category` to the output.
+//!
+//! ```
+//! use rusty_hog_scanner::classification::Validator;
+//! assert!(Validator::Luhn.validate(b"4111 1111 1111 1111"));
+//! assert!(!Validator::Luhn.validate(b"4111 1111 1111 1112"));
+//! assert!(Validator::Iban.validate(b"GB82 WEST 1234 5698 7654 32"));
+//! ```
+
+use serde_derive::{Deserialize, Serialize};
+use simple_error::SimpleError;
+use std::fmt;
+use std::str::FromStr;
+
+/// What kind of sensitive data a rule finds
+#[derive(
+    Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Category {
+    #[default]
+    Credentials,
+    Pii,
+    Financial,
+}
+
+impl FromStr for Category {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "credentials" => Ok(Category::Credentials),
+            "pii" => Ok(Category::Pii),
+            "financial" => Ok(Category::Financial),
+            _ => Err(SimpleError::new(format!("unknown category {:?}", s))),
+        }
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Category::Credentials => "credentials",
+            Category::Pii => "pii",
+            Category::Financial => "financial",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A check a match has to pass on top of the rule's regex
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Validator {
+    Luhn,
+    Iban,
+    Ssn,
+}
+
+impl FromStr for Validator {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "luhn" => Ok(Validator::Luhn),
+            "iban" => Ok(Validator::Iban),
+            "ssn" => Ok(Validator::Ssn),
+            _ => Err(SimpleError::new(format!("unknown validator {:?}", s))),
+        }
+    }
+}
+
+fn digits(text: &[u8]) -> Vec<u32> {
+    text.iter()
+        .filter(|b| b.is_ascii_digit())
+        .map(|b| u32::from(b - b'0'))
+        .collect()
+}
+
+fn luhn(text: &[u8]) -> bool {
+    let digits = digits(text);
+    if digits.len() < 9 {
+        return false;
+    }
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(i, d)| match (i % 2, d * 2) {
+            (1, doubled) if doubled > 9 => doubled - 9,
+            (1, doubled) => doubled,
+            _ => *d,
+        })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+fn iban(text: &[u8]) -> bool {
+    let iban: Vec<u8> = text
+        .iter()
+        .filter(|b| !b.is_ascii_whitespace())
+        .map(|b| b.to_ascii_uppercase())
+        .collect();
+    if iban.len() < 15 || iban.len() > 34 || !iban.iter().all(|b| b.is_ascii_alphanumeric()) {
+        return false;
+    }
+    // Move the country code and check digits to the end, then read letters as 10..=35
+    let remainder = iban[4..]
+        .iter()
+        .chain(&iban[..4])
+        .fold(0u32, |acc, b| match b {
+            b'0'..=b'9' => (acc * 10 + u32::from(b - b'0')) % 97,
+            _ => (acc * 100 + u32::from(b - b'A') + 10) % 97,
+        });
+    remainder == 1
+}
+
+fn ssn(text: &[u8]) -> bool {
+    let digits = digits(text);
+    if digits.len() != 9 {
+        return false;
+    }
+    let number = |range: std::ops::Range<usize>| digits[range].iter().fold(0, |n, d| n * 10 + d);
+    let (area, group, serial) = (number(0..3), number(3..5), number(5..9));
+    area != 0 && area != 666 && area < 900 && group != 0 && serial != 0
+}
+
+impl Validator {
+    /// Returns true if the matched text passes the check
+    pub fn validate(&self, text: &[u8]) -> bool {
+        match self {
+            Validator::Luhn => luhn(text),
+            Validator::Iban => iban(text),
+            Validator::Ssn => ssn(text),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validators_reject_invalid_numbers() {
+        assert!(Validator::Luhn.validate(b"378282246310005"));
+        assert!(Validator::Luhn.validate(b"046-454-286"));
+        assert!(!Validator::Luhn.validate(b"046-454-287"));
+        assert!(Validator::Iban.validate(b"DE89370400440532013000"));
+        assert!(Validator::Iban.validate(b"de89 3704 0044 0532 0130 00"));
+        assert!(!Validator::Iban.validate(b"DE89370400440532013001"));
+        assert!(Validator::Ssn.validate(b"123-45-6789"));
+        assert!(!Validator::Ssn.validate(b"666-45-6789"));
+        assert!(!Validator::Ssn.validate(b"123-00-6789"));
+        assert!(!Validator::Ssn.validate(b"912-45-6789"));
+    }
+
+    #[test]
+    fn parses_categories() {
+        assert_eq!("PII".parse::<Category>().unwrap(), Category::Pii);
+        assert_eq!(Category::Financial.to_string(), "financial");
+        assert!("secret".parse::<Category>().is_err());
+    }
+}
@@ -0,0 +1,101 @@
+//! Gzip-compressed output for `--output-compress`, for shipping results to object storage.
+//!
+//! The output file gets a `.gz` suffix unless it already has one (`findings.json` is written to
+//! `findings.json.gz`), and so do the parts and index of `--output-split`. Findings written to
+//! stdout are compressed too, for piping into another tool.
+//!
+//! ```
+//! use rusty_hog_scanner::compression::output_path;
+//! assert_eq!(output_path("findings.json", true), "findings.json.gz");
+//! assert_eq!(output_path("findings.json.gz", true), "findings.json.gz");
+//! assert_eq!(output_path("findings.json", false), "findings.json");
+//! ```
+
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+/// The path findings are written to, with a `.gz` suffix when compressing
+pub fn output_path(path: &str, compress: bool) -> String {
+    if compress && !path.ends_with(".gz") {
+        format!("{}.gz", path)
+    } else {
+        String::from(path)
+    }
+}
+
+/// A file (or stdout) that findings are written to, gzip-compressed or not. `finish` must be
+/// called to write the end of the gzip stream.
+pub enum OutputWriter<W: Write> {
+    Plain(BufWriter<W>),
+    Gzip(GzEncoder<BufWriter<W>>),
+}
+
+impl OutputWriter<File> {
+    /// Creates the file at `output_path(path, compress)`
+    pub fn create(path: &str, compress: bool) -> Result<Self> {
+        let file = File::create(output_path(path, compress))?;
+        Ok(Self::new(file, compress))
+    }
+}
+
+impl<W: Write> OutputWriter<W> {
+    pub fn new(inner: W, compress: bool) -> Self {
+        let writer = BufWriter::new(inner);
+        if compress {
+            OutputWriter::Gzip(GzEncoder::new(writer, Compression::default()))
+        } else {
+            OutputWriter::Plain(writer)
+        }
+    }
+
+    /// Flushes the output, finishing the gzip stream
+    pub fn finish(self) -> Result<()> {
+        let mut writer = match self {
+            OutputWriter::Plain(writer) => writer,
+            OutputWriter::Gzip(encoder) => encoder.finish()?,
+        };
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for OutputWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputWriter::Plain(writer) => writer.write(buf),
+            OutputWriter::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputWriter::Plain(writer) => writer.flush(),
+            OutputWriter::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn compresses_output() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("findings.json");
+        let mut writer = OutputWriter::create(path.to_str().unwrap(), true).unwrap();
+        writer.write_all(b"[{\"reason\":\"Slack Token\"}]").unwrap();
+        writer.finish().unwrap();
+
+        let file = File::open(dir.path().join("findings.json.gz")).unwrap();
+        let mut text = String::new();
+        GzDecoder::new(file).read_to_string(&mut text).unwrap();
+        assert_eq!(text, "[{\"reason\":\"Slack Token\"}]");
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,226 @@
+//! Links findings of paired rules into a single composite finding, e.g. an AWS access key ID and
+//! the secret access key committed next to it.
+//!
+//! A correlation names two rules and a window of lines. When findings for both rules come from
+//! the same source (same file, object, page, message, commit, ...) and are at most `window` lines
+//! apart, the pair is replaced by one finding with the correlation's name as its `reason`, the
+//! strings found by both, an elevated `severity`, and the original findings under `correlated`.
+//! Findings from sources without line numbers (e.g. a Jira issue or Slack message) are paired
+//! whenever they share a source.
+//!
+//! The built-in correlations are in `default_correlations.json`; a file in the same format can be
+//! supplied with `--correlations`:
+//!
+//! ```json
+//! [
+//!     {
+//!         "name": "Database credentials",
+//!         "rules": ["DB username", "DB password"],
+//!         "window": 3,
+//!         "severity": "critical"
+//!     }
+//! ]
+//! ```
+//!
+//! `window` defaults to 5 lines. Without a `severity`, the composite finding is one level above
+//! the more severe of the pair.
+
+use crate::{SecretScanner, Severity};
+use serde_derive::Deserialize;
+use serde_json::{Map, Value};
+
+// Properties that differ between findings from the same source
+const PER_FINDING_PROPERTIES: &[&str] = &[
+    "reason",
+    "severity",
+    "category",
+    "fingerprint",
+    "remediation",
+    "stringsFound",
+    "diff",
+    "linenum",
+    "lineindextuples",
+    "old_line_num",
+    "new_line_num",
+    "encoded",
+    "assigned_to",
+];
+
+fn default_window() -> u64 {
+    5
+}
+
+/// A pair of rules whose findings are merged when they appear close together
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Correlation {
+    pub name: String,
+    pub rules: [String; 2],
+    #[serde(default = "default_window")]
+    pub window: u64,
+    pub severity: Option<Severity>,
+}
+
+fn elevate(severity: Severity) -> Severity {
+    match severity {
+        Severity::Low => Severity::Medium,
+        Severity::Medium => Severity::High,
+        Severity::High | Severity::Critical => Severity::Critical,
+    }
+}
+
+fn reason(finding: &Value) -> &str {
+    finding
+        .get("reason")
+        .and_then(|r| r.as_str())
+        .unwrap_or_default()
+}
+
+fn severity(finding: &Value) -> Severity {
+    finding
+        .get("severity")
+        .and_then(|s| s.as_str())
+        .and_then(|s| s.parse().ok())
+        .unwrap_or_default()
+}
+
+/// The line a finding was found on, for hogs that report one
+fn line_number(finding: &Value) -> Option<u64> {
+    ["linenum", "new_line_num"]
+        .iter()
+        .filter_map(|k| finding.get(*k).and_then(|n| n.as_u64()))
+        .find(|n| *n > 0)
+}
+
+/// Identifies the source of a finding: every property except the per-finding ones
+fn source(finding: &Value) -> String {
+    match finding {
+        Value::Object(obj) => {
+            let source: Map<String, Value> = obj
+                .iter()
+                .filter(|(k, _)| !PER_FINDING_PROPERTIES.contains(&k.as_str()))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            Value::Object(source).to_string()
+        }
+        _ => String::new(),
+    }
+}
+
+fn within_window(a: &Value, b: &Value, window: u64) -> Option<u64> {
+    match (line_number(a), line_number(b)) {
+        (Some(x), Some(y)) => Some(x.abs_diff(y)).filter(|d| *d <= window),
+        _ => Some(0),
+    }
+}
+
+fn composite(correlation: &Correlation, a: &Value, b: &Value) -> Value {
+    let mut obj = match a {
+        Value::Object(obj) => obj.clone(),
+        _ => Map::new(),
+    };
+    obj.remove("fingerprint");
+    obj.remove("severity");
+    obj.insert(
+        String::from("reason"),
+        Value::String(correlation.name.clone()),
+    );
+    let strings_found: Vec<Value> = [a, b]
+        .iter()
+        .filter_map(|f| f.get("stringsFound").and_then(|s| s.as_array()))
+        .flatten()
+        .cloned()
+        .collect();
+    obj.insert(String::from("stringsFound"), Value::Array(strings_found));
+    obj.insert(
+        String::from("correlated"),
+        Value::Array(vec![a.clone(), b.clone()]),
+    );
+    let fingerprint = SecretScanner::fingerprint(&Value::Object(obj.clone()));
+    let severity = correlation
+        .severity
+        .unwrap_or_else(|| elevate(severity(a).max(severity(b))));
+    obj.insert(String::from("fingerprint"), Value::String(fingerprint));
+    obj.insert(
+        String::from("severity"),
+        Value::String(severity.to_string()),
+    );
+    Value::Object(obj)
+}
+
+/// Replaces each correlated pair of prepared findings (see `SecretScanner::prepare_findings`)
+/// with a composite finding. Correlations are applied in order, and a finding is only ever
+/// paired once, with the closest match.
+pub fn correlate(findings: Vec<Value>, correlations: &[Correlation]) -> Vec<Value> {
+    let sources: Vec<String> = findings.iter().map(source).collect();
+    let mut used = vec![false; findings.len()];
+    let mut composites = Vec::new();
+    for correlation in correlations {
+        let [first, second] = &correlation.rules;
+        for i in 0..findings.len() {
+            if used[i] || reason(&findings[i]) != first {
+                continue;
+            }
+            let closest = (0..findings.len())
+                .filter(|&j| j != i && !used[j] && reason(&findings[j]) == second)
+                .filter(|&j| sources[j] == sources[i])
+                .filter_map(|j| {
+                    within_window(&findings[i], &findings[j], correlation.window).map(|d| (d, j))
+                })
+                .min();
+            if let Some((_, j)) = closest {
+                used[i] = true;
+                used[j] = true;
+                composites.push(composite(correlation, &findings[i], &findings[j]));
+            }
+        }
+    }
+    findings
+        .into_iter()
+        .zip(used)
+        .filter(|(_, used)| !used)
+        .map(|(f, _)| f)
+        .chain(composites)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn aws_pair() -> Vec<Correlation> {
+        serde_json::from_str(
+            r#"[{"name": "AWS access key pair", "rules": ["Amazon AWS Access Key ID", "Generic Secret"]}]"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn pairs_findings_within_window() {
+        let findings = vec![
+            json!({"reason": "Amazon AWS Access Key ID", "severity": "high", "path": "a.ini", "linenum": 2, "stringsFound": ["AKIA1"]}),
+            json!({"reason": "Generic Secret", "severity": "medium", "path": "a.ini", "linenum": 3, "stringsFound": ["secret1"]}),
+            json!({"reason": "Generic Secret", "severity": "medium", "path": "a.ini", "linenum": 40, "stringsFound": ["secret2"]}),
+            json!({"reason": "Generic Secret", "severity": "medium", "path": "b.ini", "linenum": 2, "stringsFound": ["secret3"]}),
+        ];
+        let output = correlate(findings, &aws_pair());
+        assert_eq!(output.len(), 3);
+        let composite = output.last().unwrap();
+        assert_eq!(composite["reason"], "AWS access key pair");
+        assert_eq!(composite["severity"], "critical");
+        assert_eq!(composite["path"], "a.ini");
+        assert_eq!(composite["stringsFound"], json!(["AKIA1", "secret1"]));
+        assert_eq!(composite["correlated"].as_array().unwrap().len(), 2);
+        assert!(composite["fingerprint"].is_string());
+        assert_eq!(output[0]["stringsFound"], json!(["secret2"]));
+    }
+
+    #[test]
+    fn pairs_findings_without_lines_by_source() {
+        let findings = vec![
+            json!({"reason": "Amazon AWS Access Key ID", "severity": "high", "url": "https://jira/1", "issue_id": "1"}),
+            json!({"reason": "Generic Secret", "severity": "low", "url": "https://jira/2", "issue_id": "2"}),
+        ];
+        assert_eq!(correlate(findings.clone(), &aws_pair()), findings);
+    }
+}
@@ -0,0 +1,368 @@
+//! Normalized entropy, the measure `entropy_filter` rules and the standalone entropy detector
+//! (`--entropy`) use to tell random secrets from ordinary words.
+//!
+//! The Shannon entropy of a string is the average number of bits each of its characters carries,
+//! given how often each occurs. It depends on the alphabet the string is drawn from, so it is
+//! divided by the bits per character of that alphabet (the keyspace): a normalized entropy of 1
+//! means every character is as unpredictable as the keyspace allows, and 0 that the string
+//! repeats a single character. When no keyspace is given it is guessed from the characters used:
+//! 64 for strings of base64 characters, hex strings included, and 128 (ASCII) otherwise.
+//!
+//! Lines are split into words on the `WORD_SPLIT` characters, or any other set, and a line's
+//! entropy is that of its most random word, each word truncated to a maximum length so a long
+//! word isn't scored on more characters than a secret has.
+//!
+//! ```
+//! use rusty_hog_scanner::entropy;
+//! assert_eq!(entropy::guess_keyspace(b"9a303808fabab57e"), (64, false));
+//! assert_eq!(entropy::guess_keyspace(b"9a30-3808"), (128, false));
+//! assert_eq!(entropy::calc_normalized_entropy(b"aaaaaaaa", None, false), 0.0);
+//! assert!(entropy::calc_normalized_entropy(b"Zx8Kq2Lm9Vt4Rw7Ny3Bc6Fh1", None, false) > 0.7);
+//! let words: Vec<&[u8]> = entropy::split_words(b"key = 'Zx8Kq2Lm'", entropy::WORD_SPLIT)
+//!     .filter(|w| !w.is_empty())
+//!     .collect();
+//! assert_eq!(words, vec![&b"key"[..], &b"Zx8Kq2Lm"[..]]);
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+// from https://docs.rs/crate/base64/0.11.0/source/src/tables.rs
+// copied because the value itself was private in the base64 crate
+/// The characters of the standard base64 alphabet, without padding
+pub const B64_ENCODE: &[u8; 64] = &[
+    65,  // input 0 (0x0) => 'A' (0x41)
+    66,  // input 1 (0x1) => 'B' (0x42)
+    67,  // input 2 (0x2) => 'C' (0x43)
+    68,  // input 3 (0x3) => 'D' (0x44)
+    69,  // input 4 (0x4) => 'E' (0x45)
+    70,  // input 5 (0x5) => 'F' (0x46)
+    71,  // input 6 (0x6) => 'G' (0x47)
+    72,  // input 7 (0x7) => 'H' (0x48)
+    73,  // input 8 (0x8) => 'I' (0x49)
+    74,  // input 9 (0x9) => 'J' (0x4A)
+    75,  // input 10 (0xA) => 'K' (0x4B)
+    76,  // input 11 (0xB) => 'L' (0x4C)
+    77,  // input 12 (0xC) => 'M' (0x4D)
+    78,  // input 13 (0xD) => 'N' (0x4E)
+    79,  // input 14 (0xE) => 'O' (0x4F)
+    80,  // input 15 (0xF) => 'P' (0x50)
+    81,  // input 16 (0x10) => 'Q' (0x51)
+    82,  // input 17 (0x11) => 'R' (0x52)
+    83,  // input 18 (0x12) => 'S' (0x53)
+    84,  // input 19 (0x13) => 'T' (0x54)
+    85,  // input 20 (0x14) => 'U' (0x55)
+    86,  // input 21 (0x15) => 'V' (0x56)
+    87,  // input 22 (0x16) => 'W' (0x57)
+    88,  // input 23 (0x17) => 'X' (0x58)
+    89,  // input 24 (0x18) => 'Y' (0x59)
+    90,  // input 25 (0x19) => 'Z' (0x5A)
+    97,  // input 26 (0x1A) => 'a' (0x61)
+    98,  // input 27 (0x1B) => 'b' (0x62)
+    99,  // input 28 (0x1C) => 'c' (0x63)
+    100, // input 29 (0x1D) => 'd' (0x64)
+    101, // input 30 (0x1E) => 'e' (0x65)
+    102, // input 31 (0x1F) => 'f' (0x66)
+    103, // input 32 (0x20) => 'g' (0x67)
+    104, // input 33 (0x21) => 'h' (0x68)
+    105, // input 34 (0x22) => 'i' (0x69)
+    106, // input 35 (0x23) => 'j' (0x6A)
+    107, // input 36 (0x24) => 'k' (0x6B)
+    108, // input 37 (0x25) => 'l' (0x6C)
+    109, // input 38 (0x26) => 'm' (0x6D)
+    110, // input 39 (0x27) => 'n' (0x6E)
+    111, // input 40 (0x28) => 'o' (0x6F)
+    112, // input 41 (0x29) => 'p' (0x70)
+    113, // input 42 (0x2A) => 'q' (0x71)
+    114, // input 43 (0x2B) => 'r' (0x72)
+    115, // input 44 (0x2C) => 's' (0x73)
+    116, // input 45 (0x2D) => 't' (0x74)
+    117, // input 46 (0x2E) => 'u' (0x75)
+    118, // input 47 (0x2F) => 'v' (0x76)
+    119, // input 48 (0x30) => 'w' (0x77)
+    120, // input 49 (0x31) => 'x' (0x78)
+    121, // input 50 (0x32) => 'y' (0x79)
+    122, // input 51 (0x33) => 'z' (0x7A)
+    48,  // input 52 (0x34) => '0' (0x30)
+    49,  // input 53 (0x35) => '1' (0x31)
+    50,  // input 54 (0x36) => '2' (0x32)
+    51,  // input 55 (0x37) => '3' (0x33)
+    52,  // input 56 (0x38) => '4' (0x34)
+    53,  // input 57 (0x39) => '5' (0x35)
+    54,  // input 58 (0x3A) => '6' (0x36)
+    55,  // input 59 (0x3B) => '7' (0x37)
+    56,  // input 60 (0x3C) => '8' (0x38)
+    57,  // input 61 (0x3D) => '9' (0x39)
+    43,  // input 62 (0x3E) => '+' (0x2B)
+    47,  // input 63 (0x3F) => '/' (0x2F)
+];
+
+/// The characters of hexadecimal strings, in both cases
+pub const HEX_ENCODE: &[u8; 22] = &[
+    65,  // 'A' (0x41)
+    66,  // 'B' (0x42)
+    67,  // 'C' (0x43)
+    68,  // 'D' (0x44)
+    69,  // 'E' (0x45)
+    70,  // 'F' (0x46)
+    97,  // 'a' (0x61)
+    98,  // 'b' (0x62)
+    99,  // 'c' (0x63)
+    100, // 'd' (0x64)
+    101, // 'e' (0x65)
+    102, // 'f' (0x66)
+    48,  // '0' (0x30)
+    49,  // '1' (0x31)
+    50,  // '2' (0x32)
+    51,  // '3' (0x33)
+    52,  // '4' (0x34)
+    53,  // '5' (0x35)
+    54,  // '6' (0x36)
+    55,  // '7' (0x37)
+    56,  // '8' (0x38)
+    57,  // '9' (0x39)
+];
+
+/// The characters `split_words` splits on by default: spaces, quotes, backticks, parentheses and
+/// the `:` and `=` of assignments
+pub const WORD_SPLIT: &[u8] = &[
+    32, // ' '
+    34, // '"'
+    39, // "'"
+    40, // '('
+    41, // ')'
+    58, // ':'
+    61, // '='
+    96, // '`'
+];
+
+/// Whether a byte array only contains base64 characters (see `B64_ENCODE`)
+pub fn is_base64_string(string_in: &[u8]) -> bool {
+    let hashset_string_in: HashSet<&u8> = string_in.iter().collect();
+    hashset_string_in.is_subset(&B64_ENCODE.iter().collect())
+}
+
+/// Whether a byte array only contains hex characters (see `HEX_ENCODE`)
+pub fn is_hex_string(string_in: &[u8]) -> bool {
+    let hashset_string_in: HashSet<&u8> = string_in.iter().collect();
+    hashset_string_in.is_subset(&HEX_ENCODE.iter().collect())
+}
+
+/// Compute the Shannon entropy for a byte array in bits per character, optionally ignoring case
+/// (from https://docs.rs/crate/entropy/0.3.0/source/src/lib.rs)
+pub fn calc_shannon_entropy(bytes: &[u8], make_ascii_lowercase: bool) -> f32 {
+    let mut entropy = 0.0;
+    let mut counts: HashMap<u8, i32> = HashMap::new();
+
+    // there may be better ways to make this code shorter, but this method prevents byte copies
+    // if make_ascii_lowercase is set to false
+    if make_ascii_lowercase {
+        for &b in bytes {
+            let mut c = b;
+            c.make_ascii_lowercase();
+            counts.insert(c, counts.get(&c).unwrap_or(&0) + 1);
+        }
+    } else {
+        for &b in bytes {
+            counts.insert(b, counts.get(&b).unwrap_or(&0) + 1);
+        }
+    }
+
+    for &count in counts.values() {
+        let p: f32 = (count as f32) / (bytes.len() as f32);
+        entropy -= p * p.log(2.0);
+    }
+
+    entropy
+}
+
+/// Guesses the keyspace of a string from the characters it uses, and whether its case should be
+/// ignored: 64 for base64 and 128 for anything else. Hex strings are base64 as well, so the
+/// 16 (case insensitive) of hex is only used when passed as an explicit keyspace.
+pub fn guess_keyspace(bytes: &[u8]) -> (u32, bool) {
+    if is_base64_string(bytes) {
+        return (64, false);
+    };
+    if is_hex_string(bytes) {
+        return (16, true);
+    };
+    (128, false)
+}
+
+/// Because the Shannon entropy number alone does not have context of the keyspace, we use this
+/// function to determine the amount of entropy present in a string as a value between 0-1.
+/// Without a `keyspace` it is guessed by `guess_keyspace`, which also decides whether case is
+/// ignored. See https://stats.stackexchange.com/questions/281093/shannon-entropy-metric-entropy-and-relative-entropy
+pub fn calc_normalized_entropy(
+    bytes: &[u8],
+    keyspace: Option<u32>,
+    make_ascii_lowercase: bool,
+) -> f32 {
+    let (processed_keyspace, processed_lowercase): (u32, bool) = match keyspace {
+        Some(n) => (n, make_ascii_lowercase),
+        None => guess_keyspace(bytes),
+    };
+    let raw_entropy = calc_shannon_entropy(bytes, processed_lowercase);
+    raw_entropy / ((processed_keyspace as f32).log2())
+}
+
+/// Truncate a slice to the max_len, or returns the original slice when is shorter than that
+pub fn truncate_slice(word: &[u8], max_len: usize) -> &[u8] {
+    if word.len() > max_len {
+        return &word[..max_len];
+    }
+    word
+}
+
+/// Splits a line into words on any of the `word_split` characters. Consecutive split characters
+/// yield empty words.
+pub fn split_words<'a>(
+    line: &'a [u8],
+    word_split: &'a [u8],
+) -> impl Iterator<Item = &'a [u8]> + 'a {
+    line.split(move |x| word_split.contains(x))
+}
+
+/// The runs of `window` bytes of a word, starting `stride` bytes apart, plus the one ending the
+/// word so its end isn't left out. A word no longer than `window` is its only window.
+pub fn windows(word: &[u8], window: usize, stride: usize) -> impl Iterator<Item = &[u8]> {
+    let window = window.max(1);
+    let stride = stride.max(1);
+    let last = word.len().saturating_sub(window);
+    (0..=last)
+        .step_by(stride)
+        .chain((!last.is_multiple_of(stride)).then_some(last))
+        .map(move |start| &word[start..word.len().min(start + window)])
+}
+
+/// Splits a line into its runs of base64 characters (see `B64_ENCODE`), hex strings included,
+/// so that any other character ends a word. Unlike `split_words` it yields no empty words.
+pub fn split_tokens(line: &[u8]) -> impl Iterator<Item = &[u8]> {
+    line.split(|x| !B64_ENCODE.contains(x))
+        .filter(|token| !token.is_empty())
+}
+
+/// The highest normalized entropy of the words of a line at least `min_word_len` long, each
+/// truncated to `max_word_len`. 0 if there are no such words.
+pub fn max_word_entropy(
+    line: &[u8],
+    word_split: &[u8],
+    min_word_len: usize,
+    max_word_len: usize,
+    keyspace: Option<u32>,
+    make_ascii_lowercase: bool,
+) -> f32 {
+    split_words(line, word_split)
+        .filter(|word| word.len() >= min_word_len)
+        .map(|word| {
+            calc_normalized_entropy(
+                truncate_slice(word, max_word_len),
+                keyspace,
+                make_ascii_lowercase,
+            )
+        })
+        .fold(0.0, f32::max)
+}
+
+/// Like `max_word_entropy`, but words longer than `window` are scored by their most random
+/// window (see `windows`) rather than by their first `window` characters, so a secret embedded
+/// in a long token isn't diluted by the rest of it
+pub fn max_word_window_entropy(
+    line: &[u8],
+    word_split: &[u8],
+    min_word_len: usize,
+    window: usize,
+    stride: usize,
+    keyspace: Option<u32>,
+    make_ascii_lowercase: bool,
+) -> f32 {
+    split_words(line, word_split)
+        .filter(|word| word.len() >= min_word_len)
+        .flat_map(|word| windows(word, window, stride))
+        .map(|window| calc_normalized_entropy(window, keyspace, make_ascii_lowercase))
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_slice() {
+        let output = truncate_slice("secret: ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefg".as_bytes(), 10);
+        assert_eq!(output, "secret: AB".as_bytes())
+    }
+
+    #[test]
+    fn normalizes_entropy_by_keyspace() {
+        assert_eq!(calc_shannon_entropy(b"", false), 0.0);
+        assert_eq!(calc_shannon_entropy(b"abab", false), 1.0);
+        assert_eq!(calc_shannon_entropy(b"aAaA", false), 1.0);
+        assert_eq!(calc_shannon_entropy(b"aAaA", true), 0.0);
+        // 16 distinct characters carry 4 bits each, all of a hex character
+        assert_eq!(
+            calc_normalized_entropy(b"0123456789abcdef", Some(16), false),
+            1.0
+        );
+        assert_eq!(
+            calc_normalized_entropy(b"0123456789abcdef", Some(256), false),
+            0.5
+        );
+        assert_eq!(guess_keyspace(b"0123456789abcdef"), (64, false));
+        assert_eq!(guess_keyspace(b"0123456789ABCDEF"), (64, false));
+        assert_eq!(guess_keyspace(b"DEADBEEF-0000"), (128, false));
+        assert_eq!(guess_keyspace(b"a+b/c"), (64, false));
+        assert!(is_hex_string(b"09afAF") && !is_hex_string(b"09afg"));
+        assert!(is_base64_string(b"Zx8+/") && !is_base64_string(b"Zx8="));
+    }
+
+    #[test]
+    fn scores_a_line_by_its_most_random_word() {
+        let line = b"password = \"Zx8Kq2Lm9Vt4Rw7Ny3Bc6Fh1Jd5Sa0Eg\" (aaaaaaaaaaaaaaaaaaaa)";
+        let words: Vec<&[u8]> = split_words(line, WORD_SPLIT)
+            .filter(|w| !w.is_empty())
+            .collect();
+        assert_eq!(words.len(), 3);
+        let max = max_word_entropy(line, WORD_SPLIT, 5, 40, None, false);
+        assert_eq!(
+            max,
+            calc_normalized_entropy(b"Zx8Kq2Lm9Vt4Rw7Ny3Bc6Fh1Jd5Sa0Eg", None, false)
+        );
+        // too short to count
+        assert_eq!(max_word_entropy(line, WORD_SPLIT, 50, 60, None, false), 0.0);
+        // other split characters keep the secret and its key together
+        let joined = max_word_entropy(line, b"()", 5, 40, None, false);
+        assert!(joined < max);
+    }
+
+    #[test]
+    fn tokenizes_on_anything_but_base64() {
+        let tokens: Vec<&[u8]> = split_tokens(b"{\"k\":[\"ab+c/d\",0x1F]}; <x>").collect();
+        assert_eq!(tokens, vec![&b"k"[..], b"ab+c/d", b"0x1F", b"x"]);
+        assert_eq!(split_tokens(b"=;,").count(), 0);
+    }
+
+    #[test]
+    fn slides_windows_over_long_words() {
+        let starts = |word: &'static [u8], window, stride| -> Vec<&'static [u8]> {
+            windows(word, window, stride).collect()
+        };
+        assert_eq!(starts(b"abcdefg", 3, 2), vec![&b"abc"[..], b"cde", b"efg"]);
+        assert_eq!(starts(b"abcdef", 3, 2), vec![&b"abc"[..], b"cde", b"def"]);
+        assert_eq!(starts(b"ab", 3, 2), vec![&b"ab"[..]]);
+        assert_eq!(starts(b"abc", 1, 0).len(), 3);
+
+        let secret = "Zx8Kq2Lm9Vt4Rw7Ny3Bc6Fh1Jd5Sa0Eg";
+        let line = format!("token={}{}{}", "a".repeat(500), secret, "b".repeat(500));
+        let line = line.as_bytes();
+        let truncated = max_word_entropy(line, WORD_SPLIT, 10, 32, None, false);
+        let windowed = max_word_window_entropy(line, WORD_SPLIT, 10, 32, 1, None, false);
+        assert_eq!(
+            windowed,
+            calc_normalized_entropy(secret.as_bytes(), None, false)
+        );
+        assert!(truncated < 0.1 && windowed > 0.8);
+        // a coarser stride only catches part of the secret
+        let coarse = max_word_window_entropy(line, WORD_SPLIT, 10, 32, 7, None, false);
+        assert!(coarse < windowed && coarse > truncated);
+    }
+}
@@ -0,0 +1,310 @@
+//! The effective configuration printed by `--explain`, for debugging why something isn't caught.
+//!
+//! Instead of scanning, a hog run with `--explain` prints which rules are loaded and where each
+//! came from (the built-in rules, a `--regex` file, the PII pack or rules added by the hog such as
+//! a duroc_hog preset), the allowlist entries, the entropy settings and the output and
+//! enforcement settings, then the targets it would scan. For local paths handed to
+//! `SecretScannerBuilder::set_explain_paths` (duroc_hog's <FSPATH>) every file that would be
+//! scanned is listed, with the rules its path excludes and whether a `<GLOBAL>` path allowlist
+//! drops all of its findings.
+//!
+//! ```
+//! use rusty_hog_scanner::SecretScannerBuilder;
+//! let explanation = SecretScannerBuilder::new().set_pii(true).explain().unwrap();
+//! let slack = explanation.rules.iter().find(|r| r.name == "Slack Token").unwrap();
+//! assert_eq!(slack.source, "built-in rules");
+//! let ssn = explanation.rules.iter().find(|r| r.name == "US Social Security Number").unwrap();
+//! assert_eq!(ssn.source, "built-in PII rules");
+//! ```
+
+use crate::{SecretScanner, SecretScannerBuilder};
+use serde_derive::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Everything `--explain` prints
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Explanation {
+    pub rules: Vec<RuleExplanation>,
+    pub allowlist_source: String,
+    pub allowlists: BTreeMap<String, AllowListExplanation>,
+    pub entropy: EntropyExplanation,
+    pub settings: SettingsExplanation,
+    pub targets: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<FileExplanation>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RuleExplanation {
+    pub name: String,
+    pub source: String,
+    pub pattern: String,
+    pub severity: String,
+    pub category: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub entropy_threshold: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub validator: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub word_split: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub context_keywords: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub context_window: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exclude_pattern: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include_paths: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude_paths: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct AllowListExplanation {
+    pub patterns: Vec<String>,
+    pub paths: Vec<String>,
+    pub assigned_to: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct EntropyExplanation {
+    pub enabled: bool,
+    pub default_threshold: f32,
+    pub min_word_len: usize,
+    pub max_word_len: usize,
+    pub word_split: String,
+    pub tokenize: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_stride: Option<usize>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct SettingsExplanation {
+    pub case_insensitive: bool,
+    pub unicode: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decode_depth: Option<usize>,
+    pub correlations: Vec<String>,
+    pub policy: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+}
+
+/// A file a local target would have scanned
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct FileExplanation {
+    pub path: String,
+    /// Rules whose `include_paths`/`exclude_paths` leave this file out
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub skipped_rules: Vec<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub allowlisted: bool,
+}
+
+fn regex_strings(regexes: &[regex::bytes::Regex]) -> Vec<String> {
+    regexes.iter().map(|r| String::from(r.as_str())).collect()
+}
+
+/// The files under `path`, only those directly in it unless `recursive`
+fn list_files(path: &Path, recursive: bool, files: &mut Vec<String>) {
+    if path.is_file() {
+        files.push(path.to_string_lossy().into_owned());
+        return;
+    }
+    let Ok(entries) = fs::read_dir(path) else {
+        return;
+    };
+    let mut entries: Vec<_> = entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+    entries.sort();
+    for entry in entries {
+        if entry.is_file() {
+            files.push(entry.to_string_lossy().into_owned());
+        } else if recursive && entry.is_dir() && !entry.is_symlink() {
+            list_files(&entry, recursive, files);
+        }
+    }
+}
+
+fn explain_file(scanner: &SecretScanner, path: String) -> FileExplanation {
+    let skipped_rules = scanner
+        .regex_map
+        .iter()
+        .filter(|(_, rule)| !rule.applies_to_path(path.as_bytes()))
+        .map(|(name, _)| name.clone())
+        .collect();
+    let allowlisted = scanner.is_allowlisted_path("<GLOBAL>", path.as_bytes());
+    FileExplanation {
+        path,
+        skipped_rules,
+        allowlisted,
+    }
+}
+
+/// Explains a scanner built from `builder`, whose rules came from `rule_sources`
+pub(crate) fn explain(
+    builder: &SecretScannerBuilder,
+    scanner: &SecretScanner,
+    rule_sources: &BTreeMap<String, String>,
+) -> Explanation {
+    let rules = scanner
+        .regex_map
+        .iter()
+        .map(|(name, rule)| RuleExplanation {
+            name: name.clone(),
+            source: rule_sources.get(name).cloned().unwrap_or_default(),
+            pattern: String::from(rule.pattern_str()),
+            severity: rule.severity.to_string(),
+            category: rule.category.to_string(),
+            entropy_threshold: rule.entropy_threshold,
+            validator: rule.validator.map(|v| format!("{:?}", v).to_lowercase()),
+            word_split: rule
+                .word_split
+                .as_ref()
+                .map(|w| String::from_utf8_lossy(w).into_owned()),
+            context_keywords: rule
+                .context_keywords
+                .iter()
+                .map(|k| String::from_utf8_lossy(k).into_owned())
+                .collect(),
+            context_window: (!rule.context_keywords.is_empty() || rule.exclude_pattern.is_some())
+                .then_some(rule.context_window),
+            exclude_pattern: rule
+                .exclude_pattern
+                .as_ref()
+                .map(|r| String::from(r.as_str())),
+            include_paths: regex_strings(&rule.include_paths),
+            exclude_paths: regex_strings(&rule.exclude_paths),
+        })
+        .collect();
+    let allowlists = scanner
+        .allowlist_map
+        .iter()
+        .map(|(name, allowlist)| {
+            (
+                name.clone(),
+                AllowListExplanation {
+                    patterns: regex_strings(&allowlist.pattern_list),
+                    paths: regex_strings(&allowlist.path_list),
+                    assigned_to: regex_strings(&allowlist.assigned_to_list),
+                    authors: regex_strings(&allowlist.author_list),
+                    before: allowlist.before.map(|d| d.to_rfc3339()),
+                    after: allowlist.after.map(|d| d.to_rfc3339()),
+                },
+            )
+        })
+        .collect();
+    let mut files = Vec::new();
+    for path in builder.explain_paths.iter() {
+        list_files(Path::new(path), builder.explain_recursive, &mut files);
+    }
+    Explanation {
+        rules,
+        allowlist_source: match (&builder.allowlist_json_path, &builder.allowlist_json_str) {
+            (Some(p), _) => p.clone(),
+            (None, Some(_)) => String::from("JSON string"),
+            (None, None) if builder.default_allowlist => String::from("built-in allowlist"),
+            (None, None) => String::from("none"),
+        },
+        allowlists,
+        entropy: EntropyExplanation {
+            enabled: scanner.add_entropy_findings,
+            default_threshold: scanner.default_entropy_threshold,
+            min_word_len: scanner.entropy_min_word_len,
+            max_word_len: scanner.entropy_max_word_len,
+            word_split: String::from_utf8_lossy(&scanner.entropy_word_split).into_owned(),
+            tokenize: scanner.entropy_tokenize,
+            window: scanner.entropy_window,
+            window_stride: scanner
+                .entropy_window
+                .map(|_| scanner.entropy_window_stride),
+        },
+        settings: SettingsExplanation {
+            case_insensitive: scanner.case_insensitive,
+            unicode: scanner.unicode,
+            min_severity: scanner.min_severity.map(|s| s.to_string()),
+            decode_depth: builder.decode_encoded.then_some(builder.decode_depth),
+            correlations: scanner
+                .correlations
+                .iter()
+                .map(|c| c.name.clone())
+                .collect(),
+            policy: scanner.policy.is_some(),
+            output: scanner.output_path.clone(),
+        },
+        targets: builder.scan_targets.clone(),
+        files: files
+            .into_iter()
+            .map(|path| explain_file(scanner, path))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_files_with_their_rules() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("src")).unwrap();
+        fs::write(dir.path().join("src").join("app.py"), "").unwrap();
+        fs::write(dir.path().join("package-lock.json"), "").unwrap();
+        let rules =
+            r#"{ "Test Token": { "pattern": "tok_[a-z]{8}", "exclude_paths": ["\\.py$"] } }"#;
+        let allowlist = dir.path().join("allowlist.json");
+        fs::write(
+            &allowlist,
+            r#"{ "<GLOBAL>": { "patterns": [], "paths": ["package-lock\\.json$"] } }"#,
+        )
+        .unwrap();
+        let root = dir.path().to_str().unwrap();
+        let explanation = SecretScannerBuilder::new()
+            .add_rules_json_str(rules)
+            .set_allowlist_json_path(allowlist.to_str().unwrap())
+            .set_explain_paths(vec![String::from(root)], true)
+            .explain()
+            .unwrap();
+
+        let rule = explanation
+            .rules
+            .iter()
+            .find(|r| r.name == "Test Token")
+            .unwrap();
+        assert_eq!(rule.source, "additional rules");
+        assert_eq!(rule.exclude_paths, vec![String::from("\\.py$")]);
+        assert_eq!(explanation.allowlist_source, allowlist.to_str().unwrap());
+        let files: Vec<&str> = explanation.files.iter().map(|f| f.path.as_str()).collect();
+        assert_eq!(files.len(), 3);
+        let app = explanation
+            .files
+            .iter()
+            .find(|f| f.path.ends_with("app.py"))
+            .unwrap();
+        assert!(app.skipped_rules.contains(&String::from("Test Token")));
+        let lock = explanation
+            .files
+            .iter()
+            .find(|f| f.path.ends_with("package-lock.json"))
+            .unwrap();
+        assert!(lock.allowlisted);
+        assert!(!lock.skipped_rules.contains(&String::from("Test Token")));
+
+        let explanation = SecretScannerBuilder::new()
+            .set_explain_paths(vec![String::from(root)], false)
+            .explain()
+            .unwrap();
+        assert_eq!(explanation.files.len(), 2);
+    }
+}
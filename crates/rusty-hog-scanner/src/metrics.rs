@@ -0,0 +1,279 @@
+//! Prometheus metrics: a registry of counters, gauges and summaries rendered in the text
+//! exposition format, and a minimal HTTP server answering `GET /metrics` with them. The batch
+//! runner's scheduler mode (`iberian_hog --daemon --metrics-addr`) uses it to report scanner
+//! health.
+//!
+//! ```
+//! use rusty_hog_scanner::metrics::{Kind, Metrics};
+//! let metrics = Metrics::new();
+//! metrics.describe("rusty_hog_scans_total", Kind::Counter, "Scans run");
+//! metrics.inc("rusty_hog_scans_total", &[("status", "ok")]);
+//! metrics.inc("rusty_hog_scans_total", &[("status", "ok")]);
+//! let text = metrics.render();
+//! assert!(text.starts_with("# HELP rusty_hog_scans_total Scans run\n"));
+//! assert!(text.contains("# TYPE rusty_hog_scans_total counter\n"));
+//! assert!(text.ends_with("rusty_hog_scans_total{status=\"ok\"} 2\n"));
+//! ```
+
+use log::{debug, error, info};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// The content type of the text exposition format
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+// Requests are small; anything longer is not a scrape
+const MAX_REQUEST: usize = 8192;
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The type of a metric
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Counter,
+    Gauge,
+    /// Observations, exposed as their `_sum` and `_count`
+    Summary,
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Summary => "summary",
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Family {
+    kind: Option<Kind>,
+    help: String,
+    // rendered labels (`{a="b"}` or nothing) and sample name suffix to value
+    samples: BTreeMap<(String, &'static str), f64>,
+}
+
+/// A set of metrics, safe to update from the thread scanning while the server renders it
+#[derive(Debug, Default)]
+pub struct Metrics {
+    families: Mutex<BTreeMap<String, Family>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the type and help text of a metric
+    pub fn describe(&self, name: &str, kind: Kind, help: &str) {
+        let mut families = self.families.lock().unwrap();
+        let family = families.entry(String::from(name)).or_default();
+        family.kind = Some(kind);
+        family.help = String::from(help);
+    }
+
+    /// Adds one to a counter
+    pub fn inc(&self, name: &str, labels: &[(&str, &str)]) {
+        self.add(name, labels, 1.0);
+    }
+
+    /// Adds to a counter
+    pub fn add(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, labels, "", |v| *v += value);
+    }
+
+    /// Sets a gauge
+    pub fn set(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, labels, "", |v| *v = value);
+    }
+
+    /// Adds an observation to a summary
+    pub fn observe(&self, name: &str, labels: &[(&str, &str)], value: f64) {
+        self.update(name, labels, "_sum", |v| *v += value);
+        self.update(name, labels, "_count", |v| *v += 1.0);
+    }
+
+    fn update(
+        &self,
+        name: &str,
+        labels: &[(&str, &str)],
+        suffix: &'static str,
+        f: impl FnOnce(&mut f64),
+    ) {
+        let mut families = self.families.lock().unwrap();
+        let family = families.entry(String::from(name)).or_default();
+        f(family
+            .samples
+            .entry((render_labels(labels), suffix))
+            .or_insert(0.0));
+    }
+
+    /// The metrics in the text exposition format
+    pub fn render(&self) -> String {
+        let families = self.families.lock().unwrap();
+        let mut text = String::new();
+        for (name, family) in families.iter() {
+            if family.samples.is_empty() {
+                continue;
+            }
+            if let Some(kind) = family.kind {
+                text.push_str(&format!(
+                    "# HELP {} {}\n",
+                    name,
+                    escape(&family.help, false)
+                ));
+                text.push_str(&format!("# TYPE {} {}\n", name, kind));
+            }
+            for ((labels, suffix), value) in &family.samples {
+                text.push_str(&format!("{}{}{} {}\n", name, suffix, labels, value));
+            }
+        }
+        text
+    }
+
+    /// Serves the metrics at `http://<addr>/metrics` from a background thread, returning the
+    /// address it listens on. Each connection is answered on its own thread, so a client that
+    /// is slow to send its request doesn't hold up the scrapes of others.
+    pub fn serve(self: Arc<Self>, addr: &str) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(addr)?;
+        let local_addr = listener.local_addr()?;
+        info!("Serving metrics at http://{}/metrics", local_addr);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        debug!("failed to accept metrics request: {}", e);
+                        continue;
+                    }
+                };
+                let metrics = self.clone();
+                thread::spawn(move || {
+                    if let Err(e) = metrics.respond(stream) {
+                        debug!("metrics request failed: {}", e);
+                    }
+                });
+            }
+            error!("Stopped serving metrics");
+        });
+        Ok(local_addr)
+    }
+
+    fn respond(&self, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        let mut request = Vec::new();
+        let mut buffer = [0; 1024];
+        while !request.windows(4).any(|w| w == b"\r\n\r\n") && request.len() < MAX_REQUEST {
+            let n = stream.read(&mut buffer)?;
+            if n == 0 {
+                break;
+            }
+            request.extend_from_slice(&buffer[..n]);
+        }
+        let request = String::from_utf8_lossy(&request);
+        let mut request_line = request.lines().next().unwrap_or_default().split(' ');
+        let (status, body) = match (request_line.next(), request_line.next()) {
+            (Some("GET"), Some(path)) if path.split('?').next() == Some("/metrics") => {
+                ("200 OK", self.render())
+            }
+            (Some("GET"), _) => ("404 Not Found", String::from("Not found, see /metrics\n")),
+            _ => ("405 Method Not Allowed", String::new()),
+        };
+        write!(
+            stream,
+            "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            CONTENT_TYPE,
+            body.len(),
+            body
+        )?;
+        stream.flush()
+    }
+}
+
+// e.g. `{severity="high",target="repo"}`, sorted by label name
+fn render_labels(labels: &[(&str, &str)]) -> String {
+    if labels.is_empty() {
+        return String::new();
+    }
+    let mut labels = labels.to_vec();
+    labels.sort();
+    let pairs: Vec<String> = labels
+        .iter()
+        .map(|(k, v)| format!("{}=\"{}\"", k, escape(v, true)))
+        .collect();
+    format!("{{{}}}", pairs.join(","))
+}
+
+// Escapes backslashes and line breaks, and double quotes in label values
+fn escape(value: &str, quotes: bool) -> String {
+    let mut escaped = value.replace('\\', "\\\\").replace('\n', "\\n");
+    if quotes {
+        escaped = escaped.replace('"', "\\\"");
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_are_rendered_and_served() {
+        let metrics = Arc::new(Metrics::new());
+        metrics.describe(
+            "rusty_hog_scan_duration_seconds",
+            Kind::Summary,
+            "Scan durations",
+        );
+        metrics.describe("rusty_hog_findings", Kind::Gauge, "Findings\nby severity");
+        metrics.observe("rusty_hog_scan_duration_seconds", &[("target", "a")], 1.5);
+        metrics.observe("rusty_hog_scan_duration_seconds", &[("target", "a")], 2.0);
+        metrics.set(
+            "rusty_hog_findings",
+            &[("target", "say \"hi\"\\"), ("severity", "high")],
+            3.0,
+        );
+        metrics.set(
+            "rusty_hog_findings",
+            &[("severity", "low"), ("target", "b")],
+            1.0,
+        );
+        metrics.set(
+            "rusty_hog_findings",
+            &[("severity", "low"), ("target", "b")],
+            0.0,
+        );
+        // described but never updated
+        metrics.describe("rusty_hog_unused", Kind::Counter, "Unused");
+        let expected = "# HELP rusty_hog_findings Findings\\nby severity\n\
+                        # TYPE rusty_hog_findings gauge\n\
+                        rusty_hog_findings{severity=\"high\",target=\"say \\\"hi\\\"\\\\\"} 3\n\
+                        rusty_hog_findings{severity=\"low\",target=\"b\"} 0\n\
+                        # HELP rusty_hog_scan_duration_seconds Scan durations\n\
+                        # TYPE rusty_hog_scan_duration_seconds summary\n\
+                        rusty_hog_scan_duration_seconds_count{target=\"a\"} 2\n\
+                        rusty_hog_scan_duration_seconds_sum{target=\"a\"} 3.5\n";
+        assert_eq!(metrics.render(), expected);
+
+        let addr = metrics.serve("127.0.0.1:0").unwrap();
+        let get = |path: &str| {
+            let mut stream = TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        // a client that connects and never sends its request doesn't block the others
+        let _idle = TcpStream::connect(addr).unwrap();
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains(CONTENT_TYPE));
+        assert!(response.ends_with(expected));
+        assert!(get("/").starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,288 @@
+//! Uploads for `--outputfile s3://bucket/key` and `--outputfile gs://bucket/key`, so a scan in a
+//! container can ship its findings without a separate upload step.
+//!
+//! The findings are written to a temporary directory first, exactly as they would be to a local
+//! file, and every file written there (the output, or the parts and index of `--output-split`,
+//! `.gz` suffixed with `--output-compress`) is then uploaded next to the destination key.
+//!
+//! S3 uploads use the AWS SDK's default credential chain, as berkshire_hog does: the
+//! environment, the shared config and credentials files (`AWS_PROFILE`), SSO, a web identity
+//! token, then the ECS or EC2 instance metadata. The region comes from `AWS_REGION`, the profile
+//! or `AWS_DEFAULT_REGION`, and defaults to us-east-1. `AWS_ENDPOINT` (or the SDK's
+//! `AWS_ENDPOINT_URL`) points the upload at an S3 compatible store, which is addressed path
+//! style. GCS uploads use the access token in `GOOGLE_OAUTH_ACCESS_TOKEN`, or the service account
+//! of the GCE or GKE metadata server.
+//!
+//! The uploads need the `upload` feature; without it, object URIs are still recognized so that
+//! their output fails with an error instead of being written to a local `s3:` directory.
+//!
+//! ```
+//! use rusty_hog_scanner::object_storage::{is_object_uri, ObjectStore, ObjectUri};
+//! let uri: ObjectUri = "s3://scans/nightly/findings.json".parse().unwrap();
+//! assert_eq!(uri.store, ObjectStore::S3);
+//! assert_eq!(uri.bucket, "scans");
+//! assert_eq!(uri.key, "nightly/findings.json");
+//! assert_eq!(uri.with_file_name("findings.0001.json").key, "nightly/findings.0001.json");
+//! assert!(is_object_uri("gs://scans/findings.json"));
+//! assert!(!is_object_uri("findings.json"));
+//! ```
+
+use anyhow::Result;
+#[cfg(feature = "upload")]
+use aws_sdk_s3::{config::Region, error::DisplayErrorContext, primitives::ByteStream};
+#[cfg(feature = "upload")]
+use log::{self, debug};
+#[cfg(feature = "upload")]
+use serde_derive::Deserialize;
+use simple_error::SimpleError;
+use std::fmt;
+#[cfg(feature = "upload")]
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+#[cfg(feature = "upload")]
+use std::thread;
+
+#[cfg(feature = "upload")]
+const GCS_UPLOAD_URL: &str = "https://storage.googleapis.com/upload/storage/v1/b";
+#[cfg(feature = "upload")]
+const GCE_TOKEN_URL: &str =
+    "http://metadata.google.internal/computeMetadata/v1/instance/service-accounts/default/token";
+#[cfg(feature = "upload")]
+const DEFAULT_AWS_REGION: &str = "us-east-1";
+
+/// Where an object URI points
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectStore {
+    S3,
+    Gcs,
+}
+
+impl ObjectStore {
+    fn scheme(&self) -> &'static str {
+        match self {
+            ObjectStore::S3 => "s3://",
+            ObjectStore::Gcs => "gs://",
+        }
+    }
+}
+
+/// An `s3://bucket/key` or `gs://bucket/key` output destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectUri {
+    pub store: ObjectStore,
+    pub bucket: String,
+    pub key: String,
+}
+
+/// Whether an output path should be uploaded rather than written locally
+pub fn is_object_uri(path: &str) -> bool {
+    path.starts_with(ObjectStore::S3.scheme()) || path.starts_with(ObjectStore::Gcs.scheme())
+}
+
+impl FromStr for ObjectUri {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (store, rest) = if let Some(rest) = s.strip_prefix(ObjectStore::S3.scheme()) {
+            (ObjectStore::S3, rest)
+        } else if let Some(rest) = s.strip_prefix(ObjectStore::Gcs.scheme()) {
+            (ObjectStore::Gcs, rest)
+        } else {
+            return Err(SimpleError::new(format!(
+                "{:?} is not an s3:// or gs:// URI",
+                s
+            )));
+        };
+        match rest.split_once('/') {
+            Some((bucket, key)) if !bucket.is_empty() && !key.is_empty() && !key.ends_with('/') => {
+                Ok(ObjectUri {
+                    store,
+                    bucket: String::from(bucket),
+                    key: String::from(key),
+                })
+            }
+            _ => Err(SimpleError::new(format!(
+                "{:?} needs a bucket and an object key, like {}bucket/findings.json",
+                s,
+                store.scheme()
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ObjectUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}/{}", self.store.scheme(), self.bucket, self.key)
+    }
+}
+
+impl ObjectUri {
+    /// The last segment of the key, the name the output is written under locally
+    pub fn file_name(&self) -> &str {
+        self.key.rsplit('/').next().unwrap_or(&self.key)
+    }
+
+    /// The object next to this one called `file_name`
+    pub fn with_file_name(&self, file_name: &str) -> ObjectUri {
+        let key = match self.key.rsplit_once('/') {
+            Some((prefix, _)) => format!("{}/{}", prefix, file_name),
+            None => String::from(file_name),
+        };
+        ObjectUri {
+            key,
+            ..self.clone()
+        }
+    }
+
+    /// Uploads the local file to this object
+    #[cfg(feature = "upload")]
+    pub fn upload(&self, local_path: &Path) -> Result<()> {
+        let content = fs::read(local_path)?;
+        let content_type = content_type(self.file_name());
+        debug!(
+            "Uploading {} bytes of {} to {}",
+            content.len(),
+            content_type,
+            self
+        );
+        match self.store {
+            ObjectStore::S3 => {
+                // the SDK call runs on a tokio runtime of its own, which can't be started on a
+                // thread that is already running one
+                let uri = self.clone();
+                match thread::spawn(move || uri.put_s3(content, content_type)).join() {
+                    Ok(result) => result,
+                    Err(_) => Err(SimpleError::new(format!("upload to {} panicked", self)).into()),
+                }
+            }
+            ObjectStore::Gcs => self.put_gcs(&content, content_type),
+        }
+    }
+
+    #[cfg(not(feature = "upload"))]
+    pub fn upload(&self, _local_path: &Path) -> Result<()> {
+        Err(anyhow::anyhow!(
+            "--outputfile {} needs rusty-hog built with the upload feature",
+            self
+        ))
+    }
+
+    #[cfg(feature = "upload")]
+    fn put_s3(&self, content: Vec<u8>, content_type: &str) -> Result<()> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        runtime.block_on(async {
+            let config = aws_config::load_defaults(aws_config::BehaviorVersion::latest()).await;
+            let mut s3_config = aws_sdk_s3::config::Builder::from(&config);
+            if config.region().is_none() {
+                let region = std::env::var("AWS_DEFAULT_REGION")
+                    .unwrap_or_else(|_| String::from(DEFAULT_AWS_REGION));
+                s3_config = s3_config.region(Region::new(region));
+            }
+            if let Ok(endpoint) = std::env::var("AWS_ENDPOINT") {
+                // S3 compatible stores rarely serve buckets as subdomains
+                s3_config = s3_config.endpoint_url(endpoint).force_path_style(true);
+            }
+            aws_sdk_s3::Client::from_conf(s3_config.build())
+                .put_object()
+                .bucket(&self.bucket)
+                .key(&self.key)
+                .content_type(content_type)
+                .body(ByteStream::from(content))
+                .send()
+                .await
+                .map_err(|e| {
+                    SimpleError::new(format!(
+                        "upload to {} failed: {}",
+                        self,
+                        DisplayErrorContext(&e)
+                    ))
+                })?;
+            Ok(())
+        })
+    }
+
+    #[cfg(feature = "upload")]
+    fn put_gcs(&self, content: &[u8], content_type: &str) -> Result<()> {
+        let response = attohttpc::post(format!("{}/{}/o", GCS_UPLOAD_URL, self.bucket))
+            .param("uploadType", "media")
+            .param("name", &self.key)
+            .bearer_auth(gcs_access_token()?)
+            .header(attohttpc::header::CONTENT_TYPE, content_type)
+            .bytes(content)
+            .send()?;
+        if !response.is_success() {
+            let status = response.status();
+            return Err(SimpleError::new(format!(
+                "upload to {} failed with HTTP {}: {}",
+                self,
+                status,
+                response.text().unwrap_or_default()
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "upload")]
+#[derive(Deserialize)]
+struct GceToken {
+    access_token: String,
+}
+
+/// The token in `GOOGLE_OAUTH_ACCESS_TOKEN`, or the metadata server's service account token
+#[cfg(feature = "upload")]
+fn gcs_access_token() -> Result<String> {
+    if let Ok(token) = std::env::var("GOOGLE_OAUTH_ACCESS_TOKEN") {
+        return Ok(token);
+    }
+    let response = attohttpc::get(GCE_TOKEN_URL)
+        .header("Metadata-Flavor", "Google")
+        .send()
+        .map_err(|e| {
+            SimpleError::new(format!(
+                "no GOOGLE_OAUTH_ACCESS_TOKEN and no metadata server to get one from: {}",
+                e
+            ))
+        })?;
+    Ok(response
+        .error_for_status()?
+        .json::<GceToken>()?
+        .access_token)
+}
+
+#[cfg(feature = "upload")]
+fn content_type(file_name: &str) -> &'static str {
+    if file_name.ends_with(".gz") {
+        "application/gzip"
+    } else if file_name.ends_with(".json") {
+        "application/json"
+    } else {
+        "text/plain"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_object_uris() {
+        let uri: ObjectUri = "gs://scans/findings.json.gz".parse().unwrap();
+        assert_eq!(uri.store, ObjectStore::Gcs);
+        assert_eq!(uri.file_name(), "findings.json.gz");
+        assert_eq!(
+            uri.with_file_name("findings.json").to_string(),
+            "gs://scans/findings.json"
+        );
+        #[cfg(feature = "upload")]
+        assert_eq!(content_type(uri.file_name()), "application/gzip");
+        assert!("s3://scans".parse::<ObjectUri>().is_err());
+        assert!("s3://scans/".parse::<ObjectUri>().is_err());
+        assert!("s3:///findings.json".parse::<ObjectUri>().is_err());
+        assert!("findings.json".parse::<ObjectUri>().is_err());
+    }
+}
@@ -0,0 +1,278 @@
+//! Chunked output for `--output-split`, for scans with too many findings for one JSON document.
+//!
+//! Findings are serialized one at a time into numbered part files next to the output path
+//! (`findings.json` is split into `findings.0001.json`, `findings.0002.json`, ...), starting a
+//! new part every N findings or once a part reaches N megabytes. Each part is a JSON array on its
+//! own. The output path itself gets an index listing the parts and how many findings each holds.
+//! With `--output-compress` the parts and the index are gzip-compressed, and their sizes in the
+//! index and the megabyte limit are those of the uncompressed JSON.
+//!
+//! ```
+//! use rusty_hog_scanner::output_split::{part_path, OutputSplit};
+//! assert_eq!("5000".parse::<OutputSplit>().unwrap(), OutputSplit::Findings(5000));
+//! assert_eq!("50MB".parse::<OutputSplit>().unwrap(), OutputSplit::Megabytes(50));
+//! assert_eq!(part_path("out/findings.json", 3), "out/findings.0003.json");
+//! ```
+
+use crate::compression::{self, OutputWriter};
+use anyhow::Result;
+use log::{self, debug};
+use serde::Serialize;
+use simple_error::SimpleError;
+use std::fmt;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::str::FromStr;
+
+const BYTES_PER_MEGABYTE: u64 = 1024 * 1024;
+
+/// When `--output-split` starts a new part
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputSplit {
+    /// Every N findings
+    Findings(usize),
+    /// Before a part grows past N megabytes
+    Megabytes(u64),
+}
+
+impl FromStr for OutputSplit {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_ascii_lowercase();
+        let split = match s.strip_suffix("mb") {
+            Some(mb) => mb.trim().parse().ok().map(OutputSplit::Megabytes),
+            None => s.parse().ok().map(OutputSplit::Findings),
+        };
+        match split {
+            Some(OutputSplit::Findings(0)) | Some(OutputSplit::Megabytes(0)) | None => Err(
+                SimpleError::new(format!("invalid output split {:?}, expected N or NMB", s)),
+            ),
+            Some(split) => Ok(split),
+        }
+    }
+}
+
+impl fmt::Display for OutputSplit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OutputSplit::Findings(n) => write!(f, "{}", n),
+            OutputSplit::Megabytes(n) => write!(f, "{}MB", n),
+        }
+    }
+}
+
+/// A part file in the index
+#[derive(serde_derive::Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutputPart {
+    pub path: String,
+    pub findings: usize,
+    /// Uncompressed size
+    pub bytes: u64,
+}
+
+/// The index written to the output path
+#[derive(serde_derive::Serialize, Debug, Clone, PartialEq, Eq, Default)]
+pub struct OutputIndex {
+    pub findings: usize,
+    pub parts: Vec<OutputPart>,
+}
+
+/// The path of the `part`th part (from 1) of `output_path`, numbered before its extension
+pub fn part_path(output_path: &str, part: usize) -> String {
+    let path = Path::new(output_path);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!(
+                "{}.{:04}.{}",
+                stem.to_string_lossy(),
+                part,
+                extension.to_string_lossy()
+            ))
+            .to_string_lossy()
+            .into_owned(),
+        _ => format!("{}.{:04}", output_path, part),
+    }
+}
+
+/// The part being written
+struct OpenPart {
+    writer: OutputWriter<File>,
+    part: OutputPart,
+}
+
+impl OpenPart {
+    fn create(path: &str, compress: bool) -> Result<Self> {
+        let path = compression::output_path(path, compress);
+        debug!("Writing findings to {}", path);
+        let mut writer = OutputWriter::new(File::create(&path)?, compress);
+        writer.write_all(b"[")?;
+        Ok(Self {
+            writer,
+            part: OutputPart {
+                path,
+                findings: 0,
+                bytes: 1,
+            },
+        })
+    }
+
+    fn write(&mut self, finding: &[u8], pretty_print: bool) -> Result<()> {
+        let separator: &[u8] = match (self.part.findings, pretty_print) {
+            (0, false) => b"",
+            (0, true) => b"\n",
+            (_, false) => b",",
+            (_, true) => b",\n",
+        };
+        self.writer.write_all(separator)?;
+        self.writer.write_all(finding)?;
+        self.part.findings += 1;
+        self.part.bytes += (separator.len() + finding.len()) as u64;
+        Ok(())
+    }
+
+    fn finish(mut self, pretty_print: bool) -> Result<OutputPart> {
+        let end: &[u8] = if pretty_print { b"\n]" } else { b"]" };
+        self.writer.write_all(end)?;
+        self.writer.finish()?;
+        self.part.bytes += end.len() as u64;
+        Ok(self.part)
+    }
+}
+
+/// Writes the findings to part files as they are taken from `findings` and serialized, then the
+/// index of the parts to `output_path`. A finding larger than a megabyte limit gets a part of its
+/// own. The index lists the parts next to `listed_path`, which is the output path unless the
+/// files are uploaded somewhere else afterwards.
+pub fn write_parts<I>(
+    output_path: &str,
+    listed_path: &str,
+    split: OutputSplit,
+    findings: I,
+    pretty_print: bool,
+    compress: bool,
+) -> Result<OutputIndex>
+where
+    I: IntoIterator,
+    I::Item: Serialize,
+{
+    let mut index = OutputIndex::default();
+    let mut open: Option<OpenPart> = None;
+    for finding in findings {
+        let bytes = if pretty_print {
+            serde_json::to_vec_pretty(&finding)?
+        } else {
+            serde_json::to_vec(&finding)?
+        };
+        let full = open.as_ref().is_some_and(|o| match split {
+            OutputSplit::Findings(n) => o.part.findings >= n,
+            OutputSplit::Megabytes(mb) => {
+                // the separator and closing bracket take at most 4 more bytes
+                o.part.bytes + bytes.len() as u64 + 4 > mb * BYTES_PER_MEGABYTE
+            }
+        });
+        if full {
+            index.parts.push(open.take().unwrap().finish(pretty_print)?);
+        }
+        let part = match open.as_mut() {
+            Some(part) => part,
+            None => open.insert(OpenPart::create(
+                &part_path(output_path, index.parts.len() + 1),
+                compress,
+            )?),
+        };
+        part.write(&bytes, pretty_print)?;
+        index.findings += 1;
+    }
+    if let Some(part) = open {
+        index.parts.push(part.finish(pretty_print)?);
+    }
+    if listed_path != output_path {
+        for (n, part) in index.parts.iter_mut().enumerate() {
+            part.path = compression::output_path(&part_path(listed_path, n + 1), compress);
+        }
+    }
+
+    let index_json = if pretty_print {
+        serde_json::to_vec_pretty(&index)?
+    } else {
+        serde_json::to_vec(&index)?
+    };
+    let mut writer = OutputWriter::create(output_path, compress)?;
+    writer.write_all(&index_json)?;
+    writer.finish()?;
+    Ok(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use serde_json::{json, Value};
+
+    #[test]
+    fn parses_splits() {
+        assert_eq!(
+            "100 mb".parse::<OutputSplit>().unwrap(),
+            OutputSplit::Megabytes(100)
+        );
+        assert!("0".parse::<OutputSplit>().is_err());
+        assert!("ten".parse::<OutputSplit>().is_err());
+        assert_eq!(part_path("findings", 12), "findings.0012");
+    }
+
+    #[test]
+    fn writes_parts_and_index() {
+        let dir = tempfile::tempdir().unwrap();
+        let output = dir.path().join("findings.json");
+        let output = output.to_str().unwrap();
+        let findings: Vec<Value> = (0..5)
+            .map(|i| json!({"reason": "Slack Token", "path": format!("file{}", i)}))
+            .collect();
+
+        let index = write_parts(
+            output,
+            output,
+            OutputSplit::Findings(2),
+            &findings,
+            true,
+            false,
+        )
+        .unwrap();
+        assert_eq!(index.findings, 5);
+        let counts: Vec<usize> = index.parts.iter().map(|p| p.findings).collect();
+        assert_eq!(counts, vec![2, 2, 1]);
+        let mut parsed: Vec<Value> = Vec::new();
+        for part in index.parts.iter() {
+            let text = std::fs::read(&part.path).unwrap();
+            assert_eq!(text.len() as u64, part.bytes);
+            let values: Vec<Value> = serde_json::from_slice(&text).unwrap();
+            parsed.extend(values);
+        }
+        assert_eq!(parsed, findings);
+        let written: Value = serde_json::from_slice(&std::fs::read(output).unwrap()).unwrap();
+        assert_eq!(written["parts"][2]["path"], json!(part_path(output, 3)));
+
+        // every finding is about 40 bytes, so a megabyte holds all of them; they are taken from
+        // the iterator one at a time
+        let listed = "s3://scans/findings.json";
+        let index = write_parts(
+            output,
+            listed,
+            OutputSplit::Megabytes(1),
+            findings.iter(),
+            false,
+            true,
+        )
+        .unwrap();
+        assert_eq!(index.parts.len(), 1);
+        assert_eq!(index.parts[0].path, "s3://scans/findings.0001.json.gz");
+        let part = File::open(format!("{}.gz", part_path(output, 1))).unwrap();
+        let values: Vec<Value> = serde_json::from_reader(GzDecoder::new(part)).unwrap();
+        assert_eq!(values, findings);
+        let index_file = File::open(format!("{}.gz", output)).unwrap();
+        let written: Value = serde_json::from_reader(GzDecoder::new(index_file)).unwrap();
+        assert_eq!(written["findings"], json!(5));
+    }
+}
@@ -0,0 +1,256 @@
+//! Enforcement policies for `--policy`, kept apart from the rules that detect secrets.
+//!
+//! A policy maps rules, data categories and severities to what should happen to their findings:
+//! `ignore` drops them from the output, `warn` (or `report`) outputs them, and `fail` (or
+//! `fail-build`) outputs them and makes the hog exit with `FAIL_EXIT_CODE` once it is done, as
+//! `output_findings` returns `Verdict::Fail` for them. The
+//! action of a rule wins over that of its category, which wins over that of its severity;
+//! findings that match none get the `default` action, `warn` unless the policy says otherwise.
+//! Every output finding gets a `policy` property with its action.
+//!
+//! A policy with `age_severity` also adjusts the severity of findings by the `source_timestamp`
+//! of their content, since fresh leaks are more likely to be live: findings changed less than
+//! `recent_days` (7 by default) ago are raised a level, and findings unchanged for more than
+//! `stale_days` (365 by default) are lowered one. Findings without a `source_timestamp` keep
+//! their severity, and adjusted ones keep the severity of their rule as `base_severity`. The
+//! adjusted severity is the one `--min-severity` and the `severities` of the policy see.
+//!
+//! ```
+//! use rusty_hog_scanner::classification::Category;
+//! use rusty_hog_scanner::policy::{Action, Policy};
+//! use rusty_hog_scanner::Severity;
+//! let policy: Policy = serde_json::from_str(r#"{
+//!     "rules": { "Email address": "ignore" },
+//!     "categories": { "pii": "warn" },
+//!     "severities": { "high": "fail", "critical": "fail" }
+//! }"#).unwrap();
+//! assert_eq!(policy.action("Email address", Category::Pii, Severity::High), Action::Ignore);
+//! assert_eq!(policy.action("Slack Token", Category::Credentials, Severity::High), Action::Fail);
+//! assert_eq!(policy.action("Generic Secret", Category::Credentials, Severity::Low), Action::Warn);
+//!
+//! let policy: Policy = serde_json::from_str(r#"{ "age_severity": { "recent_days": 3 } }"#).unwrap();
+//! let age = policy.age_severity.unwrap();
+//! let now = "2024-06-10T00:00:00Z".parse().unwrap();
+//! assert_eq!(age.adjust(Severity::High, "2024-06-08T12:00:00Z", now), Severity::Critical);
+//! assert_eq!(age.adjust(Severity::High, "2022-01-01T00:00:00Z", now), Severity::Medium);
+//! ```
+
+use crate::classification::Category;
+use crate::Severity;
+use chrono::{DateTime, Duration, Utc};
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// The exit code of a hog whose findings fail the policy: 3, so that CI can tell it from a hog
+/// that failed to scan (1) and from a clean scan (0)
+pub const FAIL_EXIT_CODE: i32 = 3;
+
+// Defaults of `AgeSeverity`
+const DEFAULT_RECENT_DAYS: u32 = 7;
+const DEFAULT_STALE_DAYS: u32 = 365;
+
+/// What happens to the findings of a rule
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Ignore,
+    #[default]
+    #[serde(alias = "report")]
+    Warn,
+    #[serde(alias = "fail-build")]
+    Fail,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Action::Ignore => "ignore",
+            Action::Warn => "warn",
+            Action::Fail => "fail",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A policy file, see the module documentation
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default)]
+    pub default: Action,
+    #[serde(default)]
+    pub rules: BTreeMap<String, Action>,
+    #[serde(default)]
+    pub categories: BTreeMap<Category, Action>,
+    #[serde(default)]
+    pub severities: BTreeMap<Severity, Action>,
+    #[serde(default)]
+    pub age_severity: Option<AgeSeverity>,
+}
+
+/// The `age_severity` thresholds of a policy, see the module documentation
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct AgeSeverity {
+    #[serde(default = "default_recent_days")]
+    pub recent_days: u32,
+    #[serde(default = "default_stale_days")]
+    pub stale_days: u32,
+}
+
+impl Default for AgeSeverity {
+    fn default() -> Self {
+        AgeSeverity {
+            recent_days: DEFAULT_RECENT_DAYS,
+            stale_days: DEFAULT_STALE_DAYS,
+        }
+    }
+}
+
+fn default_recent_days() -> u32 {
+    DEFAULT_RECENT_DAYS
+}
+
+fn default_stale_days() -> u32 {
+    DEFAULT_STALE_DAYS
+}
+
+impl AgeSeverity {
+    /// The severity of a finding whose content has the `source_timestamp` at `now`; timestamps
+    /// that aren't RFC 3339 leave it as it is
+    pub fn adjust(
+        &self,
+        severity: Severity,
+        source_timestamp: &str,
+        now: DateTime<Utc>,
+    ) -> Severity {
+        let Ok(time) = DateTime::parse_from_rfc3339(source_timestamp) else {
+            return severity;
+        };
+        let age = now.signed_duration_since(time);
+        if age < Duration::days(self.recent_days.into()) {
+            severity.raised()
+        } else if age > Duration::days(self.stale_days.into()) {
+            severity.lowered()
+        } else {
+            severity
+        }
+    }
+}
+
+impl Policy {
+    /// The action for a finding of `rule`
+    pub fn action(&self, rule: &str, category: Category, severity: Severity) -> Action {
+        self.rules
+            .get(rule)
+            .or_else(|| self.categories.get(&category))
+            .or_else(|| self.severities.get(&severity))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+/// Whether the findings a scanner output pass its policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Verdict {
+    #[default]
+    Pass,
+    /// Some findings have the `fail` action
+    Fail,
+}
+
+impl Verdict {
+    /// The verdict on a set of prepared findings, see `SecretScanner::prepare_findings`
+    pub fn of(findings: &[Value]) -> Self {
+        let failing = findings
+            .iter()
+            .any(|f| f.get("policy").and_then(|p| p.as_str()) == Some("fail"));
+        if failing {
+            Verdict::Fail
+        } else {
+            Verdict::Pass
+        }
+    }
+
+    /// The code a hog exits with: 0, or `FAIL_EXIT_CODE` when its findings fail the policy
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Verdict::Pass => 0,
+            Verdict::Fail => FAIL_EXIT_CODE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_policy() {
+        let policy: Policy = serde_json::from_str(
+            r#"{
+                "default": "ignore",
+                "rules": { "AWS API Key": "fail-build" },
+                "severities": { "critical": "report" }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            policy.action("AWS API Key", Category::Credentials, Severity::Low),
+            Action::Fail
+        );
+        assert_eq!(
+            policy.action("Private Key", Category::Credentials, Severity::Critical),
+            Action::Warn
+        );
+        assert_eq!(
+            policy.action("Email address", Category::Pii, Severity::Low),
+            Action::Ignore
+        );
+        assert!(serde_json::from_str::<Policy>(r#"{ "rules": { "x": "block" } }"#).is_err());
+        assert!(serde_json::from_str::<Policy>(r#"{ "rule": {} }"#).is_err());
+    }
+
+    #[test]
+    fn adjusts_severity_by_age() {
+        let policy: Policy =
+            serde_json::from_str(r#"{ "age_severity": { "stale_days": 30 } }"#).unwrap();
+        let age = policy.age_severity.unwrap();
+        assert_eq!(age.recent_days, 7);
+        let now: DateTime<Utc> = "2024-06-10T00:00:00Z".parse().unwrap();
+        let adjust = |severity, time| age.adjust(severity, time, now);
+        assert_eq!(
+            adjust(Severity::Medium, "2024-06-09T00:00:00Z"),
+            Severity::High
+        );
+        assert_eq!(
+            adjust(Severity::Critical, "2024-06-09T00:00:00Z"),
+            Severity::Critical
+        );
+        assert_eq!(
+            adjust(Severity::Medium, "2024-05-20T00:00:00Z"),
+            Severity::Medium
+        );
+        assert_eq!(
+            adjust(Severity::Medium, "2024-04-01T00:00:00Z"),
+            Severity::Low
+        );
+        assert_eq!(adjust(Severity::Low, "2024-04-01T00:00:00Z"), Severity::Low);
+        assert_eq!(adjust(Severity::Medium, "last week"), Severity::Medium);
+        assert_eq!(Policy::default().age_severity, None);
+        assert!(serde_json::from_str::<Policy>(r#"{ "age_severity": { "days": 3 } }"#).is_err());
+    }
+
+    #[test]
+    fn fails_on_fail_actions() {
+        let warned = serde_json::json!({ "reason": "AWS API Key", "policy": "warn" });
+        let failed = serde_json::json!({ "reason": "Slack Token", "policy": "fail" });
+        let findings = [warned, failed];
+        assert_eq!(Verdict::of(&[]), Verdict::Pass);
+        assert_eq!(Verdict::of(&findings[..1]).exit_code(), 0);
+        assert_eq!(Verdict::of(&findings), Verdict::Fail);
+        assert_eq!(Verdict::Fail.exit_code(), FAIL_EXIT_CODE);
+    }
+}
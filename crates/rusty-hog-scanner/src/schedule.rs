@@ -0,0 +1,218 @@
+//! Cron-style schedules, used by the batch runner's scheduler mode (`iberian_hog --daemon`) to
+//! decide when each target is scanned next.
+//!
+//! A schedule has the five fields of a crontab line: minute (0-59), hour (0-23), day of month
+//! (1-31), month (1-12 or `jan`-`dec`) and day of week (0-7 or `sun`-`sat`, 0 and 7 both being
+//! Sunday). A field is `*`, a value, a range `a-b`, any of those with a step (`*/15`, `8-18/2`),
+//! or a comma separated list of them. As in cron, when both the day of month and the day of week
+//! are restricted a day matching either one is a match. The shorthands `@hourly`, `@daily`
+//! (`@midnight`), `@weekly`, `@monthly` and `@yearly` (`@annually`) are accepted too. Schedules
+//! are evaluated in UTC.
+//!
+//! ```
+//! use chrono::{TimeZone, Utc};
+//! use rusty_hog_scanner::schedule::Schedule;
+//! let schedule: Schedule = "30 2 * * mon-fri".parse().unwrap();
+//! // Saturday 2024-06-01 -> Monday 2024-06-03 02:30
+//! let next = schedule.next_after(Utc.with_ymd_and_hms(2024, 6, 1, 12, 0, 0).unwrap());
+//! assert_eq!(next, Some(Utc.with_ymd_and_hms(2024, 6, 3, 2, 30, 0).unwrap()));
+//! ```
+
+use chrono::{DateTime, Datelike, Duration, TimeZone, Timelike, Utc};
+use simple_error::SimpleError;
+use std::fmt;
+use std::str::FromStr;
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+// How far ahead `next_after` looks before deciding a schedule never matches (e.g. `0 0 30 2 *`)
+const MAX_YEARS: i32 = 5;
+
+/// A parsed cron schedule, see the module documentation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    expression: String,
+    // one bit per allowed value
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl FromStr for Schedule {
+    type Err = SimpleError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let expression = match s.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(SimpleError::new(format!(
+                "schedule {:?} does not have 5 fields",
+                s
+            )));
+        }
+        let field = |i: usize, min: u32, max: u32, names: &[&str]| {
+            parse_field(fields[i], min, max, names)
+                .map_err(|e| SimpleError::new(format!("schedule {:?}: {}", s, e)))
+        };
+        let weekdays = field(4, 0, 7, WEEKDAYS)?;
+        Ok(Schedule {
+            expression: String::from(s.trim()),
+            minutes: field(0, 0, 59, &[])?,
+            hours: field(1, 0, 23, &[])?,
+            days: field(2, 1, 31, &[])?,
+            months: field(3, 1, 12, MONTHS)?,
+            // 7 is Sunday too
+            weekdays: (weekdays | (weekdays >> 7)) & 0x7f,
+            days_restricted: !fields[2].starts_with('*'),
+            weekdays_restricted: !fields[4].starts_with('*'),
+        })
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl Schedule {
+    /// The first time after `time` (to the minute) the schedule matches, or `None` if it does not
+    /// match in the next few years
+    pub fn next_after(&self, time: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut t = time.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let last_year = time.year() + MAX_YEARS;
+        while t.year() <= last_year {
+            if !has(self.months, t.month()) {
+                // the first day of the next month
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).single()?;
+            } else if !self.matches_day(&t) {
+                t = t.with_hour(0)?.with_minute(0)? + Duration::days(1);
+            } else if !has(self.hours, t.hour()) {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if !has(self.minutes, t.minute()) {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, t: &DateTime<Utc>) -> bool {
+        let day = has(self.days, t.day());
+        let weekday = has(self.weekdays, t.weekday().num_days_from_sunday());
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+// The bits of the values a field allows
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |v: &str| -> Result<u32, String> {
+        let lower = v.to_ascii_lowercase();
+        let n = match names.iter().position(|n| *n == lower) {
+            // month names start at 1, weekday names at 0
+            Some(i) => i as u32 + min,
+            None => v.parse().map_err(|_| format!("invalid value {:?}", v))?,
+        };
+        if n < min || n > max {
+            return Err(format!("{} is not within {}-{}", n, min, max));
+        }
+        Ok(n)
+    };
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => match step.parse::<u32>() {
+                Ok(step) if step > 0 => (range, step),
+                _ => return Err(format!("invalid step {:?}", step)),
+            },
+            None => (part, 1),
+        };
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (value(start)?, value(end)?),
+            // `5/15` means 5-max/15
+            None if step > 1 => (value(range)?, max),
+            None => {
+                let v = value(range)?;
+                (v, v)
+            }
+        };
+        if start > end {
+            return Err(format!("invalid range {:?}", range));
+        }
+        for v in (start..=end).step_by(step as usize) {
+            bits |= 1 << v;
+        }
+    }
+    Ok(bits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn schedules_find_the_next_matching_minute() {
+        let now = at(2024, 2, 28, 23, 59);
+        let next = |s: &str| s.parse::<Schedule>().unwrap().next_after(now);
+        assert_eq!(next("* * * * *"), Some(at(2024, 2, 29, 0, 0)));
+        assert_eq!(next("*/15 9-17 * * *"), Some(at(2024, 2, 29, 9, 0)));
+        assert_eq!(next("@hourly"), Some(at(2024, 2, 29, 0, 0)));
+        assert_eq!(next("@monthly"), Some(at(2024, 3, 1, 0, 0)));
+        assert_eq!(next("0 12 * dec *"), Some(at(2024, 12, 1, 12, 0)));
+        // Sunday, as 0 or 7
+        assert_eq!(next("0 0 * * 7"), Some(at(2024, 3, 3, 0, 0)));
+        assert_eq!(next("0 0 * * SUN"), Some(at(2024, 3, 3, 0, 0)));
+        // the 15th or any Friday
+        assert_eq!(next("0 6 15 * fri"), Some(at(2024, 3, 1, 6, 0)));
+        assert_eq!(next("0 0 29 2 *"), Some(at(2024, 2, 29, 0, 0)));
+        assert_eq!(next("0 0 30 2 *"), None);
+        assert_eq!(
+            "5/20 * * * *"
+                .parse::<Schedule>()
+                .unwrap()
+                .next_after(at(2024, 1, 1, 0, 30)),
+            Some(at(2024, 1, 1, 0, 45))
+        );
+
+        for invalid in [
+            "* * * *",
+            "60 * * * *",
+            "* * * * mon-sun",
+            "*/0 * * * *",
+            "5-1 * * * *",
+        ] {
+            assert!(invalid.parse::<Schedule>().is_err(), "{}", invalid);
+        }
+    }
+}
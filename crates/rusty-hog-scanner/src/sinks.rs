@@ -0,0 +1,277 @@
+//! Where findings go besides the hog's output: syslog, the Jira and GitHub reporters, Slack, New
+//! Relic, the findings database and the `--attest` manifest.
+//!
+//! `Sinks` is configured apart from the rules, from the hog options with `Sinks::conf_argm`
+//! (which `SecretScannerBuilder::conf_argm` calls) or one `Sink` at a time with
+//! `Sinks::add_sink`, and given to the scanner with `SecretScannerBuilder::set_sinks`.
+//! `SecretScanner::output_findings` sends the prepared findings to every sink once they are
+//! written. iberian_hog pushes the new findings of its `--daemon` scans to the same sinks, which
+//! deserialize from its manifest.
+//!
+//! The Jira, GitHub, Slack and New Relic sinks need the `reporting` feature and the findings
+//! database the `sqlite` feature; without them, sending to these sinks fails.
+//!
+//! ```
+//! use rusty_hog_scanner::sinks::{Sink, Sinks};
+//! let sink: Sink = serde_json::from_str(r#"{ "type": "newrelic", "account_id": 42 }"#).unwrap();
+//! assert_eq!(sink.kind(), "newrelic");
+//! let sinks = Sinks::new().add_sink(sink);
+//! assert_eq!(sinks.nr_account_id(), Some(42));
+//! ```
+
+use crate::attestation::Attestation;
+#[cfg(feature = "sqlite")]
+use crate::findings_db::FindingsDb;
+use crate::syslog::{self, SyslogFormat};
+use crate::SecretScanner;
+#[cfg(feature = "reporting")]
+use crate::{github_reporting, jira_reporting, newrelic, slack_reporting};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use clap::ArgMatches;
+#[cfg(feature = "sqlite")]
+use log::{self, info};
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+/// A destination for prepared findings, e.g. `{ "type": "slack", "webhook": "https://..." }`
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Sink {
+    /// Sends CEF or LEEF syslog messages (`--syslog`, see `syslog`)
+    Syslog {
+        target: String,
+        #[serde(default)]
+        format: SyslogFormat,
+    },
+    /// Creates or updates Jira issues, configured by a JSON file (`--jira-report`, see
+    /// `jira_reporting`)
+    Jira { config: String },
+    /// Files GitHub issues or code-scanning alerts, configured by a JSON file (`--github-report`,
+    /// see `github_reporting`)
+    GitHub { config: String },
+    /// Posts a redacted digest to a Slack incoming webhook (`--notify-slack`, see
+    /// `slack_reporting`)
+    Slack { webhook: String },
+    /// Posts `RustyHogFinding` events to a New Relic account (`--nr-account-id`, see `newrelic`)
+    NewRelic { account_id: u64 },
+    /// Records the scan and upserts its findings into a SQLite database (`--db`, see
+    /// `findings_db`)
+    Db { path: String },
+}
+
+/// The sinks of a scanner, and where it writes its `--attest` manifest
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Sinks {
+    pub sinks: Vec<Sink>,
+    /// Set by `SecretScannerBuilder` from `set_attest`
+    pub attestation: Option<Attestation>,
+}
+
+impl Sink {
+    /// The sink's `type`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Sink::Syslog { .. } => "syslog",
+            Sink::Jira { .. } => "jira",
+            Sink::GitHub { .. } => "github",
+            Sink::Slack { .. } => "slack",
+            Sink::NewRelic { .. } => "newrelic",
+            Sink::Db { .. } => "db",
+        }
+    }
+
+    /// Sends the prepared findings of a scan by `tool` that started at `started`
+    pub fn send(&self, tool: &str, started: DateTime<Utc>, findings: &[Value]) -> Result<()> {
+        match self {
+            Sink::Syslog { target, format } => syslog::send_findings(target, *format, findings)?,
+            Sink::Db { path } => record_in_db(path, tool, started, findings)?,
+            #[cfg(feature = "reporting")]
+            Sink::Jira { config } => {
+                jira_reporting::JiraReporter::from_path(config)?.report(findings);
+            }
+            #[cfg(feature = "reporting")]
+            Sink::GitHub { config } => {
+                github_reporting::GitHubReporter::from_path(config)?.report(findings);
+            }
+            #[cfg(feature = "reporting")]
+            Sink::Slack { webhook } => slack_reporting::notify(webhook, findings),
+            #[cfg(feature = "reporting")]
+            Sink::NewRelic { account_id } => newrelic::post_events(*account_id, tool, findings),
+            #[cfg(not(feature = "reporting"))]
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "--jira-report, --github-report, --notify-slack and --nr-account-id need \
+                     rusty-hog built with the reporting feature"
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Sinks {
+    /// No sinks
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The sinks of the `--syslog`, `--jira-report`, `--github-report`, `--notify-slack`,
+    /// `--nr-account-id` and `--db` options of a `Command` built with `scanner_args`
+    pub fn conf_argm(mut self, arg_matches: &ArgMatches) -> Self {
+        if let Ok(Some(target)) = arg_matches.try_get_one::<String>("SYSLOG") {
+            let format = arg_matches
+                .try_get_one::<SyslogFormat>("SYSLOG_FORMAT")
+                .ok()
+                .flatten()
+                .copied()
+                .unwrap_or_default();
+            self.sinks.push(Sink::Syslog {
+                target: target.clone(),
+                format,
+            });
+        }
+        if let Ok(Some(config)) = arg_matches.try_get_one::<String>("JIRA_REPORT") {
+            self.sinks.push(Sink::Jira {
+                config: config.clone(),
+            });
+        }
+        if let Ok(Some(config)) = arg_matches.try_get_one::<String>("GITHUB_REPORT") {
+            self.sinks.push(Sink::GitHub {
+                config: config.clone(),
+            });
+        }
+        if let Ok(Some(webhook)) = arg_matches.try_get_one::<String>("NOTIFY_SLACK") {
+            self.sinks.push(Sink::Slack {
+                webhook: webhook.clone(),
+            });
+        }
+        if let Ok(Some(account_id)) = arg_matches.try_get_one::<u64>("NR_ACCOUNT_ID") {
+            self.sinks.push(Sink::NewRelic {
+                account_id: *account_id,
+            });
+        }
+        if let Ok(Some(path)) = arg_matches.try_get_one::<String>("DB") {
+            self.sinks.push(Sink::Db { path: path.clone() });
+        }
+        self
+    }
+
+    /// Also send findings to `sink`
+    pub fn add_sink(mut self, sink: Sink) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    /// The account of the first New Relic sink, for `--nr-dashboard-json`
+    pub fn nr_account_id(&self) -> Option<u64> {
+        self.sinks.iter().find_map(|sink| match sink {
+            Sink::NewRelic { account_id } => Some(*account_id),
+            _ => None,
+        })
+    }
+
+    /// Writes the `--attest` manifest of the scan, if any, and sends the findings `scanner`
+    /// prepared to every sink
+    pub fn send(&self, scanner: &SecretScanner, findings: &[Value]) -> Result<()> {
+        if let Some(attestation) = &self.attestation {
+            attestation.write(scanner.scan_manifest(attestation, findings))?;
+        }
+        let tool = crate::tool_name();
+        for sink in &self.sinks {
+            sink.send(&tool, scanner.timings.started_at, findings)?;
+        }
+        Ok(())
+    }
+}
+
+/// Records the scan and its findings in a findings database, see `findings_db`
+#[cfg(feature = "sqlite")]
+fn record_in_db(path: &str, tool: &str, started: DateTime<Utc>, findings: &[Value]) -> Result<()> {
+    let summary = FindingsDb::open(path)?.record_scan(tool, started, Utc::now(), findings)?;
+    info!(
+        "Recorded {} new and {} known findings in {}",
+        summary.new, summary.seen_again, path
+    );
+    Ok(())
+}
+
+#[cfg(not(feature = "sqlite"))]
+fn record_in_db(
+    _path: &str,
+    _tool: &str,
+    _started: DateTime<Utc>,
+    _findings: &[Value],
+) -> Result<()> {
+    Err(anyhow::anyhow!(
+        "--db needs rusty-hog built with the sqlite feature"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanner_args;
+    use clap::Command;
+
+    #[test]
+    fn configures_sinks_from_the_hog_options() {
+        let matches = scanner_args(Command::new("test_hog"))
+            .try_get_matches_from([
+                "test_hog",
+                "--syslog",
+                "tcp://siem:6514",
+                "--syslog-format",
+                "leef",
+                "--notify-slack",
+                "https://hooks.slack.com/services/T0/B0/x",
+                "--nr-account-id",
+                "42",
+            ])
+            .unwrap();
+        let sinks = Sinks::new().conf_argm(&matches);
+        assert_eq!(
+            sinks.sinks,
+            vec![
+                Sink::Syslog {
+                    target: String::from("tcp://siem:6514"),
+                    format: SyslogFormat::Leef,
+                },
+                Sink::Slack {
+                    webhook: String::from("https://hooks.slack.com/services/T0/B0/x"),
+                },
+                Sink::NewRelic { account_id: 42 },
+            ]
+        );
+        assert_eq!(sinks.nr_account_id(), Some(42));
+        assert_eq!(sinks.attestation, None);
+    }
+
+    #[test]
+    fn parses_sinks() {
+        let sink: Sink =
+            serde_json::from_str(r#"{ "type": "syslog", "target": "siem:514" }"#).unwrap();
+        assert_eq!(
+            sink,
+            Sink::Syslog {
+                target: String::from("siem:514"),
+                format: SyslogFormat::Cef,
+            }
+        );
+        let sink: Sink =
+            serde_json::from_str(r#"{ "type": "syslog", "target": "siem:514", "format": "LEEF" }"#)
+                .unwrap();
+        assert_eq!(sink.kind(), "syslog");
+        assert!(matches!(
+            sink,
+            Sink::Syslog {
+                format: SyslogFormat::Leef,
+                ..
+            }
+        ));
+        let sink: Sink =
+            serde_json::from_str(r#"{ "type": "db", "path": "findings.db" }"#).unwrap();
+        assert_eq!(sink.kind(), "db");
+        assert!(serde_json::from_str::<Sink>(r#"{ "type": "pager" }"#).is_err());
+    }
+}
@@ -0,0 +1,218 @@
+//! Detects the text encoding of scanned content and transcodes it to UTF-8 before matching.
+//!
+//! The rules assume ASCII-compatible text, so a UTF-16 file (common for configuration written by
+//! Windows tools) or an EBCDIC file (mainframe exports) never matches anything as-is. `decode`
+//! sniffs the byte order mark, falls back to a heuristic over the first `SAMPLE_LEN` bytes, and
+//! returns the content as UTF-8 along with the encoding it was detected as. Hogs record that
+//! encoding in findings from non-UTF-8 content.
+//!
+//! EBCDIC is decoded as code page 037 (US/Canada), with the EBCDIC newline (0x15) treated as a
+//! line feed so records split into lines.
+//!
+//! ```
+//! use rusty_hog_scanner::text_encoding::{decode, TextEncoding};
+//! let utf16: Vec<u8> = "password=hunter2\n"
+//!     .encode_utf16()
+//!     .flat_map(|u| u.to_le_bytes())
+//!     .collect();
+//! let (text, encoding) = decode(&utf16);
+//! assert_eq!(encoding, TextEncoding::Utf16Le);
+//! assert_eq!(&text[..], b"password=hunter2\n");
+//! ```
+
+use std::borrow::Cow;
+use std::fmt;
+
+/// Number of bytes inspected by the detection heuristic
+pub const SAMPLE_LEN: usize = 4096;
+
+/// Encodings `decode` can detect
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum TextEncoding {
+    #[default]
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Ebcdic,
+}
+
+impl fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextEncoding::Utf8 => write!(f, "utf-8"),
+            TextEncoding::Utf16Le => write!(f, "utf-16le"),
+            TextEncoding::Utf16Be => write!(f, "utf-16be"),
+            TextEncoding::Ebcdic => write!(f, "ebcdic-cp037"),
+        }
+    }
+}
+
+impl TextEncoding {
+    /// Detects the encoding of `bytes` from its byte order mark, or failing that from the
+    /// distribution of bytes in the first `SAMPLE_LEN` bytes. Anything not recognised as
+    /// UTF-16 or EBCDIC is treated as UTF-8.
+    pub fn detect(bytes: &[u8]) -> Self {
+        if bytes.starts_with(&[0xFF, 0xFE]) {
+            return TextEncoding::Utf16Le;
+        }
+        if bytes.starts_with(&[0xFE, 0xFF]) {
+            return TextEncoding::Utf16Be;
+        }
+        if bytes.starts_with(&[0xEF, 0xBB, 0xBF]) {
+            return TextEncoding::Utf8;
+        }
+        let sample = &bytes[..bytes.len().min(SAMPLE_LEN)];
+        if sample.len() < 4 {
+            return TextEncoding::Utf8;
+        }
+
+        // Mostly-ASCII UTF-16 has a NUL in every other byte, and almost none in the other half
+        let pairs = sample.len() / 2;
+        let even_nuls = sample.iter().step_by(2).filter(|&&b| b == 0).count();
+        let odd_nuls = sample
+            .iter()
+            .skip(1)
+            .step_by(2)
+            .filter(|&&b| b == 0)
+            .count();
+        if odd_nuls * 10 > pairs * 3 && even_nuls * 20 < pairs {
+            return TextEncoding::Utf16Le;
+        }
+        if even_nuls * 10 > pairs * 3 && odd_nuls * 20 < pairs {
+            return TextEncoding::Utf16Be;
+        }
+
+        // EBCDIC text is made almost entirely of its letters, digits, punctuation and spaces,
+        // most of which are outside printable ASCII
+        let ascii = sample
+            .iter()
+            .filter(|&&b| (0x20..0x7F).contains(&b) || b == b'\n' || b == b'\r' || b == b'\t')
+            .count();
+        let ebcdic = sample.iter().filter(|&&b| is_ebcdic_text(b)).count();
+        let spaces = sample.iter().filter(|&&b| b == 0x40).count();
+        if ebcdic * 10 >= sample.len() * 9
+            && ascii * 10 < sample.len() * 6
+            && spaces * 20 > sample.len()
+        {
+            return TextEncoding::Ebcdic;
+        }
+        TextEncoding::Utf8
+    }
+}
+
+/// Letters, digits, punctuation, space and line endings in EBCDIC code page 037
+fn is_ebcdic_text(b: u8) -> bool {
+    matches!(b,
+        0x05 | 0x0D | 0x15 | 0x25 | 0x40 | 0x4A..=0x50 | 0x5A..=0x61 | 0x6A..=0x6F | 0x79..=0x7F
+        | 0x81..=0x89 | 0x91..=0x99 | 0xA1..=0xA9 | 0xC0..=0xC9 | 0xD0..=0xD9 | 0xE0 | 0xE2..=0xE9
+        | 0xF0..=0xF9)
+}
+
+/// Returns `bytes` as UTF-8 along with the encoding it was detected as. UTF-8 input is borrowed
+/// (minus any byte order mark); invalid UTF-16 code units become U+FFFD.
+pub fn decode(bytes: &[u8]) -> (Cow<'_, [u8]>, TextEncoding) {
+    let encoding = TextEncoding::detect(bytes);
+    let decoded = match encoding {
+        TextEncoding::Utf8 => {
+            Cow::Borrowed(bytes.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(bytes))
+        }
+        TextEncoding::Utf16Le | TextEncoding::Utf16Be => {
+            let body = match bytes.get(..2) {
+                Some([0xFF, 0xFE]) | Some([0xFE, 0xFF]) => &bytes[2..],
+                _ => bytes,
+            };
+            let units = body.chunks_exact(2).map(|pair| {
+                if encoding == TextEncoding::Utf16Le {
+                    u16::from_le_bytes([pair[0], pair[1]])
+                } else {
+                    u16::from_be_bytes([pair[0], pair[1]])
+                }
+            });
+            let text: String = char::decode_utf16(units)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect();
+            Cow::Owned(text.into_bytes())
+        }
+        TextEncoding::Ebcdic => {
+            let text: String = bytes.iter().map(|&b| CP037[b as usize]).collect();
+            Cow::Owned(text.into_bytes())
+        }
+    };
+    (decoded, encoding)
+}
+
+/// EBCDIC code page 037 to Unicode
+#[rustfmt::skip]
+const CP037: [char; 256] = [
+    '\u{0000}', '\u{0001}', '\u{0002}', '\u{0003}', '\u{009C}', '\u{0009}', '\u{0086}', '\u{007F}',
+    '\u{0097}', '\u{008D}', '\u{008E}', '\u{000B}', '\u{000C}', '\u{000D}', '\u{000E}', '\u{000F}',
+    '\u{0010}', '\u{0011}', '\u{0012}', '\u{0013}', '\u{009D}', '\n', '\u{0008}', '\u{0087}',
+    '\u{0018}', '\u{0019}', '\u{0092}', '\u{008F}', '\u{001C}', '\u{001D}', '\u{001E}', '\u{001F}',
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{000A}', '\u{0017}', '\u{001B}',
+    '\u{0088}', '\u{0089}', '\u{008A}', '\u{008B}', '\u{008C}', '\u{0005}', '\u{0006}', '\u{0007}',
+    '\u{0090}', '\u{0091}', '\u{0016}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0004}',
+    '\u{0098}', '\u{0099}', '\u{009A}', '\u{009B}', '\u{0014}', '\u{0015}', '\u{009E}', '\u{001A}',
+    '\u{0020}', '\u{00A0}', '\u{00E2}', '\u{00E4}', '\u{00E0}', '\u{00E1}', '\u{00E3}', '\u{00E5}',
+    '\u{00E7}', '\u{00F1}', '\u{00A2}', '\u{002E}', '\u{003C}', '\u{0028}', '\u{002B}', '\u{007C}',
+    '\u{0026}', '\u{00E9}', '\u{00EA}', '\u{00EB}', '\u{00E8}', '\u{00ED}', '\u{00EE}', '\u{00EF}',
+    '\u{00EC}', '\u{00DF}', '\u{0021}', '\u{0024}', '\u{002A}', '\u{0029}', '\u{003B}', '\u{00AC}',
+    '\u{002D}', '\u{002F}', '\u{00C2}', '\u{00C4}', '\u{00C0}', '\u{00C1}', '\u{00C3}', '\u{00C5}',
+    '\u{00C7}', '\u{00D1}', '\u{00A6}', '\u{002C}', '\u{0025}', '\u{005F}', '\u{003E}', '\u{003F}',
+    '\u{00F8}', '\u{00C9}', '\u{00CA}', '\u{00CB}', '\u{00C8}', '\u{00CD}', '\u{00CE}', '\u{00CF}',
+    '\u{00CC}', '\u{0060}', '\u{003A}', '\u{0023}', '\u{0040}', '\u{0027}', '\u{003D}', '\u{0022}',
+    '\u{00D8}', '\u{0061}', '\u{0062}', '\u{0063}', '\u{0064}', '\u{0065}', '\u{0066}', '\u{0067}',
+    '\u{0068}', '\u{0069}', '\u{00AB}', '\u{00BB}', '\u{00F0}', '\u{00FD}', '\u{00FE}', '\u{00B1}',
+    '\u{00B0}', '\u{006A}', '\u{006B}', '\u{006C}', '\u{006D}', '\u{006E}', '\u{006F}', '\u{0070}',
+    '\u{0071}', '\u{0072}', '\u{00AA}', '\u{00BA}', '\u{00E6}', '\u{00B8}', '\u{00C6}', '\u{00A4}',
+    '\u{00B5}', '\u{007E}', '\u{0073}', '\u{0074}', '\u{0075}', '\u{0076}', '\u{0077}', '\u{0078}',
+    '\u{0079}', '\u{007A}', '\u{00A1}', '\u{00BF}', '\u{00D0}', '\u{00DD}', '\u{00DE}', '\u{00AE}',
+    '\u{005E}', '\u{00A3}', '\u{00A5}', '\u{00B7}', '\u{00A9}', '\u{00A7}', '\u{00B6}', '\u{00BC}',
+    '\u{00BD}', '\u{00BE}', '\u{005B}', '\u{005D}', '\u{00AF}', '\u{00A8}', '\u{00B4}', '\u{00D7}',
+    '\u{007B}', '\u{0041}', '\u{0042}', '\u{0043}', '\u{0044}', '\u{0045}', '\u{0046}', '\u{0047}',
+    '\u{0048}', '\u{0049}', '\u{00AD}', '\u{00F4}', '\u{00F6}', '\u{00F2}', '\u{00F3}', '\u{00F5}',
+    '\u{007D}', '\u{004A}', '\u{004B}', '\u{004C}', '\u{004D}', '\u{004E}', '\u{004F}', '\u{0050}',
+    '\u{0051}', '\u{0052}', '\u{00B9}', '\u{00FB}', '\u{00FC}', '\u{00F9}', '\u{00FA}', '\u{00FF}',
+    '\u{005C}', '\u{00F7}', '\u{0053}', '\u{0054}', '\u{0055}', '\u{0056}', '\u{0057}', '\u{0058}',
+    '\u{0059}', '\u{005A}', '\u{00B2}', '\u{00D4}', '\u{00D6}', '\u{00D2}', '\u{00D3}', '\u{00D5}',
+    '\u{0030}', '\u{0031}', '\u{0032}', '\u{0033}', '\u{0034}', '\u{0035}', '\u{0036}', '\u{0037}',
+    '\u{0038}', '\u{0039}', '\u{00B3}', '\u{00DB}', '\u{00DC}', '\u{00D9}', '\u{00DA}', '\u{009F}',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_byte_order_marks() {
+        let utf16be: Vec<u8> = [0xFE, 0xFF]
+            .into_iter()
+            .chain("token: AKIA".encode_utf16().flat_map(|u| u.to_be_bytes()))
+            .collect();
+        let (text, encoding) = decode(&utf16be);
+        assert_eq!(encoding, TextEncoding::Utf16Be);
+        assert_eq!(&text[..], b"token: AKIA");
+
+        let (text, encoding) = decode(b"\xEF\xBB\xBFplain");
+        assert_eq!(encoding, TextEncoding::Utf8);
+        assert!(matches!(text, Cow::Borrowed(b"plain")));
+    }
+
+    #[test]
+    fn decodes_ebcdic() {
+        // "PASSWORD = Hunter2" followed by an EBCDIC newline, in code page 037
+        let ebcdic = [
+            0xD7, 0xC1, 0xE2, 0xE2, 0xE6, 0xD6, 0xD9, 0xC4, 0x40, 0x7E, 0x40, 0xC8, 0xA4, 0x95,
+            0xA3, 0x85, 0x99, 0xF2, 0x15,
+        ];
+        let (text, encoding) = decode(&ebcdic);
+        assert_eq!(encoding, TextEncoding::Ebcdic);
+        assert_eq!(&text[..], b"PASSWORD = Hunter2\n");
+    }
+
+    #[test]
+    fn leaves_ascii_and_binary_alone() {
+        assert_eq!(decode(b"let x = 1;\n").1, TextEncoding::Utf8);
+        let binary: Vec<u8> = (0..=255).collect();
+        assert_eq!(decode(&binary).1, TextEncoding::Utf8);
+    }
+}
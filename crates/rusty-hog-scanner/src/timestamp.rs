@@ -0,0 +1,99 @@
+//! The `source_timestamp` of findings: when the content a secret was found in was created or
+//! last changed, as reported by its source, in RFC 3339 UTC (`2024-01-02T03:04:05Z`).
+//!
+//! Sources report times in many formats, so `normalize` takes any of the common ones: RFC 3339,
+//! RFC 2822 (email `Date` headers), ISO 8601 with a `+0000` offset (Jira) or without an offset
+//! (taken as UTC, like ServiceNow's `2024-01-02 03:04:05`), and Unix times in seconds, with or
+//! without a fraction (Slack's `ts`), or in milliseconds (Mattermost's `create_at`).
+//!
+//! ```
+//! use rusty_hog_scanner::timestamp;
+//! assert_eq!(timestamp::normalize("1704164645.000216").as_deref(), Some("2024-01-02T03:04:05Z"));
+//! assert_eq!(timestamp::normalize("2024-01-02T04:04:05.000+0100").as_deref(), Some("2024-01-02T03:04:05Z"));
+//! assert_eq!(timestamp::normalize("Tue, 2 Jan 2024 03:04:05 +0000").as_deref(), Some("2024-01-02T03:04:05Z"));
+//! assert_eq!(timestamp::normalize("last week"), None);
+//! ```
+
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use std::time::SystemTime;
+
+// Unix times above this are taken as milliseconds: 100000000000 seconds is in the year 5138
+const MAX_UNIX_SECONDS: f64 = 1e11;
+
+/// Formats a time as a `source_timestamp`
+pub fn format(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// The `source_timestamp` of a Unix time in seconds
+pub fn from_unix(seconds: i64) -> Option<String> {
+    DateTime::from_timestamp(seconds, 0).map(format)
+}
+
+/// The `source_timestamp` of a Unix time in milliseconds
+pub fn from_unix_millis(millis: i64) -> Option<String> {
+    DateTime::from_timestamp_millis(millis).map(format)
+}
+
+/// The `source_timestamp` of a file modification time
+pub fn from_system_time(time: SystemTime) -> String {
+    format(time.into())
+}
+
+/// The `source_timestamp` of a time in any of the formats of the module documentation, or None
+/// if it isn't one
+pub fn normalize(time: &str) -> Option<String> {
+    let time = time.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(time) {
+        return Some(format(t.into()));
+    }
+    if let Ok(t) = DateTime::parse_from_rfc2822(time) {
+        return Some(format(t.into()));
+    }
+    if let Ok(t) = DateTime::parse_from_str(time, "%Y-%m-%dT%H:%M:%S%.f%z") {
+        return Some(format(t.into()));
+    }
+    for naive_format in ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f"] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(time, naive_format) {
+            return Some(format(t.and_utc()));
+        }
+    }
+    let unix: f64 = time.parse().ok().filter(|t: &f64| t.is_finite())?;
+    if unix.abs() < MAX_UNIX_SECONDS {
+        from_unix(unix.floor() as i64)
+    } else {
+        from_unix_millis(unix as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_source_times() {
+        let expected = Some(String::from("2024-01-02T03:04:05Z"));
+        for time in [
+            "2024-01-02T03:04:05Z",
+            "2024-01-02T03:04:05.123Z",
+            "2024-01-01T22:04:05-05:00",
+            "Tue, 02 Jan 2024 03:04:05 GMT",
+            "2024-01-02T03:04:05.000+0000",
+            "2024-01-02T03:04:05",
+            "2024-01-02 03:04:05",
+            "1704164645",
+            "1704164645.999999",
+            "1704164645123",
+            " 2024-01-02T03:04:05Z\n",
+        ] {
+            assert_eq!(normalize(time), expected, "{}", time);
+        }
+        assert_eq!(from_unix(1704164645), expected);
+        assert_eq!(from_unix_millis(1704164645000), expected);
+        let time = SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1704164645);
+        assert_eq!(Some(from_system_time(time)), expected);
+        for time in ["", "yesterday", "2024-13-02", "NaN", "inf"] {
+            assert_eq!(normalize(time), None, "{}", time);
+        }
+    }
+}
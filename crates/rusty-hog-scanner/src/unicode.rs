@@ -0,0 +1,97 @@
+//! Unicode mode (`--unicode`), for text written in word processors and chat clients.
+//!
+//! The rules are byte oriented: their `[[:punct:]]` and `[[:space:]]` classes only hold ASCII,
+//! so a secret quoted with the smart quotes Confluence, Jira or Google Docs put in, or after a
+//! full-width colon, isn't found. Unicode mode does two things:
+//!
+//! - rule patterns are compiled with those classes widened to Unicode punctuation, symbols and
+//!   white space (`widen_pattern`)
+//! - the document-oriented hogs normalize their text before scanning it (`normalize`): NFKC,
+//!   which turns full-width and compatibility characters such as non-breaking spaces into their
+//!   plain forms, then smart quotes, primes, guillemets and dashes are folded to their ASCII
+//!   counterparts and zero-width characters are dropped
+//!
+//! Findings then report the normalized text, e.g. `"` where the document has `“`.
+//!
+//! ```
+//! use rusty_hog_scanner::unicode;
+//! let text = "secret：“Zx8Kq2Lm9Vt4Rw7Ny3”\u{200b}";
+//! assert_eq!(unicode::normalize(text.as_bytes()).as_ref(), b"secret:\"Zx8Kq2Lm9Vt4Rw7Ny3\"");
+//! assert_eq!(unicode::widen_pattern("[\\s[[:punct:]]]"), "[\\s[\\p{P}\\p{S}]]");
+//! ```
+
+use std::borrow::Cow;
+use unicode_normalization::UnicodeNormalization;
+
+/// NFKC, then the ASCII form of quotes and dashes that NFKC keeps, and no zero-width characters
+pub fn fold(text: &str) -> String {
+    text.nfkc()
+        .filter_map(|c| match c {
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' | '\u{2032}' | '\u{2039}'
+            | '\u{203A}' => Some('\''),
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{2033}' | '\u{00AB}'
+            | '\u{00BB}' => Some('"'),
+            '\u{2010}' | '\u{2011}' | '\u{2012}' | '\u{2013}' | '\u{2014}' | '\u{2015}'
+            | '\u{2212}' => Some('-'),
+            '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' => None,
+            c => Some(c),
+        })
+        .collect()
+}
+
+/// `fold`s the valid UTF-8 runs of `bytes`, keeping invalid bytes as they are. Pure ASCII is
+/// returned as is.
+pub fn normalize(bytes: &[u8]) -> Cow<'_, [u8]> {
+    if bytes.is_ascii() {
+        return Cow::Borrowed(bytes);
+    }
+    let mut normalized = Vec::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        normalized.extend_from_slice(fold(chunk.valid()).as_bytes());
+        normalized.extend_from_slice(chunk.invalid());
+    }
+    Cow::Owned(normalized)
+}
+
+/// Widens the ASCII `[:punct:]` and `[:space:]` classes of a pattern to Unicode punctuation and
+/// symbols (`\p{P}\p{S}`) and white space (`\s`)
+pub fn widen_pattern(pattern: &str) -> Cow<'_, str> {
+    if !pattern.contains("[:punct:]") && !pattern.contains("[:space:]") {
+        return Cow::Borrowed(pattern);
+    }
+    Cow::Owned(
+        pattern
+            .replace("[:punct:]", "\\p{P}\\p{S}")
+            .replace("[:space:]", "\\s"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SecretScannerBuilder;
+
+    #[test]
+    fn unicode_mode_finds_secrets_next_to_unicode_punctuation() {
+        assert_eq!(fold("‘a’ – „b“ «c»\u{a0}ｄ"), "'a' - \"b\" \"c\" d");
+        assert_eq!(
+            normalize(b"\xff\xe2\x80\x9cx\xe2\x80\x9d").as_ref(),
+            b"\xff\"x\""
+        );
+        assert!(matches!(normalize(b"plain"), Cow::Borrowed(_)));
+
+        let line = "secret\u{2003}» Zx8Kq2Lm9Vt4Rw7Ny3Bc6F".as_bytes();
+        let plain = SecretScannerBuilder::new().build();
+        assert!(!plain.matches_entropy(line).contains_key("Generic Secret"));
+        let scanner = SecretScannerBuilder::new().set_unicode(true).build();
+        assert!(scanner.matches_entropy(line).contains_key("Generic Secret"));
+        // the document hogs normalize smart quotes the widened classes already cover
+        let line = "secret: “Zx8Kq2Lm9Vt4Rw7Ny3Bc6F”".as_bytes();
+        assert!(!plain.matches_entropy(line).contains_key("Generic Secret"));
+        let normalized = scanner.normalize(line);
+        assert!(plain
+            .matches_entropy(&normalized)
+            .contains_key("Generic Secret"));
+        assert!(matches!(plain.normalize(line), Cow::Borrowed(_)));
+    }
+}
@@ -0,0 +1,234 @@
+//! In-memory unpacking of downloaded files, for the hogs that scan remote file stores (e.g.
+//! qingping_hog) and can't hand a local path to duroc_hog.
+//!
+//! `archive_files` recognises archives by their name: ZIP-based formats (`.zip`, Java and
+//! Android artifacts, Python wheels and eggs, NuGet packages), tarballs (`.tar`, `.gem`) and
+//! gzip files (`.gz`, `.tgz`, `.crate`, where a gzipped tarball is unpacked in one go). Archives
+//! nested in archives are unpacked in turn, and the other files are returned with their member
+//! path, joined with `!/` for nested archives (`app.war` → `WEB-INF/lib/client.jar!/a.properties`).
+//! Java `.properties` and manifest files are decoded with `java_parsing::decode_java_file`.
+//!
+//! ```
+//! use rusty_hogs::archive_parsing::archive_files;
+//! use std::io::Write;
+//! let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+//! gz.write_all(b"token=abc").unwrap();
+//! let files = archive_files("config.txt.gz", gz.finish().unwrap(), false);
+//! assert_eq!(files.len(), 1);
+//! assert_eq!(files[0].path, "config.txt");
+//! assert_eq!(files[0].data, b"token=abc");
+//! ```
+
+use crate::java_parsing;
+use flate2::read::GzDecoder;
+use log::{self, debug};
+use std::io::{Cursor, Read};
+
+pub const ZIP_EXTENSIONS: &[&str] = &[
+    "zip", "jar", "war", "ear", "aar", "apk", "whl", "egg", "nupkg", "vsix",
+];
+pub const TAR_EXTENSIONS: &[&str] = &["tar", "gem"];
+pub const GZIP_EXTENSIONS: &[&str] = &["gz", "tgz", "crate"];
+/// Nesting limit for archives inside archives
+const MAX_DEPTH: usize = 8;
+/// Archive members bigger than this (uncompressed) are skipped
+const MAX_ENTRY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A file of an archive, or a file that isn't an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveFile {
+    /// Path of the file in the archive, empty for the file itself when it isn't an archive
+    pub path: String,
+    pub data: Vec<u8>,
+}
+
+/// Returns the files in `data`, unpacking it if `name` is an archive. Files that aren't archives
+/// (or fail to unpack) are returned as they are, with an empty `path`. With `class_strings`,
+/// Java `.class` files are reduced to their string constants.
+pub fn archive_files(name: &str, data: Vec<u8>, class_strings: bool) -> Vec<ArchiveFile> {
+    let mut files = Vec::new();
+    add_files(name, "", data, class_strings, 0, &mut files);
+    files
+}
+
+fn extension(name: &str) -> String {
+    let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    file_name
+        .rsplit_once('.')
+        .map(|(_, e)| e.to_ascii_lowercase())
+        .unwrap_or_default()
+}
+
+/// Adds the files of `data`, named `name`, at `path` in the outer archives
+fn add_files(
+    name: &str,
+    path: &str,
+    data: Vec<u8>,
+    class_strings: bool,
+    depth: usize,
+    files: &mut Vec<ArchiveFile>,
+) {
+    let ext = extension(name);
+    // paths of the members of an archive nested in another one start with `<archive>!/`
+    let prefix = if path.is_empty() {
+        String::new()
+    } else {
+        format!("{}!/", path)
+    };
+    if depth < MAX_DEPTH {
+        let members = if ZIP_EXTENSIONS.contains(&ext.as_str()) {
+            zip_members(&data)
+        } else if TAR_EXTENSIONS.contains(&ext.as_str()) {
+            tar_members(&data)
+        } else if GZIP_EXTENSIONS.contains(&ext.as_str()) {
+            gunzip(name, &data)
+        } else {
+            None
+        };
+        match members {
+            Some(members) => {
+                for (member, member_data) in members {
+                    let member_path = format!("{}{}", prefix, member);
+                    add_files(
+                        &member,
+                        &member_path,
+                        member_data,
+                        class_strings,
+                        depth + 1,
+                        files,
+                    );
+                }
+                return;
+            }
+            None if !ext.is_empty() => debug!("{} isn't unpacked", name),
+            None => {}
+        }
+    }
+    let data = java_parsing::decode_java_file(name, data, class_strings);
+    files.push(ArchiveFile {
+        path: String::from(path),
+        data,
+    });
+}
+
+fn zip_members(data: &[u8]) -> Option<Vec<(String, Vec<u8>)>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).ok()?;
+    let mut members = Vec::new();
+    for i in 0..archive.len() {
+        let Ok(mut entry) = archive.by_index(i) else {
+            continue;
+        };
+        if entry.is_dir() || entry.size() > MAX_ENTRY_SIZE {
+            continue;
+        }
+        let mut content = Vec::new();
+        if entry.read_to_end(&mut content).is_ok() {
+            members.push((String::from(entry.name()), content));
+        }
+    }
+    Some(members)
+}
+
+fn tar_members(data: &[u8]) -> Option<Vec<(String, Vec<u8>)>> {
+    let mut archive = tar::Archive::new(data);
+    let mut members = Vec::new();
+    for entry in archive.entries().ok()? {
+        let Ok(mut entry) = entry else {
+            break;
+        };
+        if !entry.header().entry_type().is_file() || entry.size() > MAX_ENTRY_SIZE {
+            continue;
+        }
+        let Ok(path) = entry.path().map(|p| p.to_string_lossy().into_owned()) else {
+            continue;
+        };
+        let mut content = Vec::new();
+        if entry.read_to_end(&mut content).is_ok() {
+            members.push((path, content));
+        }
+    }
+    Some(members)
+}
+
+/// Decompresses a gzip file; a gzipped tarball is unpacked into its members
+fn gunzip(name: &str, data: &[u8]) -> Option<Vec<(String, Vec<u8>)>> {
+    let mut content = Vec::new();
+    GzDecoder::new(data)
+        .take(MAX_ENTRY_SIZE + 1)
+        .read_to_end(&mut content)
+        .ok()?;
+    if content.len() as u64 > MAX_ENTRY_SIZE {
+        return None;
+    }
+    let file_name = name.rsplit(['/', '\\']).next().unwrap_or(name);
+    let stem = file_name
+        .rsplit_once('.')
+        .map_or(file_name, |(stem, _)| stem);
+    let is_tarball = stem.to_ascii_lowercase().ends_with(".tar")
+        || matches!(extension(name).as_str(), "tgz" | "crate")
+        // ustar magic
+        || content.get(257..262) == Some(b"ustar".as_slice());
+    if is_tarball {
+        if let Some(members) = tar_members(&content) {
+            return Some(members);
+        }
+    }
+    Some(vec![(String::from(stem), content)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn unpacks_nested_archives() {
+        let mut jar = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        jar.start_file("app.properties", zip::write::FileOptions::default())
+            .unwrap();
+        jar.write_all(b"key=\\u0041BC").unwrap();
+        let jar = jar.finish().unwrap().into_inner();
+
+        let mut tar = tar::Builder::new(Vec::new());
+        for (name, data) in [
+            ("package/lib/app.jar", jar.as_slice()),
+            ("package/README", b"hello".as_slice()),
+        ] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            tar.append_data(&mut header, name, data).unwrap();
+        }
+        let mut tgz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        tgz.write_all(&tar.into_inner().unwrap()).unwrap();
+
+        let files = archive_files("repo/pkg-1.0.tgz", tgz.finish().unwrap(), false);
+        let files: Vec<(&str, &[u8])> = files
+            .iter()
+            .map(|f| (f.path.as_str(), f.data.as_slice()))
+            .collect();
+        assert_eq!(
+            files,
+            vec![
+                ("package/lib/app.jar!/app.properties", b"key=ABC".as_slice()),
+                ("package/README", b"hello".as_slice()),
+            ]
+        );
+    }
+
+    #[test]
+    fn returns_other_files_as_they_are() {
+        assert_eq!(
+            archive_files("pom.xml", b"<project/>".to_vec(), false),
+            vec![ArchiveFile {
+                path: String::new(),
+                data: b"<project/>".to_vec()
+            }]
+        );
+        // not actually a ZIP file
+        assert_eq!(
+            archive_files("broken.jar", b"PK".to_vec(), false)[0].path,
+            ""
+        );
+    }
+}
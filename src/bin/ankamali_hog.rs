@@ -5,19 +5,16 @@
 //! ankamali_hog [FLAGS] [OPTIONS] <GDRIVEID>
 //!
 //!FLAGS:
-//!         --caseinsensitive    Sets the case insensitive flag for all regexes
-//!         --entropy            Enables entropy scanning
 //!         --oauthsecret        Path to an OAuth secret file (JSON) ./clientsecret.json by default
 //!         --oauthtoken         Path to an OAuth token storage file ./temp_token by default
-//!         --prettyprint        Output the JSON in human readable format
-//!     -v, --verbose            Sets the level of debugging information
 //!     -h, --help               Prints help information
 //!     -V, --version            Prints version information
 //!
 //!OPTIONS:
-//!        --default_entropy_threshold <DEFAULT_ENTROPY_THRESHOLD>    Default entropy threshold (0.6 by default)
-//!    -o, --outputfile <OUTPUT>    Sets the path to write the scanner results to (stdout by default)
-//!        --regex <REGEX>          Sets a custom regex JSON file
+//!        --unicode    Widens the rules' punctuation and space classes to Unicode and normalizes text (NFKC, smart quotes and dashes to ASCII) before scanning
+//!        --revisions    Also scans earlier revisions of the file for secrets that were edited out
+//!        --max-retries <MAX_RETRIES>    Times a Google Drive API call that hit a rate limit or server error is retried (5 by default)
+//!     Plus the scanner options shared by every hog, listed in rusty_hog_scanner::scanner_args
 //!
 //!ARGS:
 //!    <GDRIVEID>    The ID of the google drive file you want to scan
@@ -32,70 +29,25 @@ extern crate yup_oauth2 as oauth2;
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use drive3::DriveHub;
 use log::{self, error, info};
-use rusty_hog_scanner::{SecretScanner, SecretScannerBuilder};
-use rusty_hogs::google_scanning::{GDriveFileInfo, GDriveScanner};
+use rusty_hog_scanner::{policy::Verdict, scanner_args, SecretScanner, SecretScannerBuilder};
+use rusty_hogs::google_scanning::{GDriveFolderCache, GDriveScanner};
 use simple_error::SimpleError;
 use std::path::Path;
 
 /// Main entry function that uses the [clap crate](https://docs.rs/clap/2.33.0/clap/)
 #[tokio::main]
 async fn main() {
-    let matches = Command::new("ankamali_hog")
+    let command = Command::new("ankamali_hog")
         .version("1.0.11")
         .author("Scott Cutler <scutler@newrelic.com>")
-        .about("Google Drive secret scanner in Rust.")
-        .arg(
-            Arg::new("REGEX")
-                .long("regex")
-                .action(ArgAction::Set)
-                .help("Sets a custom regex JSON file"),
-        )
+        .about("Google Drive secret scanner in Rust.");
+    let matches = scanner_args(command)
         .arg(
             Arg::new("GDRIVEID")
-                .required(true)
+                .required_unless_present_any(["SELF_TEST", "GENERATE_CANARIES", "NR_DASHBOARD_JSON", "TRIAGE"])
                 .action(ArgAction::Set)
                 .help("The ID of the Google drive file you want to scan"),
         )
-        .arg(
-            Arg::new("VERBOSE")
-                .short('v')
-                .long("verbose")
-                .action(ArgAction::Count)
-                .help("Sets the level of debugging information"),
-        )
-        .arg(
-            Arg::new("ENTROPY")
-                .long("entropy")
-                .action(ArgAction::SetTrue)
-                .help("Enables entropy scanning"),
-        )
-        .arg(
-            Arg::new("DEFAULT_ENTROPY_THRESHOLD")
-                .long("default_entropy_threshold")
-                .action(ArgAction::Set)
-                .default_value("0.6")
-                .value_parser(clap::value_parser!(f32))
-                .help("Default entropy threshold (0.6 by default)"),
-        )
-        .arg(
-            Arg::new("CASE")
-                .long("caseinsensitive")
-                .action(ArgAction::SetTrue)
-                .help("Sets the case insensitive flag for all regexes"),
-        )
-        .arg(
-            Arg::new("OUTPUT")
-                .short('o')
-                .long("outputfile")
-                .action(ArgAction::Set)
-                .help("Sets the path to write the scanner results to (stdout by default)"),
-        )
-        .arg(
-            Arg::new("PRETTYPRINT")
-                .long("prettyprint")
-                .action(ArgAction::SetTrue)
-                .help("Outputs the JSON in human readable format"),
-        )
         .arg(
             Arg::new("OAUTHSECRETFILE")
                 .long("oauthsecret")
@@ -111,22 +63,35 @@ async fn main() {
                 .help("Path to an OAuth token storage file ./temp_token by default"),
         )
         .arg(
-            Arg::new("ALLOWLIST")
-                .short('a')
-                .long("allowlist")
+            Arg::new("UNICODE")
+                .long("unicode")
+                .action(ArgAction::SetTrue)
+                .help("Widens the rules' punctuation and space classes to Unicode and normalizes text (NFKC, smart quotes and dashes to ASCII) before scanning"),
+        )
+        .arg(
+            Arg::new("REVISIONS")
+                .long("revisions")
+                .action(ArgAction::SetTrue)
+                .help("Also scans earlier revisions of the file for secrets that were edited out"),
+        )
+        .arg(
+            Arg::new("MAX_RETRIES")
+                .long("max-retries")
                 .action(ArgAction::Set)
-                .help("Sets a custom allowlist JSON file"),
+                .default_value("5")
+                .value_parser(clap::value_parser!(u32))
+                .help("Times a Google Drive API call that hit a rate limit or server error is retried"),
         )
         .get_matches();
     match run(matches).await {
-        Ok(()) => {}
+        Ok(verdict) => std::process::exit(verdict.exit_code()),
         Err(e) => error!("Error running command: {}", e),
     }
 }
 
 /// Main logic contained here. Get the CLI variables, setup OAuth, setup GDriveScanner and output
 /// the results.
-async fn run(arg_matches: ArgMatches) -> Result<(), SimpleError> {
+async fn run(arg_matches: ArgMatches) -> Result<Verdict, SimpleError> {
     // Set logging
     SecretScanner::set_logging(arg_matches.get_count("VERBOSE").into());
 
@@ -135,9 +100,22 @@ async fn run(arg_matches: ArgMatches) -> Result<(), SimpleError> {
         .get_one::<String>("OAUTHSECRETFILE")
         .map(|s| s.as_str())
         .unwrap_or("clientsecret.json");
+    let ssb = SecretScannerBuilder::new()
+        .conf_argm(&arg_matches)
+        .set_tool_version(env!("CARGO_PKG_VERSION"));
+    if let Some(code) = ssb
+        .run_mode()
+        .map_err(|e| SimpleError::with("invalid scanner configuration", e))?
+    {
+        std::process::exit(code);
+    }
+    let secret_scanner = ssb
+        .try_build()
+        .map_err(|e| SimpleError::with("invalid scanner configuration", e))?;
     let file_id = arg_matches.get_one::<String>("GDRIVEID").unwrap();
-    let secret_scanner = SecretScannerBuilder::new().conf_argm(&arg_matches).build();
-    let gdrive_scanner = GDriveScanner::new_from_scanner(secret_scanner);
+    let gdrive_scanner = GDriveScanner::new_from_scanner(secret_scanner)
+        .set_scan_revisions(arg_matches.get_flag("REVISIONS"))
+        .set_max_retries(*arg_matches.get_one::<u32>("MAX_RETRIES").unwrap());
 
     // Start with GDrive auth - based on example code from drive3 API and yup-oauth2
     // https://docs.rs/google-drive3/latest/google_drive3/
@@ -156,16 +134,21 @@ async fn run(arg_matches: ArgMatches) -> Result<(), SimpleError> {
     let mut hub = DriveHub::new(hyper::Client::builder().build(hyper_rustls::HttpsConnectorBuilder::new().with_native_roots().https_or_http().enable_http1().build()), auth);
 
     // get some initial info about the file
-    let gdriveinfo = GDriveFileInfo::new(file_id, &hub).await.unwrap();
+    let gdriveinfo = gdrive_scanner
+        .file_info(file_id, &hub, &mut GDriveFolderCache::new())
+        .await
+        .unwrap();
 
     // Do the scan
     let findings = gdrive_scanner.perform_scan(&gdriveinfo, &hub).await;
     info!("Found {} secrets", findings.len());
-    match gdrive_scanner.secret_scanner.output_findings(&findings) {
-        Ok(_) => Ok(()),
-        Err(err) => Err(SimpleError::with(
-            "failed to output findings",
-            SimpleError::new(err.to_string()),
-        )),
-    }
+    gdrive_scanner
+        .secret_scanner
+        .output_findings(&findings)
+        .map_err(|err| {
+            SimpleError::with(
+                "failed to output findings",
+                SimpleError::new(err.to_string()),
+            )
+        })
 }
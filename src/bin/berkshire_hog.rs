@@ -5,19 +5,18 @@
 //! berkshire_hog [FLAGS] [OPTIONS] <S3URI> <S3REGION>
 //!
 //!FLAGS:
-//!        --caseinsensitive    Sets the case insensitive flag for all regexes
-//!        --entropy            Enables entropy scanning
-//!        --prettyprint        Outputs the JSON in human readable format
 //!    -r, --recursive          Recursively scans files under the prefix
-//!    -v, --verbose            Sets the level of debugging information
 //!    -h, --help               Prints help information
 //!    -V, --version            Prints version information
 //!
 //!OPTIONS:
-//!        --default_entropy_threshold <DEFAULT_ENTROPY_THRESHOLD>    Default entropy threshold (0.6 by default)
-//!    -o, --outputfile <OUTPUT>    Sets the path to write the scanner results to (stdout by default)
 //!        --profile <PROFILE>      When using a configuration file, use a non-default profile
-//!        --regex <REGEX>          Sets a custom regex JSON file
+//!        --retries <RETRIES>    Times an S3 request that was throttled or failed with a 5xx or network error is retried, with exponential backoff (3 by default)
+//!        --check-acl    Fetches the ACL of each object with findings and reports whether it is publicly readable
+//!        --class-strings    Scans only the string constants of .class files in Java archives instead of their raw bytes
+//!        --max-rows <MAX_ROWS>    Rows of each Parquet, Avro or ORC object to scan, with the columnar feature (100000 by default)
+//!        --request-timeout <REQUEST_TIMEOUT>    Timeout in seconds for each S3 request (60 by default)
+//!    Plus the scanner options shared by every hog, listed in rusty_hog_scanner::scanner_args
 //!
 //!ARGS:
 //!    <S3URI>       The location of a S3 bucket and optional prefix or filename to scan. This must be written in the form
@@ -29,51 +28,61 @@ extern crate clap;
 
 use clap::{Arg, ArgAction, ArgMatches, Command};
 use log::{self, debug, error, info};
-use s3::bucket::Bucket;
-use s3::creds::Credentials;
-use s3::region::Region;
 use simple_error::SimpleError;
 use simple_error::{require_with, try_with};
-use std::str;
+use std::time::Duration;
 use url::Url;
 
-use rusty_hog_scanner::{SecretScanner, SecretScannerBuilder};
-use rusty_hogs::aws_scanning::{S3Finding, S3Scanner};
+use rusty_hog_scanner::{policy::Verdict, scanner_args, SecretScanner, SecretScannerBuilder};
+use rusty_hogs::aws_scanning::{Bucket, S3Finding, S3Scanner};
 use std::collections::HashSet;
 
 /// Main entry function that uses the [clap crate](https://docs.rs/clap/2.33.0/clap/)
 fn main() {
-    let matches = Command::new("berkshire_hog")
+    let command = Command::new("berkshire_hog")
         .version("1.0.11")
         .author("Scott Cutler <scutler@newrelic.com>")
-        .about("S3 secret hunter in Rust. Avoid bandwidth costs, run this within a VPC!")
-        .arg(Arg::new("REGEX").long("regex").action(ArgAction::Set).help("Sets a custom regex JSON file"))
-        .arg(Arg::new("S3URI").required(true).action(ArgAction::Set).help("The location of a S3 bucket and optional prefix or filename to scan. This must be written in the form s3://mybucket[/prefix_or_file]"))
-        .arg(Arg::new("S3REGION").required(true).action(ArgAction::Set).help("Sets the region of the S3 bucket to scan"))
+        .about("S3 secret hunter in Rust. Avoid bandwidth costs, run this within a VPC!");
+    let matches = scanner_args(command)
+        .arg(Arg::new("S3URI").required_unless_present_any(["SELF_TEST", "GENERATE_CANARIES", "NR_DASHBOARD_JSON", "TRIAGE"]).action(ArgAction::Set).help("The location of a S3 bucket and optional prefix or filename to scan. This must be written in the form s3://mybucket[/prefix_or_file]"))
+        .arg(Arg::new("S3REGION").required_unless_present_any(["SELF_TEST", "GENERATE_CANARIES", "NR_DASHBOARD_JSON", "TRIAGE"]).action(ArgAction::Set).help("Sets the region of the S3 bucket to scan"))
         .arg(Arg::new("RECURSIVE").short('r').long("recursive").action(ArgAction::SetTrue).help("Recursively scans files under the prefix"))
-        .arg(Arg::new("VERBOSE").short('v').long("verbose").action(ArgAction::Count).help("Sets the level of debugging information"))
-        .arg(Arg::new("ENTROPY").long("entropy").action(ArgAction::SetTrue).help("Enables entropy scanning"))
-        .arg(Arg::new("DEFAULT_ENTROPY_THRESHOLD").long("default_entropy_threshold").action(ArgAction::Set).default_value("0.6").value_parser(clap::value_parser!(f32)).help("Default entropy threshold (0.6 by default)"))
-        .arg(Arg::new("CASE").long("caseinsensitive").action(ArgAction::SetTrue).help("Sets the case insensitive flag for all regexes"))
-        .arg(Arg::new("OUTPUT").short('o').long("outputfile").action(ArgAction::Set).help("Sets the path to write the scanner results to (stdout by default)"))
-        .arg(Arg::new("PRETTYPRINT").long("prettyprint").action(ArgAction::SetTrue).help("Outputs the JSON in human readable format"))
         .arg(Arg::new("PROFILE").long("profile").action(ArgAction::Set).help("When using a configuration file, enables a non-default profile"))
-        .arg(Arg::new("ALLOWLIST").short('a').long("allowlist").action(ArgAction::Set).help("Sets a custom allowlist JSON file"))
+        .arg(Arg::new("RETRIES").long("retries").action(ArgAction::Set).default_value("3").value_parser(clap::value_parser!(u32)).help("Times an S3 request that was throttled or failed with a 5xx or network error is retried, with exponential backoff"))
+        .arg(Arg::new("CHECK_ACL").long("check-acl").action(ArgAction::SetTrue).help("Fetches the ACL of each object with findings and reports whether it is publicly readable"))
+        .arg(Arg::new("CLASS_STRINGS").long("class-strings").action(ArgAction::SetTrue).help("Scans only the string constants of .class files in Java archives instead of their raw bytes"))
+        .arg(Arg::new("MAX_ROWS").long("max-rows").action(ArgAction::Set).default_value("100000").value_parser(clap::value_parser!(u64)).help("Rows of each Parquet, Avro or ORC object to scan, with the columnar feature"))
+        .arg(Arg::new("REQUEST_TIMEOUT").long("request-timeout").action(ArgAction::Set).default_value("60").value_parser(clap::value_parser!(u64)).help("Timeout in seconds for each S3 request"))
         .get_matches();
     match run(&matches) {
-        Ok(()) => {}
+        Ok(verdict) => std::process::exit(verdict.exit_code()),
         Err(e) => error!("Error running command: {}", e),
     }
 }
 
 /// Main logic contained here. Initialize S3Scanner, parse the URL and objects, then run the scan.
-fn run(arg_matches: &ArgMatches) -> Result<(), SimpleError> {
+fn run(arg_matches: &ArgMatches) -> Result<Verdict, SimpleError> {
     // Set logging
     SecretScanner::set_logging(arg_matches.get_count("VERBOSE").into());
 
     // Get regex objects
-    let ss = SecretScannerBuilder::new().conf_argm(arg_matches).build();
-    let s3scanner = S3Scanner::new_from_scanner(ss);
+    let ssb = SecretScannerBuilder::new()
+        .conf_argm(arg_matches)
+        .set_tool_version(env!("CARGO_PKG_VERSION"));
+    if let Some(code) = ssb
+        .run_mode()
+        .map_err(|e| SimpleError::with("invalid scanner configuration", e))?
+    {
+        std::process::exit(code);
+    }
+    let ss = ssb
+        .try_build()
+        .map_err(|e| SimpleError::with("invalid scanner configuration", e))?;
+    let s3scanner = S3Scanner::new_from_scanner(ss)
+        .set_retries(*arg_matches.get_one::<u32>("RETRIES").unwrap())
+        .set_check_acl(arg_matches.get_flag("CHECK_ACL"))
+        .set_class_strings(arg_matches.get_flag("CLASS_STRINGS"))
+        .set_max_rows(*arg_matches.get_one::<u64>("MAX_ROWS").unwrap());
 
     // Parse the S3URI
     let url: Url = try_with!(
@@ -82,27 +91,14 @@ fn run(arg_matches: &ArgMatches) -> Result<(), SimpleError> {
     );
     let bucket_string = require_with!(url.host_str(), "Bucket name not detected in S3 URI");
     debug!("bucket_string: {:?}", bucket_string);
-    let key_path = match url.path() {
-        "/" => "",
-        s => s,
-    };
+    let key_path = url.path().trim_start_matches('/');
 
     // Initialize our S3 variables
     let profile = arg_matches.get_one::<String>("PROFILE").map(|s| s.as_str());
-    let credentials = Credentials::new(None, None, None, None, profile.as_deref()).unwrap();
-    debug!(
-        "credentials: {:?} {:?} {:?}",
-        credentials.access_key, credentials.secret_key, credentials.security_token
-    );
-    let region_str = arg_matches.get_one::<String>("S3REGION").unwrap();
-    let region: Region = match region_str.parse() {
-        Ok(r) => r,
-        Err(e) => return Err(SimpleError::new(e.to_string())),
-    };
-    let bucket: Bucket = match Bucket::new(bucket_string, region, credentials) {
-        Ok(r) => r,
-        Err(e) => return Err(SimpleError::new(e.to_string())),
-    };
+    let region = arg_matches.get_one::<String>("S3REGION").unwrap();
+    let mut bucket = Bucket::new(bucket_string, region, profile)?;
+    let request_timeout = *arg_matches.get_one::<u64>("REQUEST_TIMEOUT").unwrap();
+    bucket.set_request_timeout(Some(Duration::from_secs(request_timeout)));
 
     let delimiter = if arg_matches.get_flag("RECURSIVE") {
         None
@@ -110,58 +106,23 @@ fn run(arg_matches: &ArgMatches) -> Result<(), SimpleError> {
         Some(String::from("/"))
     };
 
-    // Retrieve all the keys that match the prefix
+    // List the keys that match the prefix a page at a time, scanning each object as we go
     debug!("key_path: {:?} delimiter: {:?}", key_path, delimiter);
-    let results = bucket.list_blocking(String::from(key_path), delimiter);
-    let results = match results {
-        Ok(r) => r,
-        Err(e) => {
-            //TODO: This bug has been fixed, need to test it.
-            error!(
-                "WARNING: There is a bug in rust-s3 library that prevents it from \
-                 reading access tokens from .credentials files. If you are using this method, \
-                 you will need to export the credentials as environment variables instead. \
-                 https://durch.github.io/rust-s3/s3/credentials/struct.Credentials.html"
-            );
-            return Err(SimpleError::new(format!(
-                "Error running AWS list operation: {:?} (failed auth?)",
-                e
-            )));
-        }
-    };
-    let mut keys: Vec<String> = results
-        .into_iter()
-        .flat_map(|x| x.contents)
-        .map(|x| x.key)
-        .filter(|x| !x.ends_with('/'))
-        .collect();
-
-    // if we didn't find any keys, try accessing the prefix as a file
-    if keys.is_empty() {
-        keys.push(key_path.to_string());
-    }
-
-    // Download and scan each file, generating lots of S3Finding objects
-    info!("Scanning {} objects...", keys.len());
-    debug!("keys: {:?}", keys);
-    let mut findings: Vec<S3Finding> = Vec::new();
-    for key in keys {
-        let f_result: Result<Vec<S3Finding>, SimpleError> =
-            s3scanner.scan_s3_file(bucket.clone(), key.as_ref());
-        match f_result {
-            Ok(mut f) => findings.append(&mut f),
-            Err(_) => error!("Failed to download key {:?}", key),
-        };
-    }
+    info!("Scanning objects under {:?}...", key_path);
+    let findings = s3scanner
+        .scan_s3_prefix(&bucket, key_path, delimiter)
+        .map_err(|e| SimpleError::with("failed to scan the bucket", e))?;
 
     // Output the results
     let findings: HashSet<S3Finding> = findings.into_iter().collect();
     info!("Found {} secrets", findings.len());
-    match s3scanner.secret_scanner.output_findings(&findings) {
-        Ok(_) => Ok(()),
-        Err(err) => Err(SimpleError::with(
-            "failed to output findings",
-            SimpleError::new(err.to_string()),
-        )),
-    }
+    s3scanner
+        .secret_scanner
+        .output_findings(&findings)
+        .map_err(|err| {
+            SimpleError::with(
+                "failed to output findings",
+                SimpleError::new(err.to_string()),
+            )
+        })
 }
@@ -14,18 +14,18 @@
 //!2) Set up the SQS topic to accept events from S3, including IAM permissions.
 //!3) Run Berkshire Hog with IAM access to SQS and S3.
 
-extern crate s3;
-
+use aws_config::BehaviorVersion;
+use aws_sdk_s3::config::Region;
+use aws_sdk_s3::error::DisplayErrorContext;
+use aws_sdk_s3::primitives::ByteStream;
 use lambda_runtime::{handler_fn, Context, Error};
 use log::{self, warn, LevelFilter};
 use rusty_hog_scanner::SecretScannerBuilder;
-use rusty_hogs::aws_scanning::{S3Finding, S3Scanner};
-use s3::bucket::Bucket;
-use s3::creds::Credentials;
-use s3::region::Region;
+use rusty_hogs::aws_scanning::{Bucket, S3Finding, S3Scanner};
 use serde_derive::{Deserialize, Serialize};
 use simple_error::SimpleError;
 use simple_logger::SimpleLogger;
+use std::collections::HashSet;
 use std::env;
 use std::time::SystemTime;
 
@@ -119,28 +119,31 @@ async fn my_handler(event: CustomEvent, _: Context) -> Result<CustomOutput, Erro
     // let event: CustomEvent = serde_json::from_value(e).unwrap();
     warn!("incoming JSON: {:?}", event);
     // Initialize our S3 variables
-    let credentials = Credentials::new(None, None, None, None, None).unwrap();
     let output_bucket_name = env::var("OUTPUT_BUCKET_NAME").unwrap();
-    let output_bucket_region: Region = env::var("OUTPUT_BUCKET_REGION").unwrap().parse().unwrap();
+    let output_bucket_region = env::var("OUTPUT_BUCKET_REGION").unwrap();
     let output_bucket_keyprefix = env::var("OUTPUT_BUCKET_KEYPREFIX").unwrap();
-    let output_bucket = Bucket::new(
-        output_bucket_name.as_ref(),
-        output_bucket_region,
-        credentials.clone(),
-    )
-    .unwrap();
+    let output_config = aws_config::defaults(BehaviorVersion::latest())
+        .region(Region::new(output_bucket_region))
+        .load()
+        .await;
+    let output_client = aws_sdk_s3::Client::new(&output_config);
 
     // Main loop - create a list of findings based on each S3 file contained in the json
     let mut findings: Vec<S3Finding> = Vec::new();
-    let ss = SecretScannerBuilder::new().build();
+    let mut ssb = SecretScannerBuilder::new();
+    if let Ok(min_severity) = env::var("MIN_SEVERITY") {
+        match min_severity.parse() {
+            Ok(s) => ssb = ssb.set_min_severity(s),
+            Err(e) => warn!("Ignoring MIN_SEVERITY: {}", e),
+        }
+    }
+    let ss = ssb.try_build()?;
     let s3scanner = S3Scanner::new_from_scanner(ss);
     for top_record in event.records {
         let body_obj: Body = serde_json::from_str(top_record.body.as_str()).unwrap(); //yo dawg
         for record in body_obj.records {
-            let region_str = record.aws_region;
-            let region: Region = region_str.parse().unwrap();
             let bucket_name = record.s3.bucket.name;
-            let bucket = Bucket::new(bucket_name.as_ref(), region, credentials.clone()).unwrap();
+            let bucket = Bucket::new(&bucket_name, &record.aws_region, None)?;
             let key = record.s3.object.key;
             //            let filesize = record.s3.object.size;
             let f_result: Result<Vec<S3Finding>, SimpleError> =
@@ -153,15 +156,27 @@ async fn my_handler(event: CustomEvent, _: Context) -> Result<CustomOutput, Erro
     }
 
     // Cleanup - Write output to S3, return a 200 to lambda
-    let output_string: String = serde_json::to_string(&findings).unwrap();
+    let ss = &s3scanner.secret_scanner;
+    let findings_set: HashSet<S3Finding> = findings.iter().cloned().collect();
+    let output_values = match ss.prepare_findings(&findings_set) {
+        Ok(v) => v,
+        Err(e) => return Err(Error::from(e.to_string())),
+    };
+    let output_string: String = serde_json::to_string(&output_values).unwrap();
     let epoch = SystemTime::now()
         .duration_since(SystemTime::UNIX_EPOCH)
         .unwrap()
         .as_secs();
     let dest = format!("{}/{}", output_bucket_keyprefix, epoch);
-    output_bucket
-        .put_object_with_content_type_blocking(&dest, output_string.as_bytes(), "text/plain")
-        .unwrap();
+    output_client
+        .put_object()
+        .bucket(output_bucket_name)
+        .key(dest)
+        .content_type("text/plain")
+        .body(ByteStream::from(output_string.into_bytes()))
+        .send()
+        .await
+        .map_err(|e| Error::from(DisplayErrorContext(&e).to_string()))?;
     Ok(CustomOutput {
         is_base64_encoded: false,
         status_code: 200,
@@ -6,26 +6,22 @@
 //!     choctaw_hog [FLAGS] [OPTIONS] <GITPATH>
 //!
 //!FLAGS:
-//!        --caseinsensitive    Sets the case insensitive flag for all regexes
-//!        --entropy            Enables entropy scanning
 //!        --match_entropy      Enable entropy for each pattern match
-//!        --prettyprint        Outputs the JSON in human readable format
-//!    -v, --verbose            Sets the level of debugging information
+//!        --unreachable        Also scans the blobs no branch or tag reaches (reset or force-pushed commits), for repos opened in place
 //!    -h, --help               Prints help information
 //!    -V, --version            Prints version information
 //!
 //!OPTIONS:
-//!    -a, --allowlist <allowlist>          Sets a custom allowlist JSON file
 //!        --recent_days <RECENTDAYS>       Filters commits to the last number of days (branch agnostic)
-//!        --default_entropy_threshold <DEFAULT_ENTROPY_THRESHOLD>    Default entropy threshold (0.6 by default)
 //!        --httpspass <HTTPSPASS>          Takes a password for HTTPS-based authentication
 //!        --httpsuser <HTTPSUSER>          Takes a username for HTTPS-based authentication
-//!    -o, --outputfile <OUTPUT>            Sets the path to write the scanner results to (stdout by default)
-//!    -r, --regex <REGEX>                  Sets a custom regex JSON file, defaults to built-in
 //!        --since_commit <SINCECOMMIT>     Filters commits based on date committed (branch agnostic)
 //!        --sshkeypath <SSHKEYPATH>        Takes a path to a private SSH key for git authentication, defaults to ssh-agent
 //!        --sshkeyphrase <SSHKEYPHRASE>    Takes a passphrase to a private SSH key for git authentication, defaults to none
 //!        --until_commit <UNTILCOMMIT>     Filters commits based on date committed (branch agnostic)
+//!        --path-include <PATH_INCLUDE>... Only scans the files matching this pathspec (repeatable), e.g. services/billing
+//!        --path-exclude <PATH_EXCLUDE>... Skips the files matching this pathspec (repeatable), e.g. *.lock
+//!    Plus the scanner options shared by every hog, listed in rusty_hog_scanner::scanner_args
 //!
 //!ARGS:
 //!    <GITPATH>    Sets the path (or URL) of the Git repo to scan. SSH links must include username (git@)
@@ -45,23 +41,18 @@ use simple_error::SimpleError;
 use std::str;
 use tempdir::TempDir;
 
-use rusty_hog_scanner::{SecretScanner, SecretScannerBuilder};
+use rusty_hog_scanner::{policy::Verdict, scanner_args, SecretScanner, SecretScannerBuilder};
 use rusty_hogs::git_scanning::GitScanner;
 
 /// Main entry function that uses the [clap crate](https://docs.rs/clap/2.33.0/clap/)
 fn main() {
-    let matches = Command::new("choctaw_hog")
+    let command = Command::new("choctaw_hog")
         .version("1.0.11")
         .author("Scott Cutler <scutler@newrelic.com>")
-        .about("Git secret scanner in Rust")
-        .arg(Arg::new("REGEX").short('r').long("regex").action(ArgAction::Set).value_name("REGEX").help("Sets a custom regex JSON file"))
-        .arg(Arg::new("GITPATH").required(true).action(ArgAction::Set).value_name("GIT_PATH").help("Sets the path (or URL) of the Git repo to scan. SSH links must include username (git@)"))
-        .arg(Arg::new("VERBOSE").short('v').long("verbose").action(ArgAction::Count).help("Sets the level of debugging information"))
-        .arg(Arg::new("ENTROPY").long("entropy").action(ArgAction::SetTrue).help("Enables entropy scanning"))
-        .arg(Arg::new("DEFAULT_ENTROPY_THRESHOLD").long("default_entropy_threshold").action(ArgAction::Set).default_value("0.6").value_parser(clap::value_parser!(f32)).help("Default entropy threshold (0.6 by default)"))
-        .arg(Arg::new("CASE").long("caseinsensitive").action(ArgAction::SetTrue).help("Sets the case insensitive flag for all regexes"))
-        .arg(Arg::new("OUTPUT").short('o').long("outputfile").action(ArgAction::Set).help("Sets the path to write the scanner results to (stdout by default)"))
-        .arg(Arg::new("PRETTYPRINT").long("prettyprint").action(ArgAction::SetTrue).help("Outputs the JSON in human readable format"))
+        .about("Git secret scanner in Rust");
+    let matches = scanner_args(command)
+        .mut_arg("REGEX", |a| a.short('r'))
+        .arg(Arg::new("GITPATH").required_unless_present_any(["SELF_TEST", "GENERATE_CANARIES", "NR_DASHBOARD_JSON", "TRIAGE"]).action(ArgAction::Set).value_name("GIT_PATH").help("Sets the path (or URL) of the Git repo to scan. SSH links must include username (git@)"))
         .arg(Arg::new("SINCECOMMIT").long("since_commit").action(ArgAction::Set).help("Filters commits based on date committed (branch agnostic)"))
         .arg(Arg::new("UNTILCOMMIT").long("until_commit").action(ArgAction::Set).help("Filters commits based on date committed (branch agnostic)"))
         .arg(Arg::new("SSHKEYPATH").long("sshkeypath").action(ArgAction::Set).help("Takes a path to a private SSH key for git authentication, defaults to ssh-agent"))
@@ -69,21 +60,34 @@ fn main() {
         .arg(Arg::new("HTTPSUSER").long("httpsuser").action(ArgAction::Set).help("Takes a username for HTTPS-based authentication"))
         .arg(Arg::new("HTTPSPASS").long("httpspass").action(ArgAction::Set).help("Takes a password for HTTPS-based authentication"))
         .arg(Arg::new("RECENTDAYS").long("recent_days").action(ArgAction::Set).value_parser(clap::value_parser!(u32)).conflicts_with("SINCECOMMIT").help("Filters commits to the last number of days (branch agnostic)"))
-        .arg(Arg::new("ALLOWLIST").short('a').long("allowlist").action(ArgAction::Set).help("Sets a custom allowlist JSON file"))
+        .arg(Arg::new("UNREACHABLE").long("unreachable").action(ArgAction::SetTrue).help("Also scans the blobs no branch or tag reaches (reset or force-pushed commits), for repos opened in place"))
+        .arg(Arg::new("PATH_INCLUDE").long("path-include").action(ArgAction::Append).help("Only scans the files matching this pathspec (repeatable), e.g. services/billing"))
+        .arg(Arg::new("PATH_EXCLUDE").long("path-exclude").action(ArgAction::Append).help("Skips the files matching this pathspec (repeatable), e.g. *.lock"))
         .get_matches();
     match run(&matches) {
-        Ok(()) => {}
+        Ok(verdict) => std::process::exit(verdict.exit_code()),
         Err(e) => error!("Error running command: {}", e),
     }
 }
 
 /// Main logic contained here. Get the CLI variables, and use them to initialize a GitScanner
-fn run(arg_matches: &ArgMatches) -> Result<(), SimpleError> {
+fn run(arg_matches: &ArgMatches) -> Result<Verdict, SimpleError> {
     // Set logging
     SecretScanner::set_logging(arg_matches.get_count("VERBOSE").into());
 
     // Initialize some more variables
-    let secret_scanner = SecretScannerBuilder::new().conf_argm(arg_matches).build();
+    let ssb = SecretScannerBuilder::new()
+        .conf_argm(arg_matches)
+        .set_tool_version(env!("CARGO_PKG_VERSION"));
+    if let Some(code) = ssb
+        .run_mode()
+        .map_err(|e| SimpleError::with("invalid scanner configuration", e))?
+    {
+        std::process::exit(code);
+    }
+    let secret_scanner = ssb
+        .try_build()
+        .map_err(|e| SimpleError::with("invalid scanner configuration", e))?;
     let sshkeypath = arg_matches
         .get_one::<String>("SSHKEYPATH")
         .map(|s| s.as_str());
@@ -102,6 +106,14 @@ fn run(arg_matches: &ArgMatches) -> Result<(), SimpleError> {
     let until_commit = arg_matches
         .get_one::<String>("UNTILCOMMIT")
         .map(|s| s.as_str());
+    let path_include: Vec<String> = arg_matches
+        .get_many::<String>("PATH_INCLUDE")
+        .map(|p| p.cloned().collect())
+        .unwrap_or_default();
+    let path_exclude: Vec<String> = arg_matches
+        .get_many::<String>("PATH_EXCLUDE")
+        .map(|p| p.cloned().collect())
+        .unwrap_or_default();
     let recent_days: Option<u32> = match arg_matches.get_one::<u32>("RECENTDAYS") {
         Some(d) => {
             if *d == 0 {
@@ -122,23 +134,30 @@ fn run(arg_matches: &ArgMatches) -> Result<(), SimpleError> {
         .unwrap();
 
     // Do the scan
-    let git_scanner = GitScanner::new_from_scanner(secret_scanner).init_git_repo(
-        source_path,
-        &dest_dir_path,
-        sshkeypath,
-        sshkeyphrase,
-        httpsuser,
-        httpspass,
-    );
-    let findings = git_scanner.perform_scan(None, since_commit, until_commit, recent_days);
+    let git_scanner = GitScanner::new_from_scanner(secret_scanner)
+        .init_git_repo(
+            source_path,
+            dest_dir_path,
+            sshkeypath,
+            sshkeyphrase,
+            httpsuser,
+            httpspass,
+        )
+        .set_path_filters(path_include, path_exclude);
+    let mut findings = git_scanner.perform_scan(None, since_commit, until_commit, recent_days);
+    if arg_matches.get_flag("UNREACHABLE") {
+        findings.extend(git_scanner.perform_unreachable_scan());
+    }
 
     // Output the results
     info!("Found {} secrets", findings.len());
-    match git_scanner.secret_scanner.output_findings(&findings) {
-        Ok(_) => Ok(()),
-        Err(err) => Err(SimpleError::with(
-            "failed to output findings",
-            SimpleError::new(err.to_string()),
-        )),
-    }
+    git_scanner
+        .secret_scanner
+        .output_findings(&findings)
+        .map_err(|err| {
+            SimpleError::with(
+                "failed to output findings",
+                SimpleError::new(err.to_string()),
+            )
+        })
 }
@@ -4,20 +4,20 @@
 //!     essex_hog [FLAGS] [OPTIONS] <PAGEID> <URL>
 //!
 //! FLAGS:
-//!         --caseinsensitive    Sets the case insensitive flag for all regexes
-//!         --entropy            Enables entropy scanning
-//!         --prettyprint        Outputs the JSON in human readable format
-//!     -v, --verbose            Sets the level of debugging information
+//!         --cloud              Use the Confluence Cloud v2 API (the default for *.atlassian.net URLs)
+//!         --server             Use the Confluence Server/Data Center REST API (the default for other URLs)
+//!         --raw                Scans the storage format XHTML of pages as it is, instead of their text with tags removed and entities decoded
 //!     -h, --help               Prints help information
 //!     -V, --version            Prints version information
 //!
 //! OPTIONS:
-//!         --default_entropy_threshold <DEFAULT_ENTROPY_THRESHOLD>    Default entropy threshold (0.6 by default)
 //!         --authtoken <BEARERTOKEN>    Confluence basic auth bearer token (instead of user & pass)
-//!     -o, --outputfile <OUTPUT>        Sets the path to write the scanner results to (stdout by default)
-//!         --password <PASSWORD>        Confluence password (crafts basic auth header)
-//!         --regex <REGEX>              Sets a custom regex JSON file
-//!         --username <USERNAME>        Confluence username (crafts basic auth header)
+//!         --password <PASSWORD>        Confluence password, or API token for Confluence Cloud (crafts basic auth header)
+//!         --unicode    Widens the rules' punctuation and space classes to Unicode and normalizes text (NFKC, smart quotes and dashes to ASCII) before scanning
+//!         --username <USERNAME>        Confluence username, or Atlassian account email for Confluence Cloud (crafts basic auth header)
+//!         --rate-limit <RATE_LIMIT>    Maximum Confluence API requests per second (10 by default, 0 for no limit)
+//!         --state-file <STATE_FILE>    Records the comment pages of a page left to scan after every one, and resumes an interrupted scan of the page from there
+//!     Plus the scanner options shared by every hog, listed in rusty_hog_scanner::scanner_args
 //!
 //! ARGS:
 //!     <PAGEID>    The ID (e.g. 1234) of the confluence page you want to scan
@@ -28,6 +28,12 @@
 //!                 Or without context: http://host:port/rest/api/resource-name
 //!                 Example with context: http://example.com:8080/confluence/rest/api/space/ds
 //!                 Example without context: http://confluence.myhost.com:8095/rest/api/space/ds
+//!                 Confluence Cloud sites (*.atlassian.net) are read through the v2 API under /wiki/api/v2,
+//!                 with or without /wiki in the URL.
+//!
+//! The page's comments are read a page of results at a time. With `--state-file`, the URLs of
+//! the comment pages left to read are recorded after every one, and the findings in a
+//! `FindingsSpool` next to it, so that an interrupted scan of the same page picks up from there.
 
 extern crate clap;
 extern crate hyper;
@@ -42,16 +48,22 @@ use hyper::body;
 use hyper::header::AUTHORIZATION;
 use hyper::http::Request;
 use hyper::http::StatusCode;
-use hyper::{client, Body, Client};
+use hyper::{client, Body};
 use log::{self, debug, error, info};
 use rusty_hog_scanner::SecretScannerBuilder;
-use rusty_hog_scanner::{RustyHogMatch, SecretScanner};
+use rusty_hog_scanner::{policy::Verdict, scanner_args, timestamp, RustyHogMatch, SecretScanner};
+use rusty_hogs::markup_parsing::html_to_text;
+use rusty_hogs::scan_state::{self, FindingsSpool};
+use rusty_hogs::web_client::WebClient;
 use serde_derive::{Deserialize, Serialize};
 use serde_json::{Map, Value};
 use simple_error::SimpleError;
 use std::collections::{BTreeMap, HashSet};
 use url::Url;
 
+// Atlassian doesn't publish fixed Confluence limits; this stays well below where throttling starts
+const DEFAULT_RATE_LIMIT: &str = "10";
+
 /// `serde_json` object that represents a single found secret - finding
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Default)]
 pub struct ConfluenceFinding {
@@ -60,124 +72,151 @@ pub struct ConfluenceFinding {
     pub page_id: String,
     pub reason: String,
     pub url: String,
+    /// Encoding of the matched span, if the secret was found by the decoding pass (`--decode`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encoded: Option<String>,
+    /// Identifier the secret is assigned to, e.g. "DB_PASSWORD" for `DB_PASSWORD = "..."`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assigned_to: Option<String>,
+    /// When the content the secret was found in was created or last changed, in RFC 3339 UTC
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_timestamp: Option<String>,
 }
 
-/// stores the content of a confluence page including its body and comments
+/// stores the content of a confluence page, without its comments
 #[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Hash, Clone, Default)]
 pub struct ConfluencePage {
     web_link: String,
     body: String,
-    comments: String,
+    /// When the current version of the page was made, as a `source_timestamp`
+    last_modified: Option<String>,
+}
+
+/// Which Confluence REST API a base URL is read through
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ConfluenceApi {
+    /// Confluence Server/Data Center: `<base>/rest/api/content`
+    Server,
+    /// Confluence Cloud: `<site>/wiki/api/v2/pages`
+    Cloud,
+}
+
+impl ConfluenceApi {
+    /// Confluence Cloud sites are hosted under atlassian.net
+    fn detect(base_url: &Url) -> Self {
+        match base_url.host_str() {
+            Some(h) if h.ends_with(".atlassian.net") => ConfluenceApi::Cloud,
+            _ => ConfluenceApi::Server,
+        }
+    }
 }
 
 /// Main entry function that uses the [clap crate](https://docs.rs/clap/2.33.0/clap/)
 #[tokio::main]
 async fn main() {
-    let matches = Command::new("gottingen_hog")
+    let command = Command::new("gottingen_hog")
         .version("1.0.11")
         .author("Emily Cain <ecain@newrelic.com>, Scott Cutler")
-        .about("Confluence secret scanner in Rust.")
-        .arg(
-            Arg::new("REGEX")
-                .long("regex")
-                .action(ArgAction::Set)
-                .help("Sets a custom regex JSON file"),
-        )
+        .about("Confluence secret scanner in Rust.");
+    let matches = scanner_args(command)
         .arg(
             Arg::new("PAGEID")
-                .required(true)
+                .required_unless_present_any(["SELF_TEST", "GENERATE_CANARIES", "NR_DASHBOARD_JSON", "TRIAGE"])
                 .action(ArgAction::Set)
                 .help("The ID (e.g. 1234) of the confluence page you want to scan"),
         )
         .arg(
             Arg::new("URL")
-                .required(true)
+                .required_unless_present_any(["SELF_TEST", "GENERATE_CANARIES", "NR_DASHBOARD_JSON", "TRIAGE"])
                 .action(ArgAction::Set)
                 .help("Base URL of Confluence instance (e.g. https://newrelic.atlassian.net/)"),
         )
         .arg(
-            Arg::new("VERBOSE")
-                .short('v')
-                .long("verbose")
-                .action(ArgAction::Count)
-                .help("Sets the level of debugging information"),
+            Arg::new("USERNAME")
+                .long("username")
+                .action(ArgAction::Set)
+                .conflicts_with("BEARERTOKEN")
+                .help("Confluence username, or Atlassian account email for Confluence Cloud (crafts basic auth header)"),
         )
         .arg(
-            Arg::new("ENTROPY")
-                .long("entropy")
+            Arg::new("PASSWORD")
+                .long("password")
                 .action(ArgAction::Set)
-                .help("Enables entropy scanning"),
+                .conflicts_with("BEARERTOKEN")
+                .help("Confluence password, or API token for Confluence Cloud (crafts basic auth header)"),
         )
         .arg(
-            Arg::new("DEFAULT_ENTROPY_THRESHOLD")
-                .long("default_entropy_threshold")
+            Arg::new("BEARERTOKEN")
+                .long("authtoken")
                 .action(ArgAction::Set)
-                .default_value("0.6")
-                .value_parser(clap::value_parser!(f32))
-                .help("Default entropy threshold (0.6 by default)"),
+                .conflicts_with_all(["USERNAME", "PASSWORD"])
+                .help("Confluence basic auth bearer token (instead of user & pass)"),
         )
         .arg(
-            Arg::new("CASE")
-                .long("caseinsensitive")
+            Arg::new("UNICODE")
+                .long("unicode")
                 .action(ArgAction::SetTrue)
-                .help("Sets the case insensitive flag for all regexes"),
+                .help("Widens the rules' punctuation and space classes to Unicode and normalizes text (NFKC, smart quotes and dashes to ASCII) before scanning"),
         )
         .arg(
-            Arg::new("OUTPUT")
-                .short('o')
-                .long("outputfile")
+            Arg::new("CLOUD")
+                .long("cloud")
                 .action(ArgAction::SetTrue)
-                .help("Sets the path to write the scanner results to (stdout by default)"),
+                .conflicts_with("SERVER")
+                .help("Use the Confluence Cloud v2 API (the default for *.atlassian.net URLs)"),
         )
         .arg(
-            Arg::new("PRETTYPRINT")
-                .long("prettyprint")
+            Arg::new("SERVER")
+                .long("server")
                 .action(ArgAction::SetTrue)
-                .help("Outputs the JSON in human readable format"),
+                .help("Use the Confluence Server/Data Center REST API (the default for other URLs)"),
         )
         .arg(
-            Arg::new("USERNAME")
-                .long("username")
-                .action(ArgAction::Set)
-                .conflicts_with("BEARERTOKEN")
-                .help("Confluence username (crafts basic auth header)"),
-        )
-        .arg(
-            Arg::new("PASSWORD")
-                .long("password")
-                .action(ArgAction::Set)
-                .conflicts_with("BEARERTOKEN")
-                .help("Confluence password (crafts basic auth header)"),
+            Arg::new("RAW")
+                .long("raw")
+                .action(ArgAction::SetTrue)
+                .help("Scans the storage format XHTML of pages as it is, instead of their text with tags removed and entities decoded"),
         )
         .arg(
-            Arg::new("BEARERTOKEN")
-                .long("authtoken")
+            Arg::new("RATE_LIMIT")
+                .long("rate-limit")
                 .action(ArgAction::Set)
-                .conflicts_with_all(["USERNAME", "PASSWORD"])
-                .help("Confluence basic auth bearer token (instead of user & pass)"),
+                .value_parser(clap::value_parser!(f64))
+                .default_value(DEFAULT_RATE_LIMIT)
+                .help("Maximum Confluence API requests per second (10 by default, 0 for no limit); 429 responses are retried after their Retry-After"),
         )
         .arg(
-            Arg::new("ALLOWLIST")
-                .short('a')
-                .long("allowlist")
+            Arg::new("STATE_FILE")
+                .long("state-file")
                 .action(ArgAction::Set)
-                .help("Sets a custom allowlist JSON file"),
+                .value_name("STATE_FILE")
+                .help("Records the comment pages of a page left to scan after every one, and resumes an interrupted scan of the page from there"),
         )
         .get_matches();
     match run(matches).await {
-        Ok(()) => {}
+        Ok(verdict) => std::process::exit(verdict.exit_code()),
         Err(e) => error!("Error running command: {}", e),
     }
 }
 
 /// Main logic contained here. Get the CLI variables, create the appropriate TLS objects,
 /// make the TLS calls, and scan the result..
-async fn run(arg_matches: ArgMatches) -> Result<(), SimpleError> {
+async fn run(arg_matches: ArgMatches) -> Result<Verdict, SimpleError> {
     SecretScanner::set_logging(arg_matches.get_count("VERBOSE").into());
 
     // initialize the basic variables and CLI options
-    let ssb = SecretScannerBuilder::new().conf_argm(&arg_matches);
-    let secret_scanner = ssb.build();
+    let ssb = SecretScannerBuilder::new()
+        .conf_argm(&arg_matches)
+        .set_tool_version(env!("CARGO_PKG_VERSION"));
+    if let Some(code) = ssb
+        .run_mode()
+        .map_err(|e| SimpleError::with("invalid scanner configuration", e))?
+    {
+        std::process::exit(code);
+    }
+    let secret_scanner = ssb
+        .try_build()
+        .map_err(|e| SimpleError::with("invalid scanner configuration", e))?;
 
     let jirausername = arg_matches.get_one::<String>("USERNAME");
     let jirapassword = arg_matches.get_one::<String>("PASSWORD");
@@ -193,6 +232,14 @@ async fn run(arg_matches: ArgMatches) -> Result<(), SimpleError> {
         .unwrap();
 
     let base_url = base_url_as_url.as_str();
+    let api = if arg_matches.get_flag("CLOUD") {
+        ConfluenceApi::Cloud
+    } else if arg_matches.get_flag("SERVER") {
+        ConfluenceApi::Server
+    } else {
+        ConfluenceApi::detect(&base_url_as_url)
+    };
+    debug!("Confluence API: {:?}", api);
 
     // Still inside `async fn main`...
     let https = hyper_rustls::HttpsConnectorBuilder::new()
@@ -201,6 +248,10 @@ async fn run(arg_matches: ArgMatches) -> Result<(), SimpleError> {
         .enable_all_versions()
         .build();
     let hyper_client: client::Client<_, hyper::Body> = client::Client::builder().build(https);
+    let web_client = WebClient::new(
+        hyper_client,
+        *arg_matches.get_one::<f64>("RATE_LIMIT").unwrap(),
+    );
 
     // TODO: Support other modes of JIRA authentication
     let auth_string = match jirausername {
@@ -208,7 +259,7 @@ async fn run(arg_matches: ArgMatches) -> Result<(), SimpleError> {
         Some(u) => {
             format!(
                 "Basic {}",
-                Base64Engine::STANDARD_NO_PAD.encode(format!("{}:{}", u, jirapassword.unwrap()))
+                Base64Engine::STANDARD.encode(format!("{}:{}", u, jirapassword.unwrap()))
             )
         }
         // otherwise use AUTHTOKEN to craft the auth header
@@ -217,30 +268,132 @@ async fn run(arg_matches: ArgMatches) -> Result<(), SimpleError> {
         }
     };
 
-    // fetch the content of confluence page along with the comments
-    let page = get_page(hyper_client, auth_string, &base_url, &page_id).await;
+    let raw = arg_matches.get_flag("RAW");
+    let text = |content: String| if raw { content } else { html_to_text(&content) };
 
-    // find secrets in page body and comments
-    let mut content = page.body;
-    content.push_str(&page.comments);
-    let secrets = get_findings(&secret_scanner, page_id, content.as_bytes(), &page.web_link);
+    // fetch the content of confluence page, which is also needed to resume for its link
+    let page = match api {
+        ConfluenceApi::Server => get_page(&web_client, &auth_string, base_url, page_id).await,
+        ConfluenceApi::Cloud => {
+            get_cloud_page(&web_client, &auth_string, &base_url_as_url, page_id).await
+        }
+    };
+
+    let state_file = arg_matches.get_one::<String>("STATE_FILE");
+    let mut state: ScanState = match state_file {
+        Some(path) => scan_state::load(path)?,
+        None => ScanState::default(),
+    };
+    let spool = state_file.map(|path| FindingsSpool::new(path, page_id));
+
+    // the findings are spooled up to `spooled`
+    let (mut secrets, mut pending, mut spooled) =
+        match (state.in_progress.remove(page_id.as_str()), &spool) {
+            // Resuming an interrupted scan of the page from the comment pages it had left, with the
+            // findings it spooled
+            (Some(progress), Some(spool)) => {
+                let secrets: Vec<ConfluenceFinding> = spool.read()?;
+                info!(
+                "Resuming the interrupted scan of {} with {} findings and {} comment pages left",
+                page_id,
+                secrets.len(),
+                progress.pending.len()
+            );
+                let spooled = secrets.len();
+                (secrets, progress.pending, spooled)
+            }
+            _ => {
+                if let Some(spool) = &spool {
+                    spool.remove()?;
+                }
+                // find secrets in page body
+                let secrets = get_findings(
+                    &secret_scanner,
+                    page_id,
+                    text(page.body).as_bytes(),
+                    &page.web_link,
+                    page.last_modified.clone(),
+                );
+                (secrets, comment_urls(api, &base_url_as_url, page_id), 0)
+            }
+        };
+
+    // find secrets in the comments, a page of them at a time
+    loop {
+        // recording where to resume from if the scan is interrupted before the next page
+        if let (Some(path), Some(spool), false) = (state_file, &spool, pending.is_empty()) {
+            spool.append(&secrets[spooled..])?;
+            spooled = secrets.len();
+            state.in_progress.insert(
+                page_id.clone(),
+                PageProgress {
+                    pending: pending.clone(),
+                },
+            );
+            scan_state::save(path, &state)?;
+        }
+        let url = match pending.first() {
+            Some(url) => url.clone(),
+            None => break,
+        };
+        let results = Value::Object(get_json(&web_client, &auth_string, &url).await);
+        let mut comments = String::new();
+        if let Some(Value::Array(results)) = results.get("results") {
+            for comment in results {
+                comments.push_str(storage_value(comment));
+            }
+        }
+        secrets.extend(get_findings(
+            &secret_scanner,
+            page_id,
+            text(comments).as_bytes(),
+            &page.web_link,
+            page.last_modified.clone(),
+        ));
+        pending.remove(0);
+        if let Some(next) = next_comments_url(api, &base_url_as_url, &results) {
+            pending.insert(0, next);
+        }
+    }
 
     // combine and output the results
     let findings: HashSet<ConfluenceFinding> = secrets.into_iter().collect();
     info!("Found {} secrets", findings.len());
-    match secret_scanner.output_findings(&findings) {
-        Ok(_) => Ok(()),
-        Err(err) => Err(SimpleError::with(
+    let verdict = secret_scanner.output_findings(&findings).map_err(|err| {
+        SimpleError::with(
             "failed to output findings",
             SimpleError::new(err.to_string()),
-        )),
+        )
+    })?;
+    // the scan is done, there is nothing to resume
+    if let (Some(path), Some(spool)) = (state_file, spool) {
+        state.in_progress.remove(page_id.as_str());
+        scan_state::save(path, &state)?;
+        spool.remove()?;
     }
+    Ok(verdict)
+}
+
+/// The `--state-file` (see `rusty_hogs::scan_state`): the comment pages left to scan of the
+/// pages whose scans were interrupted
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default)]
+struct ScanState {
+    /// The scans in progress by page ID
+    #[serde(default)]
+    in_progress: BTreeMap<String, PageProgress>,
 }
 
-/// Fetches the body of a confluence page along with the comments
+/// Where an interrupted scan of a page stopped. The body has been scanned.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Default, Clone)]
+struct PageProgress {
+    /// The URLs of the comment pages left to scan, the next first
+    pending: Vec<String>,
+}
+
+/// Fetches the body of a confluence page
 async fn get_page<'a, C>(
-    hyper_client: Client<C>,
-    auth_headers: String,
+    web_client: &WebClient<C>,
+    auth_headers: &String,
     base_url: &str,
     page_id: &str,
 ) -> ConfluencePage
@@ -249,10 +402,11 @@ where
 {
     let base_url_trimmed = base_url.trim_end_matches('/');
     let page_full_url = format!(
-        "{}/rest/api/content/{}?expand=body.storage",
+        "{}/rest/api/content/{}?expand=body.storage,version",
         base_url_trimmed, page_id
     );
-    let json_results = get_json(&hyper_client, &auth_headers, &page_full_url).await;
+    let json_results = get_json(web_client, auth_headers, &page_full_url).await;
+    let last_modified = version_time(json_results.get("version"));
     let body = json_results
         .get("body")
         .unwrap()
@@ -272,38 +426,114 @@ where
         .trim_start_matches('/');
     let web_link = format!("{}/{}", base_url_trimmed, webui);
 
-    let comments_full_url = format!(
-        "{}/rest/api/content/{}/child/comment?expand=body.storage",
-        base_url_trimmed, page_id
-    );
-    let json_results = get_json(&hyper_client, &auth_headers, &comments_full_url).await;
-    let comments = json_results.get("results").unwrap();
-    let mut all_comments: String = String::new();
-    if let Value::Array(comments) = comments {
-        for comment in comments {
-            let comment_body = comment
-                .get("body")
-                .unwrap()
-                .get("storage")
-                .unwrap()
-                .get("value")
-                .unwrap()
-                .as_str()
-                .unwrap();
-            all_comments.push_str(comment_body);
+    ConfluencePage {
+        web_link,
+        body: String::from(body),
+        last_modified,
+    }
+}
+
+/// The first pages of a page's comments: its children on Server/Data Center, and its footer and
+/// inline comments in the v2 API
+fn comment_urls(api: ConfluenceApi, base_url: &Url, page_id: &str) -> Vec<String> {
+    match api {
+        ConfluenceApi::Server => vec![format!(
+            "{}/rest/api/content/{}/child/comment?expand=body.storage",
+            base_url.as_str().trim_end_matches('/'),
+            page_id
+        )],
+        ConfluenceApi::Cloud => ["footer-comments", "inline-comments"]
+            .iter()
+            .map(|kind| {
+                format!(
+                    "{}/api/v2/pages/{}/{}?body-format=storage",
+                    cloud_wiki_base(base_url),
+                    page_id,
+                    kind
+                )
+            })
+            .collect(),
+    }
+}
+
+/// The next page of comments after `results`, if there is one. `next` is relative to the
+/// `_links.base` of the Server/Data Center API, and to the site in the v2 API, e.g.
+/// /wiki/api/v2/pages/1/footer-comments?cursor=..
+fn next_comments_url(api: ConfluenceApi, base_url: &Url, results: &Value) -> Option<String> {
+    let next = results.pointer("/_links/next").and_then(Value::as_str)?;
+    match api {
+        ConfluenceApi::Server => {
+            let base = results
+                .pointer("/_links/base")
+                .and_then(Value::as_str)
+                .unwrap_or_else(|| base_url.as_str());
+            Some(format!("{}{}", base.trim_end_matches('/'), next))
         }
-    };
+        ConfluenceApi::Cloud => base_url.join(next).ok().map(String::from),
+    }
+}
+
+/// Returns the `body.storage.value` of a v2 page or comment, or "" if it has none
+fn storage_value(obj: &Value) -> &str {
+    obj.pointer("/body/storage/value")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+}
+
+/// When the `version` of a page was made: its `when` in the Server API and its `createdAt` in
+/// the v2 API
+fn version_time(version: Option<&Value>) -> Option<String> {
+    let version = version?;
+    version
+        .get("when")
+        .or_else(|| version.get("createdAt"))
+        .and_then(Value::as_str)
+        .and_then(timestamp::normalize)
+}
+
+/// Confluence Cloud's REST APIs live under `/wiki`, which may or may not be in the URL given
+fn cloud_wiki_base(base_url: &Url) -> String {
+    let base = base_url.as_str().trim_end_matches('/');
+    if base.ends_with("/wiki") {
+        String::from(base)
+    } else {
+        format!("{}/wiki", base)
+    }
+}
+
+/// Fetches the body of a Confluence Cloud page using the v2 API
+async fn get_cloud_page<C>(
+    web_client: &WebClient<C>,
+    auth_headers: &String,
+    base_url: &Url,
+    page_id: &str,
+) -> ConfluencePage
+where
+    C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
+{
+    let wiki_base = cloud_wiki_base(base_url);
+    let page_full_url = format!("{}/api/v2/pages/{}?body-format=storage", wiki_base, page_id);
+    let page = Value::Object(get_json(web_client, auth_headers, &page_full_url).await);
+    let links_base = page
+        .pointer("/_links/base")
+        .and_then(Value::as_str)
+        .unwrap_or(&wiki_base);
+    let webui = page
+        .pointer("/_links/webui")
+        .and_then(Value::as_str)
+        .unwrap_or_default();
+    let web_link = format!("{}{}", links_base.trim_end_matches('/'), webui);
 
     ConfluencePage {
         web_link,
-        body: String::from(body),
-        comments: all_comments,
+        body: String::from(storage_value(&page)),
+        last_modified: version_time(page.get("version")),
     }
 }
 
 /// Uses a hyper::client object to perform a GET on the full_url and return parsed serde JSON data
 async fn get_json<'a, C>(
-    hyper_client: &Client<C>,
+    web_client: &WebClient<C>,
     auth_headers: &String,
     full_url: &str,
 ) -> Map<String, Value>
@@ -311,11 +541,16 @@ where
     C: hyper::client::connect::Connect + Clone + Send + Sync + 'static,
 {
     debug!("auth header: {}", auth_headers);
-    let req_builder = Request::builder()
-        .header(AUTHORIZATION, auth_headers)
-        .uri(full_url);
-    let r = req_builder.body(Body::empty()).unwrap();
-    let resp = hyper_client.request(r).await.unwrap();
+    let resp = web_client
+        .send(|| {
+            Request::builder()
+                .header(AUTHORIZATION, auth_headers)
+                .uri(full_url)
+                .body(Body::empty())
+                .unwrap()
+        })
+        .await
+        .unwrap();
     debug!("sending request to {}", full_url);
     let status = resp.status().clone();
     debug!("Response: {:?}", status);
@@ -340,7 +575,9 @@ fn get_findings(
     issue_id: &str,
     content: &[u8],
     web_link: &str,
+    source_timestamp: Option<String>,
 ) -> Vec<ConfluenceFinding> {
+    let content = secret_scanner.normalize(content);
     let lines = content.split(|&x| (x as char) == '\n');
     let mut secrets: Vec<ConfluenceFinding> = Vec::new();
     for new_line in lines {
@@ -348,6 +585,8 @@ fn get_findings(
             secret_scanner.matches_entropy(new_line);
         for (reason, match_iterator) in matches_map {
             let mut secrets_for_reason: HashSet<String> = HashSet::new();
+            let encoded = SecretScanner::encoded(&match_iterator);
+            let assigned_to = SecretScanner::assigned_to(new_line, &match_iterator);
             for matchobj in match_iterator {
                 secrets_for_reason.insert(
                     ASCII
@@ -364,9 +603,104 @@ fn get_findings(
                     page_id: String::from(issue_id),
                     reason,
                     url: String::from(web_link),
+                    encoded,
+                    assigned_to,
+                    source_timestamp: source_timestamp.clone(),
                 });
             }
         }
     }
     secrets
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn detects_confluence_cloud() {
+        let cloud = Url::parse("https://newrelic.atlassian.net/").unwrap();
+        let server = Url::parse("http://confluence.myhost.com:8095/confluence").unwrap();
+        assert_eq!(ConfluenceApi::detect(&cloud), ConfluenceApi::Cloud);
+        assert_eq!(ConfluenceApi::detect(&server), ConfluenceApi::Server);
+        assert_eq!(
+            cloud_wiki_base(&cloud),
+            "https://newrelic.atlassian.net/wiki"
+        );
+        let with_wiki = Url::parse("https://newrelic.atlassian.net/wiki/").unwrap();
+        assert_eq!(
+            cloud_wiki_base(&with_wiki),
+            "https://newrelic.atlassian.net/wiki"
+        );
+    }
+
+    #[test]
+    fn reads_page_versions() {
+        let server = json!({"number": 3, "when": "2024-01-02T04:04:05.000+01:00"});
+        let cloud = json!({"number": 3, "createdAt": "2024-01-02T03:04:05.000Z"});
+        assert_eq!(
+            version_time(Some(&server)).as_deref(),
+            Some("2024-01-02T03:04:05Z")
+        );
+        assert_eq!(
+            version_time(Some(&cloud)).as_deref(),
+            Some("2024-01-02T03:04:05Z")
+        );
+        assert_eq!(version_time(None), None);
+    }
+
+    #[test]
+    fn reads_v2_storage_bodies() {
+        let comment = json!({"id": "7", "body": {"storage": {"value": "<p>token</p>"}}});
+        assert_eq!(storage_value(&comment), "<p>token</p>");
+        assert_eq!(storage_value(&json!({"id": "8"})), "");
+    }
+
+    #[test]
+    fn pages_through_comments() {
+        let cloud = Url::parse("https://newrelic.atlassian.net/").unwrap();
+        assert_eq!(
+            comment_urls(ConfluenceApi::Cloud, &cloud, "1"),
+            vec![
+                "https://newrelic.atlassian.net/wiki/api/v2/pages/1/footer-comments?body-format=storage",
+                "https://newrelic.atlassian.net/wiki/api/v2/pages/1/inline-comments?body-format=storage"
+            ]
+        );
+        let next = json!({"_links": {"next": "/wiki/api/v2/pages/1/footer-comments?cursor=abc"}});
+        assert_eq!(
+            next_comments_url(ConfluenceApi::Cloud, &cloud, &next).as_deref(),
+            Some("https://newrelic.atlassian.net/wiki/api/v2/pages/1/footer-comments?cursor=abc")
+        );
+        assert_eq!(
+            next_comments_url(ConfluenceApi::Cloud, &cloud, &json!({"_links": {}})),
+            None
+        );
+
+        let server = Url::parse("http://confluence.myhost.com:8095/confluence").unwrap();
+        assert_eq!(
+            comment_urls(ConfluenceApi::Server, &server, "1"),
+            vec!["http://confluence.myhost.com:8095/confluence/rest/api/content/1/child/comment?expand=body.storage"]
+        );
+        let next = json!({"_links": {
+            "base": "http://confluence.myhost.com:8095/confluence",
+            "next": "/rest/api/content/1/child/comment?expand=body.storage&start=25&limit=25"
+        }});
+        assert_eq!(
+            next_comments_url(ConfluenceApi::Server, &server, &next).as_deref(),
+            Some("http://confluence.myhost.com:8095/confluence/rest/api/content/1/child/comment?expand=body.storage&start=25&limit=25")
+        );
+
+        // an interrupted scan keeps only the comment pages left in the state file
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let path = path.to_str().unwrap();
+        let mut state = ScanState::default();
+        let pending = comment_urls(ConfluenceApi::Cloud, &cloud, "1");
+        state
+            .in_progress
+            .insert(String::from("1"), PageProgress { pending });
+        scan_state::save(path, &state).unwrap();
+        assert_eq!(scan_state::load::<ScanState>(path).unwrap(), state);
+    }
+}
@@ -0,0 +1,64 @@
+//! The former name of hante_hog, the Slack scanner, kept so that existing scripts and
+//! documentation keep working.
+//!
+//! USAGE:
+//!     slack_hog [hante_hog arguments]
+//!
+//! Warns that the name is deprecated on stderr and runs the hante_hog binary next to it with the
+//! same arguments, exiting with its exit code. See hante_hog for the flags and options.
+
+use std::env;
+use std::path::PathBuf;
+use std::process::{self, Command};
+
+fn main() {
+    eprintln!("warning: slack_hog is deprecated and will be removed, use hante_hog instead");
+    let hante_hog = hante_hog_path();
+    let mut command = Command::new(&hante_hog);
+    command.args(env::args_os().skip(1));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // only returns if hante_hog couldn't be run
+        let e = command.exec();
+        eprintln!("error: failed to run {}: {}", hante_hog.display(), e);
+        process::exit(1);
+    }
+    #[cfg(not(unix))]
+    match command.status() {
+        Ok(status) => process::exit(status.code().unwrap_or(1)),
+        Err(e) => {
+            eprintln!("error: failed to run {}: {}", hante_hog.display(), e);
+            process::exit(1);
+        }
+    }
+}
+
+/// hante_hog in the directory of this binary, or on the PATH if that can't be found
+fn hante_hog_path() -> PathBuf {
+    let name = format!("hante_hog{}", env::consts::EXE_SUFFIX);
+    env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|dir| dir.join(&name)))
+        .filter(|p| p.is_file())
+        .unwrap_or_else(|| PathBuf::from(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use escargot::CargoBuild;
+
+    #[test]
+    fn runs_hante_hog_with_a_warning() {
+        // the shim looks for hante_hog next to its own binary
+        CargoBuild::new().bin("hante_hog").run().unwrap();
+        let shim = CargoBuild::new().bin("slack_hog").run().unwrap();
+        let output = shim.command().arg("--version").output().unwrap();
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(stdout.starts_with("hante_hog "), "{}", stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("slack_hog is deprecated"), "{}", stderr);
+    }
+}
@@ -0,0 +1,471 @@
+//! binwalk-style carving of firmware images, for duroc_hog's `--firmware` mode.
+//!
+//! Firmware blobs are usually a bootloader, a kernel and one or more filesystems concatenated
+//! together, often compressed. `carve` walks the blob looking for the magic bytes of formats it
+//! can unpack (gzip and zlib streams, ZIP entries, tar and cpio archives) and returns the files it
+//! extracts, with their location in the blob. Formats that are recognised but can't be unpacked
+//! here (SquashFS, CramFS, UBI, xz, bzip2, zstd, 7z) are returned as `Signature`s so they can be
+//! reported; extract them with binwalk or unsquashfs and scan the result.
+//!
+//! Extraction is bounded by `CarveLimits`, since a crafted or corrupt image can claim anything.
+//!
+//! ```
+//! use rusty_hogs::firmware_carving::{carve, CarveLimits};
+//! use std::io::Write;
+//! let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+//! gz.write_all(b"password = hunter2").unwrap();
+//! let blob = [vec![0xff; 100], gz.finish().unwrap(), vec![0xff; 100]].concat();
+//! let carving = carve(&blob, &CarveLimits::default());
+//! assert_eq!(carving.files.len(), 1);
+//! assert_eq!(carving.files[0].format, "gzip");
+//! assert_eq!(carving.files[0].range.start, 100);
+//! assert_eq!(carving.files[0].data, b"password = hunter2");
+//! ```
+
+use flate2::bufread::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use std::io::Read;
+use std::ops::Range;
+
+/// Shortest zlib stream output that counts as a carved file, to keep false positives down
+const MIN_ZLIB_LEN: usize = 32;
+const TAR_BLOCK: usize = 512;
+const CPIO_HEADER_LEN: usize = 110;
+/// Formats that are recognised but not unpacked, by magic bytes
+const UNSUPPORTED_SIGNATURES: &[(&[u8], &str)] = &[
+    (b"hsqs", "squashfs"),
+    (b"sqsh", "squashfs"),
+    (b"\x45\x3d\xcd\x28", "cramfs"),
+    (b"UBI#", "ubi"),
+    (b"\xfd7zXZ\x00", "xz"),
+    (b"BZh91AY&SY", "bzip2"),
+    (b"\x28\xb5\x2f\xfd", "zstd"),
+    (b"7z\xbc\xaf\x27\x1c", "7z"),
+];
+
+/// Bounds on what `carve` extracts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CarveLimits {
+    /// Files bigger than this (after decompression) are skipped
+    pub max_file_size: usize,
+    /// Carving stops once this many bytes have been extracted
+    pub max_total_size: usize,
+    /// Carving stops once this many files have been extracted
+    pub max_files: usize,
+}
+
+impl Default for CarveLimits {
+    fn default() -> Self {
+        CarveLimits {
+            max_file_size: 64 * 1024 * 1024,
+            max_total_size: 512 * 1024 * 1024,
+            max_files: 10_000,
+        }
+    }
+}
+
+/// A file extracted from the blob
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CarvedFile {
+    /// "gzip", "zlib", "zip", "tar" or "cpio"
+    pub format: &'static str,
+    /// Name of the file in its archive (or the gzip header), or the format for nameless streams
+    pub name: String,
+    /// Location of the file's data in the blob (compressed, for gzip, zlib and ZIP)
+    pub range: Range<usize>,
+    pub data: Vec<u8>,
+}
+
+impl CarvedFile {
+    /// Whether `data` was decompressed from a stream, and may itself be a blob worth carving
+    pub fn is_stream(&self) -> bool {
+        matches!(self.format, "gzip" | "zlib")
+    }
+}
+
+/// Magic bytes of a format that `carve` recognises but doesn't unpack
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Signature {
+    pub offset: usize,
+    pub format: &'static str,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Carving {
+    pub files: Vec<CarvedFile>,
+    pub unsupported: Vec<Signature>,
+}
+
+/// Extracts the files of every gzip, zlib, ZIP, tar and cpio region of `blob`, in order
+pub fn carve(blob: &[u8], limits: &CarveLimits) -> Carving {
+    let mut carving = Carving::default();
+    let mut total = 0;
+    let mut pos = 0;
+    while pos < blob.len() {
+        if carving.files.len() >= limits.max_files || total >= limits.max_total_size {
+            break;
+        }
+        let found = carve_gzip(blob, pos, limits)
+            .or_else(|| carve_zlib(blob, pos, limits))
+            .or_else(|| carve_zip_entry(blob, pos, limits))
+            .or_else(|| carve_tar(blob, pos, limits))
+            .or_else(|| carve_cpio(blob, pos, limits));
+        match found {
+            Some((files, end)) => {
+                for file in files {
+                    total += file.data.len();
+                    carving.files.push(file);
+                }
+                pos = end.max(pos + 1);
+            }
+            None => {
+                if let Some((_, format)) = UNSUPPORTED_SIGNATURES
+                    .iter()
+                    .find(|(magic, _)| blob[pos..].starts_with(magic))
+                {
+                    carving.unsupported.push(Signature {
+                        offset: pos,
+                        format,
+                    });
+                }
+                pos += 1;
+            }
+        }
+    }
+    carving.files.truncate(limits.max_files);
+    carving
+}
+
+/// Carved files and the offset where the region ends
+type Carved = Option<(Vec<CarvedFile>, usize)>;
+
+/// Reads up to `max` bytes from `decoder`, or `None` if the stream is corrupt or bigger
+fn read_limited<R: Read>(decoder: R, max: usize) -> Option<Vec<u8>> {
+    let mut data = Vec::new();
+    decoder.take(max as u64 + 1).read_to_end(&mut data).ok()?;
+    (data.len() <= max).then_some(data)
+}
+
+/// Strips the leading `/` and `./` of archive member names, so they can be joined to a path
+fn relative_name(name: &[u8]) -> String {
+    let name = String::from_utf8_lossy(name);
+    String::from(name.trim_start_matches("./").trim_start_matches('/'))
+}
+
+fn carve_gzip(blob: &[u8], pos: usize, limits: &CarveLimits) -> Carved {
+    let input = &blob[pos..];
+    // magic, deflate, no reserved flags
+    if input.len() < 18 || !input.starts_with(b"\x1f\x8b\x08") || input[3] & 0xe0 != 0 {
+        return None;
+    }
+    let mut decoder = GzDecoder::new(input);
+    let data = read_limited(&mut decoder, limits.max_file_size)?;
+    let name = decoder
+        .header()
+        .and_then(|h| h.filename())
+        .map(relative_name)
+        .filter(|n| !n.is_empty())
+        .unwrap_or_else(|| String::from("gzip"));
+    let end = pos + input.len() - decoder.into_inner().len();
+    let file = CarvedFile {
+        format: "gzip",
+        name,
+        range: pos..end,
+        data,
+    };
+    Some((vec![file], end))
+}
+
+fn carve_zlib(blob: &[u8], pos: usize, limits: &CarveLimits) -> Carved {
+    let input = &blob[pos..];
+    // deflate with a 32K window, a valid header checksum and no preset dictionary
+    if input.len() < 8
+        || input[0] != 0x78
+        || (u16::from(input[0]) << 8 | u16::from(input[1])) % 31 != 0
+        || input[1] & 0x20 != 0
+    {
+        return None;
+    }
+    let mut decoder = ZlibDecoder::new(input);
+    let data = read_limited(&mut decoder, limits.max_file_size)?;
+    if data.len() < MIN_ZLIB_LEN {
+        return None;
+    }
+    let end = pos + input.len() - decoder.into_inner().len();
+    let file = CarvedFile {
+        format: "zlib",
+        name: String::from("zlib"),
+        range: pos..end,
+        data,
+    };
+    Some((vec![file], end))
+}
+
+fn le16(b: &[u8], at: usize) -> usize {
+    usize::from(u16::from_le_bytes([b[at], b[at + 1]]))
+}
+
+fn le32(b: &[u8], at: usize) -> usize {
+    u32::from_le_bytes([b[at], b[at + 1], b[at + 2], b[at + 3]]) as usize
+}
+
+/// Carves one ZIP local file entry; the entries of an archive are found one after the other
+fn carve_zip_entry(blob: &[u8], pos: usize, limits: &CarveLimits) -> Carved {
+    let input = &blob[pos..];
+    if input.len() < 30 || !input.starts_with(b"PK\x03\x04") {
+        return None;
+    }
+    let flags = le16(input, 6);
+    let method = le16(input, 8);
+    let compressed_size = le32(input, 18);
+    let data_start = 30 + le16(input, 26) + le16(input, 28);
+    if data_start > input.len() {
+        return None;
+    }
+    let name = relative_name(&input[30..30 + le16(input, 26)]);
+    let sizes_known = flags & 0x08 == 0;
+    let (data, data_len) = match method {
+        // stored
+        0 if sizes_known => {
+            let data = input.get(data_start..data_start + compressed_size)?;
+            if data.len() > limits.max_file_size {
+                return Some((Vec::new(), pos + data_start + compressed_size));
+            }
+            (data.to_vec(), compressed_size)
+        }
+        // deflated
+        8 => {
+            let compressed = &input[data_start..];
+            let mut decoder = DeflateDecoder::new(compressed);
+            let data = read_limited(&mut decoder, limits.max_file_size)?;
+            (data, compressed.len() - decoder.into_inner().len())
+        }
+        _ => return None,
+    };
+    let end = pos + data_start + data_len;
+    // directories and encrypted entries are skipped
+    if name.ends_with('/') || name.is_empty() || flags & 0x01 != 0 {
+        return Some((Vec::new(), end));
+    }
+    let file = CarvedFile {
+        format: "zip",
+        name,
+        range: pos + data_start..end,
+        data,
+    };
+    Some((vec![file], end))
+}
+
+/// Parses a NUL or space terminated octal number, as found in tar headers
+fn octal(field: &[u8]) -> Option<usize> {
+    let digits = String::from_utf8_lossy(field);
+    let digits = digits.trim_matches(|c: char| c == '\0' || c == ' ');
+    usize::from_str_radix(digits, 8).ok()
+}
+
+fn carve_tar(blob: &[u8], pos: usize, limits: &CarveLimits) -> Carved {
+    let is_header = |at: usize| blob.get(at + 257..at + 262).is_some_and(|m| m == b"ustar");
+    if !is_header(pos) {
+        return None;
+    }
+    let mut files = Vec::new();
+    let mut long_name: Option<String> = None;
+    let mut at = pos;
+    while is_header(at) && at + TAR_BLOCK <= blob.len() {
+        let header = &blob[at..at + TAR_BLOCK];
+        let Some(size) = octal(&header[124..136]) else {
+            break;
+        };
+        let data_start = at + TAR_BLOCK;
+        let data_end = data_start.saturating_add(size).min(blob.len());
+        let data = &blob[data_start..data_end];
+        let field = |r: Range<usize>| {
+            let f = &header[r];
+            &f[..f.iter().position(|&b| b == 0).unwrap_or(f.len())]
+        };
+        match header[156] {
+            // GNU long name of the next entry
+            b'L' => {
+                long_name = Some(relative_name(
+                    &data[..data.iter().position(|&b| b == 0).unwrap_or(data.len())],
+                ))
+            }
+            b'0' | b'\0' | b'7' => {
+                let name = long_name.take().unwrap_or_else(|| {
+                    let prefix = field(345..500);
+                    if prefix.is_empty() {
+                        relative_name(field(0..100))
+                    } else {
+                        relative_name(&[prefix, b"/", field(0..100)].concat())
+                    }
+                });
+                if size <= limits.max_file_size {
+                    files.push(CarvedFile {
+                        format: "tar",
+                        name,
+                        range: data_start..data_end,
+                        data: data.to_vec(),
+                    });
+                }
+            }
+            _ => long_name = None,
+        }
+        at = data_start.saturating_add(size.div_ceil(TAR_BLOCK) * TAR_BLOCK);
+    }
+    Some((files, at.min(blob.len())))
+}
+
+fn carve_cpio(blob: &[u8], pos: usize, limits: &CarveLimits) -> Carved {
+    let is_header = |at: usize| {
+        blob.get(at..at + 6)
+            .is_some_and(|m| m == b"070701" || m == b"070702")
+    };
+    if !is_header(pos) {
+        return None;
+    }
+    let align = |n: usize| n.div_ceil(4) * 4;
+    let mut files = Vec::new();
+    let mut at = pos;
+    while is_header(at) && at + CPIO_HEADER_LEN <= blob.len() {
+        let hex = |field: usize| {
+            let start = at + 6 + field * 8;
+            usize::from_str_radix(&String::from_utf8_lossy(&blob[start..start + 8]), 16).ok()
+        };
+        let (Some(mode), Some(size), Some(name_size)) = (hex(1), hex(6), hex(11)) else {
+            break;
+        };
+        let name_start = at + CPIO_HEADER_LEN;
+        let name_end = (name_start + name_size.saturating_sub(1)).min(blob.len());
+        let name = relative_name(&blob[name_start..name_end]);
+        if name == "TRAILER!!!" {
+            at = align(name_start + name_size).min(blob.len());
+            break;
+        }
+        let data_start = align(name_start + name_size - pos) + pos;
+        let data_end = data_start.saturating_add(size).min(blob.len());
+        // regular files only
+        if mode & 0o170000 == 0o100000 && size <= limits.max_file_size && data_start <= data_end {
+            files.push(CarvedFile {
+                format: "cpio",
+                name,
+                range: data_start..data_end,
+                data: blob[data_start..data_end].to_vec(),
+            });
+        }
+        at = align(data_start.saturating_add(size) - pos) + pos;
+    }
+    Some((files, at.min(blob.len())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn tar_entry(name: &str, data: &[u8]) -> Vec<u8> {
+        let mut header = vec![0u8; TAR_BLOCK];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        header[124..135].copy_from_slice(format!("{:011o}", data.len()).as_bytes());
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        let mut data = data.to_vec();
+        data.resize(data.len().div_ceil(TAR_BLOCK) * TAR_BLOCK, 0);
+        [header, data].concat()
+    }
+
+    fn cpio_entry(name: &str, mode: u32, data: &[u8]) -> Vec<u8> {
+        let mut entry = format!("070701{:08x}{:08x}", 1, mode).into_bytes();
+        for _ in 0..4 {
+            entry.extend(b"00000000");
+        }
+        entry.extend(format!("{:08x}", data.len()).into_bytes());
+        for _ in 0..4 {
+            entry.extend(b"00000000");
+        }
+        entry.extend(format!("{:08x}00000000", name.len() + 1).into_bytes());
+        entry.extend(name.as_bytes());
+        entry.push(0);
+        entry.resize(entry.len().div_ceil(4) * 4, 0);
+        entry.extend(data);
+        entry.resize(entry.len().div_ceil(4) * 4, 0);
+        entry
+    }
+
+    #[test]
+    fn carves_archives() {
+        let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        zip.start_file("conf/app.ini", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(b"token = zip").unwrap();
+        let zip = zip.finish().unwrap().into_inner();
+        let mut zlib = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::best());
+        zlib.write_all(&b"zlib stream ".repeat(10)).unwrap();
+        let tar = [
+            tar_entry("./etc/shadow", b"root:$1$x"),
+            tar_entry("etc/motd", b"hello"),
+            vec![0u8; 2 * TAR_BLOCK],
+        ]
+        .concat();
+        let cpio = [
+            cpio_entry("bin", 0o040755, b""),
+            cpio_entry("init", 0o100755, b"#!/bin/sh\n"),
+            cpio_entry("TRAILER!!!", 0, b""),
+        ]
+        .concat();
+        let blob = [
+            b"bootloader\0\0".to_vec(),
+            zip,
+            zlib.finish().unwrap(),
+            tar.clone(),
+            cpio,
+            b"hsqs".to_vec(),
+        ]
+        .concat();
+
+        let carving = carve(&blob, &CarveLimits::default());
+        let found: Vec<(&str, &str, &[u8])> = carving
+            .files
+            .iter()
+            .map(|f| (f.format, f.name.as_str(), f.data.as_slice()))
+            .collect();
+        let zlib_data = b"zlib stream ".repeat(10);
+        assert_eq!(
+            found,
+            vec![
+                ("zip", "conf/app.ini", b"token = zip".as_slice()),
+                ("zlib", "zlib", zlib_data.as_slice()),
+                ("tar", "etc/shadow", b"root:$1$x".as_slice()),
+                ("tar", "etc/motd", b"hello".as_slice()),
+                ("cpio", "init", b"#!/bin/sh\n".as_slice()),
+            ]
+        );
+        let shadow = &carving.files[2];
+        assert_eq!(&blob[shadow.range.clone()], b"root:$1$x");
+        assert_eq!(
+            carving.unsupported,
+            vec![Signature {
+                offset: blob.len() - 4,
+                format: "squashfs"
+            }]
+        );
+    }
+
+    #[test]
+    fn respects_limits() {
+        let tar = [
+            tar_entry("a", &[b'a'; 600]),
+            tar_entry("b", b"small"),
+            tar_entry("c", b"small"),
+        ]
+        .concat();
+        let limits = CarveLimits {
+            max_file_size: 100,
+            max_total_size: 1000,
+            max_files: 1,
+        };
+        let carving = carve(&tar, &limits);
+        assert_eq!(carving.files.len(), 1);
+        assert_eq!(carving.files[0].name, "b");
+        // truncated archives end where the blob does
+        assert_eq!(carve(&tar[..700], &CarveLimits::default()).files.len(), 1);
+    }
+}
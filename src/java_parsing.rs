@@ -0,0 +1,320 @@
+//! Helpers for scanning Java artifacts: JAR, WAR, EAR and AAR files are ZIP archives, often with
+//! more JARs nested inside (`WEB-INF/lib/*.jar`, Spring Boot's `BOOT-INF/lib/*.jar`).
+//!
+//! `jar_entries` unpacks an artifact and its nested archives in memory and returns the text of
+//! each file, named with Java's `outer.jar!/inner/path` convention. Along the way
+//! `decode_java_file` makes the Java-specific formats scannable: `.properties` files have their
+//! `\uXXXX` escapes decoded and continuation lines joined, `META-INF/MANIFEST.MF` has its 72-byte
+//! lines unwrapped, and, when asked, `.class` files are reduced to their string constants (the
+//! literals of the code) one per line.
+//!
+//! ```
+//! use rusty_hogs::java_parsing::decode_java_file;
+//! let properties = b"db.password=p\\u00e4ss\\\n    word\n".to_vec();
+//! assert_eq!(
+//!     decode_java_file("app.properties", properties, false),
+//!     "db.password=p\u{e4}ssword\n".as_bytes()
+//! );
+//! ```
+
+use simple_error::SimpleError;
+use std::collections::HashSet;
+use std::io::{Cursor, Read};
+
+/// Extensions of the ZIP archives Java artifacts are packaged in
+pub const JAR_EXTENSIONS: &[&str] = &["jar", "war", "ear", "aar"];
+/// Nesting limit for archives inside archives
+const MAX_DEPTH: usize = 8;
+/// Entries bigger than this are skipped
+const MAX_ENTRY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Whether `path` has the extension of a Java archive
+pub fn is_jar(path: &str) -> bool {
+    has_extension(path, JAR_EXTENSIONS)
+}
+
+fn has_extension(path: &str, extensions: &[&str]) -> bool {
+    path.rsplit_once('.')
+        .is_some_and(|(_, e)| extensions.iter().any(|x| e.eq_ignore_ascii_case(x)))
+}
+
+/// Returns the scannable text of a file from a Java artifact, based on its `path`: decoded for
+/// `.properties` and manifest files, the string constants for `.class` files if
+/// `class_strings` is set, and `data` unchanged otherwise
+pub fn decode_java_file(path: &str, data: Vec<u8>, class_strings: bool) -> Vec<u8> {
+    let name = path.rsplit(['/', '\\']).next().unwrap_or(path);
+    if has_extension(name, &["properties"]) {
+        decode_properties(&data)
+    } else if name.eq_ignore_ascii_case("MANIFEST.MF") {
+        unfold_manifest(&data)
+    } else if class_strings && has_extension(name, &["class"]) {
+        match class_string_constants(&data) {
+            Some(strings) => strings.join("\n").into_bytes(),
+            None => data,
+        }
+    } else {
+        data
+    }
+}
+
+/// Unpacks the Java archive `data`, recursing into nested archives, and returns the path and
+/// text (see `decode_java_file`) of each file. Paths of files in nested archives are joined
+/// with `!/`, e.g. `WEB-INF/lib/client.jar!/client.properties`.
+pub fn jar_entries(
+    data: &[u8],
+    class_strings: bool,
+) -> Result<Vec<(String, Vec<u8>)>, SimpleError> {
+    let mut entries = Vec::new();
+    add_jar_entries(data, "", class_strings, 0, &mut entries)?;
+    Ok(entries)
+}
+
+fn add_jar_entries(
+    data: &[u8],
+    prefix: &str,
+    class_strings: bool,
+    depth: usize,
+    entries: &mut Vec<(String, Vec<u8>)>,
+) -> Result<(), SimpleError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))
+        .map_err(|e| SimpleError::with("not a ZIP archive", e))?;
+    for i in 0..archive.len() {
+        let mut entry = archive
+            .by_index(i)
+            .map_err(|e| SimpleError::with("bad ZIP entry", e))?;
+        if entry.is_dir() || entry.size() > MAX_ENTRY_SIZE {
+            continue;
+        }
+        let path = format!("{}{}", prefix, entry.name());
+        let mut content = Vec::new();
+        if entry.read_to_end(&mut content).is_err() {
+            continue;
+        }
+        if is_jar(&path) && depth < MAX_DEPTH {
+            let nested_prefix = format!("{}!/", path);
+            if add_jar_entries(&content, &nested_prefix, class_strings, depth + 1, entries).is_ok()
+            {
+                continue;
+            }
+        }
+        let text = decode_java_file(&path, content, class_strings);
+        entries.push((path, text));
+    }
+    Ok(())
+}
+
+/// Joins the continuation lines of a manifest (lines starting with a space) to the line before
+pub fn unfold_manifest(data: &[u8]) -> Vec<u8> {
+    let mut output: Vec<u8> = Vec::with_capacity(data.len());
+    for line in data.split(|&b| b == b'\n') {
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        match line.strip_prefix(b" ") {
+            Some(rest) if !output.is_empty() => {
+                output.pop();
+                output.extend_from_slice(rest);
+            }
+            _ => output.extend_from_slice(line),
+        }
+        output.push(b'\n');
+    }
+    output.pop();
+    output
+}
+
+/// Decodes the escapes of a `.properties` file (`\uXXXX`, `\=`, `\:`, `\\`, ...) and joins
+/// lines continued with a trailing backslash, keeping one line per property. `\n`, `\r`, `\t`
+/// and `\f` are left as they are, so values spanning several lines stay on one.
+pub fn decode_properties(data: &[u8]) -> Vec<u8> {
+    let text = String::from_utf8_lossy(data);
+    let mut output = String::with_capacity(text.len());
+    let mut continued = false;
+    for line in text.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let line = if continued {
+            line.trim_start_matches([' ', '\t', '\x0c'])
+        } else {
+            line
+        };
+        // an odd number of trailing backslashes continues the line
+        let trailing = line.len() - line.trim_end_matches('\\').len();
+        continued = trailing % 2 == 1;
+        let line = if continued {
+            &line[..line.len() - 1]
+        } else {
+            line
+        };
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                output.push(c);
+                continue;
+            }
+            match chars.next() {
+                Some('u') => {
+                    let hex: String = chars.clone().take(4).collect();
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Some(decoded) if hex.len() == 4 => {
+                            output.push(decoded);
+                            chars.nth(3);
+                        }
+                        _ => output.push_str("\\u"),
+                    }
+                }
+                Some(e @ ('n' | 'r' | 't' | 'f')) => {
+                    output.push('\\');
+                    output.push(e);
+                }
+                Some(other) => output.push(other),
+                None => output.push('\\'),
+            }
+        }
+        if !continued {
+            output.push('\n');
+        }
+    }
+    if !continued {
+        output.pop();
+    }
+    output.into_bytes()
+}
+
+/// Returns the string literals of a compiled class (its `CONSTANT_String` constants), in
+/// constant pool order and without duplicates, or `None` if `data` isn't a class file
+pub fn class_string_constants(data: &[u8]) -> Option<Vec<String>> {
+    if data.len() < 10 || data[..4] != [0xca, 0xfe, 0xba, 0xbe] {
+        return None;
+    }
+    let u16_at = |at: usize| -> Option<usize> {
+        Some(usize::from(u16::from_be_bytes([
+            *data.get(at)?,
+            *data.get(at + 1)?,
+        ])))
+    };
+    let count = u16_at(8)?;
+    let mut utf8: Vec<Option<String>> = vec![None; count];
+    let mut string_indexes: Vec<usize> = Vec::new();
+    let mut at = 10;
+    let mut index = 1;
+    while index < count {
+        let tag = *data.get(at)?;
+        at += 1;
+        let (len, slots) = match tag {
+            // Utf8
+            1 => {
+                let len = u16_at(at)?;
+                let bytes = data.get(at + 2..at + 2 + len)?;
+                utf8[index] = Some(String::from_utf8_lossy(bytes).into_owned());
+                (2 + len, 1)
+            }
+            // String
+            8 => {
+                string_indexes.push(u16_at(at)?);
+                (2, 1)
+            }
+            // Integer, Float, Fieldref, Methodref, InterfaceMethodref, NameAndType, Dynamic,
+            // InvokeDynamic
+            3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => (4, 1),
+            // Long, Double take two slots
+            5 | 6 => (8, 2),
+            // Class, MethodType, Module, Package
+            7 | 16 | 19 | 20 => (2, 1),
+            // MethodHandle
+            15 => (3, 1),
+            _ => return None,
+        };
+        at += len;
+        index += slots;
+    }
+    let mut seen = HashSet::new();
+    Some(
+        string_indexes
+            .into_iter()
+            .filter_map(|i| utf8.get(i).cloned().flatten())
+            .filter(|s| seen.insert(s.clone()))
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use zip::write::FileOptions;
+
+    fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, data) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    /// A class file whose constant pool holds the literal "s3cr3t" and a long
+    fn class_file() -> Vec<u8> {
+        let mut class = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 52, 0, 7];
+        class.extend([5, 0, 0, 0, 0, 0, 0, 0, 42]); // #1-2 Long
+        class.extend([1, 0, 6]); // #3 Utf8
+        class.extend(b"s3cr3t");
+        class.extend([8, 0, 3]); // #4 String -> #3
+        class.extend([1, 0, 4]); // #5 Utf8, not a literal
+        class.extend(b"main");
+        class.extend([8, 0, 3]); // #6 String -> #3 again
+        class
+    }
+
+    #[test]
+    fn decodes_properties_and_manifests() {
+        let properties = b"# comment\r\nkey\\=name = a\\\\b\\tc \\\n   d\\u0041\\uZZ\nlast=\\";
+        assert_eq!(
+            String::from_utf8(decode_properties(properties)).unwrap(),
+            "# comment\nkey=name = a\\b\\tc dA\\uZZ\nlast="
+        );
+        let manifest = b"Manifest-Version: 1.0\r\nX-Token: abcdefgh\r\n ijklmnop\r\n qrst\r\n";
+        assert_eq!(
+            String::from_utf8(unfold_manifest(manifest)).unwrap(),
+            "Manifest-Version: 1.0\nX-Token: abcdefghijklmnopqrst\n"
+        );
+        assert_eq!(
+            class_string_constants(&class_file()),
+            Some(vec![String::from("s3cr3t")])
+        );
+        assert_eq!(class_string_constants(b"not a class"), None);
+    }
+
+    #[test]
+    fn unpacks_nested_jars() {
+        let inner = zip(&[
+            ("client.properties", b"api.key=\\u0041KIA".as_slice()),
+            ("com/example/Client.class", class_file().as_slice()),
+        ]);
+        let war = zip(&[
+            ("META-INF/MANIFEST.MF", b"A: b\r\n c\r\n".as_slice()),
+            ("WEB-INF/lib/client.jar", inner.as_slice()),
+            ("WEB-INF/lib/broken.jar", b"not a zip".as_slice()),
+        ]);
+
+        let entries = jar_entries(&war, true).unwrap();
+        let entries: Vec<(&str, &[u8])> = entries
+            .iter()
+            .map(|(p, t)| (p.as_str(), t.as_slice()))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                ("META-INF/MANIFEST.MF", b"A: bc\n".as_slice()),
+                (
+                    "WEB-INF/lib/client.jar!/client.properties",
+                    b"api.key=AKIA".as_slice()
+                ),
+                (
+                    "WEB-INF/lib/client.jar!/com/example/Client.class",
+                    b"s3cr3t".as_slice()
+                ),
+                ("WEB-INF/lib/broken.jar", b"not a zip".as_slice()),
+            ]
+        );
+        assert!(is_jar("app.WAR"));
+        assert!(!is_jar("jar"));
+    }
+}
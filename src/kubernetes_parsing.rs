@@ -0,0 +1,251 @@
+//! Structural parsing of Kubernetes manifests and Helm values files, for duroc_hog's
+//! `--kubernetes` mode.
+//!
+//! `yaml_values` parses every document of a YAML file and returns its scalar values along with
+//! their document path, e.g. `spec.template.spec.containers[0].env[3].value`, so findings can
+//! point at the field a secret is in rather than a line of a large multi-document manifest.
+//! Two Kubernetes conventions are understood:
+//!
+//! * the `data` values of `kind: Secret` documents are base64 encoded, and are decoded;
+//! * in `{name: X, value: Y}` pairs, as in container `env` lists, `X` is reported as the key
+//!   `Y` is assigned to instead of `value`.
+//!
+//! Helm chart templates aren't rendered: with their `{{ ... }}` actions they usually aren't valid
+//! YAML, and `yaml_values` returns an error so they can be scanned as text instead. Render them
+//! with `helm template` first to scan the manifests a chart produces.
+//!
+//! ```
+//! use rusty_hogs::kubernetes_parsing::yaml_values;
+//! let manifest = b"kind: Secret\ndata:\n  password: aHVudGVyMg==\n---\nspec:\n  env:\n  - name: DB_PASSWORD\n    value: hunter3\n";
+//! let values = yaml_values(manifest).unwrap();
+//! assert_eq!(values[1].path, "data.password");
+//! assert_eq!(values[1].value, "hunter2");
+//! assert!(values[1].decoded);
+//! assert_eq!(values[3].path, "spec.env[0].value");
+//! assert_eq!(values[3].key, "DB_PASSWORD");
+//! assert_eq!(values[3].document, 1);
+//! ```
+
+use base64::{engine::general_purpose as Base64Engine, Engine as _};
+use serde::Deserialize;
+use serde_yaml::Value;
+use simple_error::SimpleError;
+
+/// A scalar value of a YAML document
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct YamlValue {
+    /// Index of the document in the file, from 0
+    pub document: usize,
+    /// Path of the value in its document, e.g. `spec.containers[0].env[3].value`
+    pub path: String,
+    /// Key the value is assigned to: its mapping key, the `name` of a `{name, value}` pair, or
+    /// the key of the enclosing mapping for items of a sequence
+    pub key: String,
+    pub value: String,
+    /// Whether `value` was base64-decoded from the `data` of a Secret
+    pub decoded: bool,
+}
+
+/// Parses every document of `data` and returns their scalar values in document order
+pub fn yaml_values(data: &[u8]) -> Result<Vec<YamlValue>, SimpleError> {
+    let mut values = Vec::new();
+    for (index, document) in serde_yaml::Deserializer::from_slice(data).enumerate() {
+        let document =
+            Value::deserialize(document).map_err(|e| SimpleError::with("invalid YAML", e))?;
+        let is_secret = document.get("kind").and_then(Value::as_str) == Some("Secret");
+        let mut walker = Walker {
+            document: index,
+            is_secret,
+            values: &mut values,
+        };
+        walker.walk(&document, String::new(), "", false);
+    }
+    Ok(values)
+}
+
+struct Walker<'a> {
+    document: usize,
+    is_secret: bool,
+    values: &'a mut Vec<YamlValue>,
+}
+
+impl Walker<'_> {
+    /// Walks `value`, found at `path` under `key`; `secret_data` is set for the values of a
+    /// Secret's `data`
+    fn walk(&mut self, value: &Value, path: String, key: &str, secret_data: bool) {
+        match value {
+            Value::Mapping(mapping) => {
+                let pair_name = mapping
+                    .get("name")
+                    .and_then(Value::as_str)
+                    .filter(|_| mapping.contains_key("value"));
+                for (k, v) in mapping {
+                    let k = scalar(k).unwrap_or_default();
+                    let child_key = match pair_name {
+                        Some(name) if k == "value" => name,
+                        _ => k.as_str(),
+                    };
+                    let data = secret_data || (self.is_secret && path.is_empty() && k == "data");
+                    self.walk(v, join(&path, &k), child_key, data);
+                }
+            }
+            Value::Sequence(sequence) => {
+                for (i, v) in sequence.iter().enumerate() {
+                    self.walk(v, format!("{}[{}]", path, i), key, false);
+                }
+            }
+            Value::Tagged(tagged) => self.walk(&tagged.value, path, key, secret_data),
+            scalar_value => {
+                let Some(text) = scalar(scalar_value) else {
+                    return;
+                };
+                let decoded = secret_data
+                    .then(|| Base64Engine::STANDARD.decode(text.trim()).ok())
+                    .flatten()
+                    .and_then(|d| String::from_utf8(d).ok());
+                self.values.push(YamlValue {
+                    document: self.document,
+                    path,
+                    key: String::from(key),
+                    decoded: decoded.is_some(),
+                    value: decoded.unwrap_or(text),
+                });
+            }
+        }
+    }
+}
+
+/// Text of a scalar, `None` for nulls and collections
+fn scalar(value: &Value) -> Option<String> {
+    match value {
+        Value::String(s) => Some(s.clone()),
+        Value::Bool(b) => Some(b.to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Tagged(tagged) => scalar(&tagged.value),
+        _ => None,
+    }
+}
+
+/// Appends `key` to `path`, bracketing keys that aren't plain identifiers (`data["tls.key"]`)
+fn join(path: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '/');
+    match (path.is_empty(), plain) {
+        (true, true) => String::from(key),
+        (false, true) => format!("{}.{}", path, key),
+        (_, false) => format!("{}[{:?}]", path, key),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn walks_documents() {
+        let manifest = br#"
+apiVersion: apps/v1
+kind: Deployment
+spec:
+  template:
+    spec:
+      containers:
+      - name: web
+        args: ["--token", "abc"]
+        env:
+        - name: LOG_LEVEL
+          value: debug
+        - name: API_KEY
+          value: !!str 12345
+---
+kind: Secret
+data:
+  tls.key: LS0tLS1CRUdJTg==
+  binary: //79
+stringData:
+  plain: text
+---
+"#;
+        let values = yaml_values(manifest).unwrap();
+        let found: Vec<(usize, &str, &str, &str, bool)> = values
+            .iter()
+            .map(|v| {
+                (
+                    v.document,
+                    v.path.as_str(),
+                    v.key.as_str(),
+                    v.value.as_str(),
+                    v.decoded,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (0, "apiVersion", "apiVersion", "apps/v1", false),
+                (0, "kind", "kind", "Deployment", false),
+                (
+                    0,
+                    "spec.template.spec.containers[0].name",
+                    "name",
+                    "web",
+                    false
+                ),
+                (
+                    0,
+                    "spec.template.spec.containers[0].args[0]",
+                    "args",
+                    "--token",
+                    false
+                ),
+                (
+                    0,
+                    "spec.template.spec.containers[0].args[1]",
+                    "args",
+                    "abc",
+                    false
+                ),
+                (
+                    0,
+                    "spec.template.spec.containers[0].env[0].name",
+                    "name",
+                    "LOG_LEVEL",
+                    false
+                ),
+                (
+                    0,
+                    "spec.template.spec.containers[0].env[0].value",
+                    "LOG_LEVEL",
+                    "debug",
+                    false
+                ),
+                (
+                    0,
+                    "spec.template.spec.containers[0].env[1].name",
+                    "name",
+                    "API_KEY",
+                    false
+                ),
+                (
+                    0,
+                    "spec.template.spec.containers[0].env[1].value",
+                    "API_KEY",
+                    "12345",
+                    false
+                ),
+                (1, "kind", "kind", "Secret", false),
+                (1, "data[\"tls.key\"]", "tls.key", "-----BEGIN", true),
+                (1, "data.binary", "binary", "//79", false),
+                (1, "stringData.plain", "plain", "text", false),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_helm_templates() {
+        let template = b"spec:\n  replicas: {{ .Values.replicas }}\n  {{- with .Values.env }}\n";
+        assert!(yaml_values(template).is_err());
+    }
+}
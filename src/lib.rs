@@ -1,5 +1,55 @@
-extern crate rusty_hog_scanner;
+//! The data source scanners and file parsers of the Rusty Hog binaries, built on the
+//! `SecretScanner` of the `rusty_hog_scanner` crate.
+//!
+//! Everything in `rusty_hog_scanner` is re-exported here, so library users can depend on
+//! `rusty_hogs` alone and use `rusty_hogs::SecretScannerBuilder`, `rusty_hogs::AllowList` and
+//! friends. There is a single `SecretScanner` implementation, the one in that crate.
+//!
+//! ```
+//! use rusty_hogs::git_scanning::GitScanner;
+//! use rusty_hogs::SecretScannerBuilder;
+//! let ss = SecretScannerBuilder::new().set_pretty_print(true).build();
+//! let gs = GitScanner::new_from_scanner(ss);
+//! ```
 
+pub use rusty_hog_scanner::*;
+
+/// Unpacks the artifacts downloaded by qingping_hog
+pub mod archive_parsing;
+/// Scans S3 objects, for berkshire_hog
 pub mod aws_scanning;
+/// Reads the Parquet, Avro and ORC files scanned by duroc_hog and berkshire_hog
+#[cfg(feature = "columnar")]
+pub mod columnar_parsing;
+/// Reads the rows of the SQLite databases and SQL dumps scanned by duroc_hog
+pub mod database_parsing;
+/// Decodes the mbox and EML archives scanned by duroc_hog
+pub mod email_parsing;
+/// Unpacks the filesystems of duroc_hog `--firmware` scans
+pub mod firmware_carving;
+/// Scans the history of git repositories, for choctaw_hog
 pub mod git_scanning;
+/// Scans Google Drive documents, for ankamali_hog
 pub mod google_scanning;
+/// Unpacks the JARs, WARs and AARs scanned by duroc_hog and berkshire_hog
+pub mod java_parsing;
+/// Walks the YAML of duroc_hog `--kubernetes` scans
+pub mod kubernetes_parsing;
+/// Extracts the text of the Confluence pages, Jira issues and Slack messages scanned by essex_hog,
+/// gottingen_hog and hante_hog
+pub mod markup_parsing;
+/// Extracts the text of the APKs and IPAs scanned by pietrain_hog
+pub mod mobile_parsing;
+/// Reassembles the HTTP traffic scanned by ossabaw_hog
+#[cfg(feature = "pcap")]
+pub mod pcap_parsing;
+/// Lists the files of duroc_hog `--preset` scans
+pub mod presets;
+/// Lists and reads the SFTP, FTP, WebDAV and SMB trees duroc_hog and rongchang_hog scan by URL
+pub mod remote_fs;
+/// The `--state-file` hogs resume interrupted scans from
+pub mod scan_state;
+/// Maps the JavaScript bundles scanned by duroc_hog and saddleback_hog back to their sources
+pub mod source_maps;
+/// The rate-limited HTTP client of the hogs that scan web APIs
+pub mod web_client;
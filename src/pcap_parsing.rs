@@ -0,0 +1,612 @@
+//! Packet capture parsing for ossabaw_hog (only built with the `pcap` feature).
+//!
+//! `tcp_segments` reads the TCP segments of a pcap or pcapng capture with `pcap-parser` and
+//! `etherparse` (Ethernet with VLAN tags, Linux cooked, BSD loopback or raw IP link types, IPv4
+//! and IPv6), `reassemble` puts each direction of each TCP
+//! connection back in order, dropping retransmitted data, and `http_messages` splits a
+//! reassembled stream into HTTP/1.x requests or responses with their headers and decoded bodies
+//! (chunked transfer encoding, gzip and deflate are undone).
+//!
+//! ```
+//! use rusty_hogs::pcap_parsing::{http_messages, TcpStream};
+//! let stream = TcpStream {
+//!     data: b"GET /login HTTP/1.1\r\nAuthorization: Basic dXNlcjpwYXNz\r\n\r\n".to_vec(),
+//!     ..Default::default()
+//! };
+//! let messages = http_messages(&stream);
+//! assert_eq!(messages[0].start_line, "GET /login HTTP/1.1");
+//! assert_eq!(messages[0].headers[0].0, "Authorization");
+//! ```
+
+use chrono::{DateTime, TimeZone, Utc};
+use etherparse::{EtherType, NetSlice, SlicedPacket, TransportSlice};
+use pcap_parser::data::{get_packetdata, PacketData};
+use pcap_parser::traits::PcapNGPacketBlock;
+use pcap_parser::{
+    parse_pcap_frame, parse_pcap_frame_be, parse_pcap_header, Block, Linktype, PcapBlockOwned,
+    PcapNGSlice,
+};
+use simple_error::SimpleError;
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr};
+
+const MAX_HTTP_HEADERS: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+/// One direction of a TCP connection
+pub struct Flow {
+    pub src_ip: IpAddr,
+    pub src_port: u16,
+    pub dst_ip: IpAddr,
+    pub dst_port: u16,
+}
+
+impl Default for Flow {
+    fn default() -> Self {
+        Self {
+            src_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            src_port: 0,
+            dst_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            dst_port: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A TCP segment read from the capture
+pub struct TcpSegment {
+    pub timestamp: DateTime<Utc>,
+    pub flow: Flow,
+    pub seq: u32,
+    pub syn: bool,
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// The reassembled payload of one direction of a TCP connection. `timestamps` holds the offset
+/// in `data` of each segment with the time it was captured.
+pub struct TcpStream {
+    pub flow: Flow,
+    pub data: Vec<u8>,
+    pub timestamps: Vec<(usize, DateTime<Utc>)>,
+}
+
+impl TcpStream {
+    /// Capture time of the segment holding the byte at `offset`
+    pub fn timestamp_at(&self, offset: usize) -> Option<DateTime<Utc>> {
+        self.timestamps
+            .iter()
+            .take_while(|(o, _)| *o <= offset)
+            .last()
+            .or_else(|| self.timestamps.first())
+            .map(|(_, ts)| *ts)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+/// An HTTP request or response. `start_line` is the request line (`GET /path HTTP/1.1`) or the
+/// status line (`HTTP/1.1 200 OK`), and `offset` its position in the stream.
+pub struct HttpMessage {
+    pub offset: usize,
+    pub start_line: String,
+    pub headers: Vec<(String, Vec<u8>)>,
+    pub body: Vec<u8>,
+}
+
+/// Reads the TCP segments of a pcap or pcapng capture. Packets that aren't TCP over IP, are
+/// truncated or are IP fragments are skipped, as are packets of link types other than Ethernet,
+/// Linux cooked, BSD loopback and raw IP.
+pub fn tcp_segments(capture: &[u8]) -> Result<Vec<TcpSegment>, SimpleError> {
+    if let Ok(blocks) = PcapNGSlice::from_slice(capture) {
+        return Ok(pcapng_segments(blocks));
+    }
+    let (mut rest, header) =
+        parse_pcap_header(capture).map_err(|_| SimpleError::new("not a pcap or pcapng capture"))?;
+    let parse_frame = if header.is_bigendian() {
+        parse_pcap_frame_be
+    } else {
+        parse_pcap_frame
+    };
+    let resolution = if header.is_nanosecond_precision() {
+        1_000_000_000
+    } else {
+        1_000_000
+    };
+    let mut segments = Vec::new();
+    // a truncated last record ends the capture
+    while let Ok((next, frame)) = parse_frame(rest) {
+        rest = next;
+        let timestamp = timestamp(frame.ts_sec, frame.ts_usec, resolution);
+        segments.extend(parse_packet(header.network, frame.data, timestamp));
+    }
+    Ok(segments)
+}
+
+/// The segments of a pcapng capture, whose packets each have the link type and timestamp
+/// resolution of the interface that captured them
+fn pcapng_segments(blocks: PcapNGSlice) -> Vec<TcpSegment> {
+    // link type, timestamp offset and resolution of the interfaces of the current section
+    let mut interfaces: Vec<(Linktype, u64, u64)> = Vec::new();
+    let mut segments = Vec::new();
+    for block in blocks {
+        let Ok(PcapBlockOwned::NG(block)) = block else {
+            break;
+        };
+        match block {
+            Block::SectionHeader(_) => interfaces.clear(),
+            Block::InterfaceDescription(idb) => interfaces.push((
+                idb.linktype,
+                u64::try_from(idb.ts_offset()).unwrap_or_default(),
+                idb.ts_resolution().unwrap_or(1_000_000),
+            )),
+            Block::EnhancedPacket(epb) => {
+                let Some(&(link_type, offset, resolution)) = interfaces.get(epb.if_id as usize)
+                else {
+                    continue;
+                };
+                let (secs, frac) = epb.decode_ts(offset, resolution);
+                let timestamp = timestamp(secs, frac, resolution);
+                segments.extend(parse_packet(link_type, epb.packet_data(), timestamp));
+            }
+            // simple packets come from the first interface and have no timestamp
+            Block::SimplePacket(spb) => {
+                if let Some(&(link_type, _, _)) = interfaces.first() {
+                    segments.extend(parse_packet(
+                        link_type,
+                        spb.packet_data(),
+                        DateTime::default(),
+                    ));
+                }
+            }
+            _ => {}
+        }
+    }
+    segments
+}
+
+/// A capture time of `secs` seconds and `frac` units of 1/`resolution` second
+fn timestamp(secs: u32, frac: u32, resolution: u64) -> DateTime<Utc> {
+    let nsecs = u64::from(frac) * 1_000_000_000 / resolution.max(1);
+    Utc.timestamp_opt(i64::from(secs), u32::try_from(nsecs).unwrap_or_default())
+        .single()
+        .unwrap_or_default()
+}
+
+/// The TCP segment in a captured packet, if there is one
+fn parse_packet(link_type: Linktype, data: &[u8], timestamp: DateTime<Utc>) -> Option<TcpSegment> {
+    let packet = match get_packetdata(data, link_type, data.len())? {
+        PacketData::L2(frame) if link_type == Linktype::ETHERNET => {
+            SlicedPacket::from_ethernet(frame).ok()?
+        }
+        PacketData::L3(ether_type, ip) => {
+            SlicedPacket::from_ether_type(EtherType(ether_type), ip).ok()?
+        }
+        _ => return None,
+    };
+    let (src_ip, dst_ip) = match packet.net? {
+        NetSlice::Ipv4(ip) if !ip.is_payload_fragmented() => (
+            IpAddr::V4(ip.header().source_addr()),
+            IpAddr::V4(ip.header().destination_addr()),
+        ),
+        NetSlice::Ipv6(ip) if !ip.is_payload_fragmented() => (
+            IpAddr::V6(ip.header().source_addr()),
+            IpAddr::V6(ip.header().destination_addr()),
+        ),
+        _ => return None,
+    };
+    let TransportSlice::Tcp(tcp) = packet.transport? else {
+        return None;
+    };
+    Some(TcpSegment {
+        timestamp,
+        flow: Flow {
+            src_ip,
+            src_port: tcp.source_port(),
+            dst_ip,
+            dst_port: tcp.destination_port(),
+        },
+        seq: tcp.sequence_number(),
+        syn: tcp.syn(),
+        payload: tcp.payload().to_vec(),
+    })
+}
+
+/// Reassembles the segments of each flow in sequence order. Retransmitted data is dropped and
+/// missing data is skipped. A SYN on a flow that already carried data starts a new stream, as
+/// the port pair has been reused for another connection.
+pub fn reassemble(segments: Vec<TcpSegment>) -> Vec<TcpStream> {
+    let mut flows: Vec<(Flow, Vec<TcpSegment>)> = Vec::new();
+    let mut current: HashMap<Flow, usize> = HashMap::new();
+    for segment in segments {
+        let index = match current.get(&segment.flow) {
+            Some(&i) if !(segment.syn && flows[i].1.iter().any(|s| !s.payload.is_empty())) => i,
+            _ => {
+                flows.push((segment.flow, Vec::new()));
+                current.insert(segment.flow, flows.len() - 1);
+                flows.len() - 1
+            }
+        };
+        flows[index].1.push(segment);
+    }
+
+    flows
+        .into_iter()
+        .filter_map(|(flow, segments)| {
+            let base = segments.first()?.seq;
+            // sequence numbers relative to the first segment, allowing for wrap-around
+            let mut ordered: Vec<(i64, &TcpSegment)> = segments
+                .iter()
+                .filter(|s| !s.payload.is_empty())
+                .map(|s| (i64::from(s.seq.wrapping_sub(base) as i32), s))
+                .collect();
+            ordered.sort_by_key(|(rel, _)| *rel);
+            let mut stream = TcpStream {
+                flow,
+                ..Default::default()
+            };
+            let mut next = ordered.first()?.0;
+            for (rel, segment) in ordered {
+                let end = rel + segment.payload.len() as i64;
+                if end <= next {
+                    continue;
+                }
+                let skip = (next - rel).max(0) as usize;
+                stream
+                    .timestamps
+                    .push((stream.data.len(), segment.timestamp));
+                stream.data.extend_from_slice(&segment.payload[skip..]);
+                next = end;
+            }
+            Some(stream)
+        })
+        .collect()
+}
+
+/// Splits a stream into HTTP/1.x messages. Parsing stops at the first data that isn't HTTP, so a
+/// stream of another protocol gives no messages.
+pub fn http_messages(stream: &TcpStream) -> Vec<HttpMessage> {
+    let mut messages = Vec::new();
+    let mut at = 0;
+    while at < stream.data.len() {
+        let Some((message, len)) = parse_http(&stream.data[at..]) else {
+            break;
+        };
+        messages.push(HttpMessage {
+            offset: at,
+            ..message
+        });
+        at += len.max(1);
+    }
+    messages
+}
+
+fn parse_http(data: &[u8]) -> Option<(HttpMessage, usize)> {
+    let owned = |headers: &[httparse::Header]| -> Vec<(String, Vec<u8>)> {
+        headers
+            .iter()
+            .map(|h| (String::from(h.name), h.value.to_vec()))
+            .collect()
+    };
+    let mut header_storage = [httparse::EMPTY_HEADER; MAX_HTTP_HEADERS];
+    let mut request = httparse::Request::new(&mut header_storage);
+    if let Ok(httparse::Status::Complete(len)) = request.parse(data) {
+        let start_line = format!(
+            "{} {} HTTP/1.{}",
+            request.method?, request.path?, request.version?
+        );
+        return http_body(data, &start_line, owned(request.headers), len, None);
+    }
+    let mut header_storage = [httparse::EMPTY_HEADER; MAX_HTTP_HEADERS];
+    let mut response = httparse::Response::new(&mut header_storage);
+    let httparse::Status::Complete(len) = response.parse(data).ok()? else {
+        return None;
+    };
+    let code = response.code?;
+    let start_line = format!(
+        "HTTP/1.{} {} {}",
+        response.version?,
+        code,
+        response.reason.unwrap_or_default()
+    );
+    http_body(
+        data,
+        start_line.trim_end(),
+        owned(response.headers),
+        len,
+        Some(code),
+    )
+}
+
+/// Reads the body that follows the headers, returning the message and its length in the stream
+fn http_body(
+    data: &[u8],
+    start_line: &str,
+    headers: Vec<(String, Vec<u8>)>,
+    header_len: usize,
+    status: Option<u16>,
+) -> Option<(HttpMessage, usize)> {
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| String::from_utf8_lossy(v).trim().to_ascii_lowercase())
+    };
+    let rest = &data[header_len..];
+    let (body, body_len) = if header("transfer-encoding").is_some_and(|te| te.contains("chunked")) {
+        dechunk(rest)
+    } else if let Some(length) = header("content-length").and_then(|l| l.parse::<usize>().ok()) {
+        let length = length.min(rest.len());
+        (rest[..length].to_vec(), length)
+    } else if status.is_some_and(|code| !(code < 200 || code == 204 || code == 304)) {
+        // a response without a length runs to the end of the connection
+        (rest.to_vec(), rest.len())
+    } else {
+        (Vec::new(), 0)
+    };
+    let body = match header("content-encoding").as_deref() {
+        Some("gzip") | Some("x-gzip") => inflate(body, true),
+        Some("deflate") => inflate(body, false),
+        _ => body,
+    };
+    Some((
+        HttpMessage {
+            offset: 0,
+            start_line: String::from(start_line),
+            headers,
+            body,
+        },
+        header_len + body_len,
+    ))
+}
+
+/// Decompresses a gzip or zlib body, or returns it as is if it doesn't decompress
+fn inflate(body: Vec<u8>, gzip: bool) -> Vec<u8> {
+    let mut output = Vec::new();
+    let result = if gzip {
+        flate2::read::GzDecoder::new(&body[..]).read_to_end(&mut output)
+    } else {
+        flate2::read::ZlibDecoder::new(&body[..]).read_to_end(&mut output)
+    };
+    match result {
+        Ok(_) => output,
+        Err(_) => body,
+    }
+}
+
+/// Decodes a chunked body, returning it with the number of bytes it took in the stream
+fn dechunk(data: &[u8]) -> (Vec<u8>, usize) {
+    let mut body = Vec::new();
+    let mut at = 0;
+    while let Some(eol) = data[at..].windows(2).position(|w| w == b"\r\n") {
+        let size_line = String::from_utf8_lossy(&data[at..at + eol]);
+        let Ok(size) = usize::from_str_radix(size_line.split(';').next().unwrap_or("").trim(), 16)
+        else {
+            break;
+        };
+        at += eol + 2;
+        if size == 0 {
+            // skip any trailers up to the empty line
+            while let Some(eol) = data[at..].windows(2).position(|w| w == b"\r\n") {
+                at += eol + 2;
+                if eol == 0 {
+                    break;
+                }
+            }
+            break;
+        }
+        let end = (at + size).min(data.len());
+        body.extend_from_slice(&data[at..end]);
+        at = (end + 2).min(data.len());
+    }
+    (body, at)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv6Addr;
+
+    const PCAP_MAGIC_MICROS: u32 = 0xa1b2_c3d4;
+    const LINKTYPE_ETHERNET: u32 = 1;
+    const ETHERTYPE_IPV4: u16 = 0x0800;
+    const IPPROTO_TCP: u8 = 6;
+
+    /// A TCP header, without checksum, and its payload
+    fn tcp_segment(src_port: u16, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let mut tcp = vec![0u8; 20];
+        tcp[0..2].copy_from_slice(&src_port.to_be_bytes());
+        tcp[2..4].copy_from_slice(&(if src_port == 80 { 40000u16 } else { 80 }).to_be_bytes());
+        tcp[4..8].copy_from_slice(&seq.to_be_bytes());
+        tcp[12] = 5 << 4;
+        tcp[13] = flags;
+        tcp.extend_from_slice(payload);
+        tcp
+    }
+
+    /// An Ethernet + IPv4 + TCP packet record
+    fn packet_record(secs: u32, src_port: u16, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        ip_record(
+            secs,
+            src_port,
+            0x4000,
+            &tcp_segment(src_port, seq, flags, payload),
+        )
+    }
+
+    /// An Ethernet + IPv4 packet record carrying `tcp`, with the flags and fragment offset field
+    /// `fragment`
+    fn ip_record(secs: u32, src_port: u16, fragment: u16, tcp: &[u8]) -> Vec<u8> {
+        let mut ip = vec![0x45, 0];
+        ip.extend_from_slice(&((20 + tcp.len()) as u16).to_be_bytes());
+        ip.extend_from_slice(&[0, 0]);
+        ip.extend_from_slice(&fragment.to_be_bytes());
+        ip.extend_from_slice(&[64, IPPROTO_TCP, 0, 0]);
+        let (src, dst) = if src_port == 80 {
+            ([10, 0, 0, 2], [10, 0, 0, 1])
+        } else {
+            ([10, 0, 0, 1], [10, 0, 0, 2])
+        };
+        ip.extend_from_slice(&src);
+        ip.extend_from_slice(&dst);
+        ip.extend_from_slice(tcp);
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&ETHERTYPE_IPV4.to_be_bytes());
+        frame.extend_from_slice(&ip);
+        // Ethernet padding isn't part of the IP packet
+        frame.extend_from_slice(&[0, 0]);
+        let mut record = Vec::new();
+        for field in [secs, 0, frame.len() as u32, frame.len() as u32] {
+            record.extend_from_slice(&field.to_le_bytes());
+        }
+        record.extend_from_slice(&frame);
+        record
+    }
+
+    fn capture(records: &[Vec<u8>]) -> Vec<u8> {
+        let mut capture = Vec::new();
+        for field in [
+            PCAP_MAGIC_MICROS,
+            0x0004_0002,
+            0,
+            0,
+            65535,
+            LINKTYPE_ETHERNET,
+        ] {
+            capture.extend_from_slice(&field.to_le_bytes());
+        }
+        capture.extend(records.iter().flatten());
+        capture
+    }
+
+    #[test]
+    fn reassembles_tcp_streams() {
+        let request =
+            b"POST /api HTTP/1.1\r\nHost: example.com\r\nContent-Length: 11\r\n\r\nhello world";
+        let (first, second) = request.split_at(30);
+        let pcap = capture(&[
+            packet_record(1, 40000, 999, 0x02, b""),
+            // out of order, with a retransmission
+            packet_record(3, 40000, 1030, 0x18, second),
+            packet_record(2, 40000, 1000, 0x18, first),
+            packet_record(4, 40000, 1000, 0x18, first),
+            packet_record(5, 80, 5000, 0x18, b"HTTP/1.1 204 No Content\r\n\r\n"),
+            // IP fragments are skipped
+            ip_record(
+                6,
+                40000,
+                0x2000,
+                &tcp_segment(40000, 1072, 0x18, b"fragment"),
+            ),
+            // and so is a truncated last record
+            packet_record(7, 40000, 1072, 0x18, b"truncated")[..40].to_vec(),
+        ]);
+        let streams = reassemble(tcp_segments(&pcap).unwrap());
+        assert_eq!(streams.len(), 2);
+        assert_eq!(streams[0].data, request);
+        assert_eq!(streams[0].flow.src_port, 40000);
+        assert_eq!(
+            streams[0].flow.dst_ip,
+            IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))
+        );
+        assert_eq!(streams[0].timestamp_at(0).unwrap().timestamp(), 2);
+        assert_eq!(streams[0].timestamp_at(40).unwrap().timestamp(), 3);
+
+        let messages = http_messages(&streams[0]);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].start_line, "POST /api HTTP/1.1");
+        assert_eq!(messages[0].body, b"hello world");
+        let messages = http_messages(&streams[1]);
+        assert_eq!(messages[0].start_line, "HTTP/1.1 204 No Content");
+
+        assert!(tcp_segments(b"\x0a\x0d\x0d\x0a....").is_err());
+        assert!(tcp_segments(b"not a capture").is_err());
+    }
+
+    /// A little-endian pcapng block
+    fn pcapng_block(block_type: u32, body: &[u8]) -> Vec<u8> {
+        let len = (12 + body.len()) as u32;
+        let mut block = Vec::new();
+        block.extend_from_slice(&block_type.to_le_bytes());
+        block.extend_from_slice(&len.to_le_bytes());
+        block.extend_from_slice(body);
+        block.extend_from_slice(&len.to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn reads_pcapng_captures() {
+        let tcp = tcp_segment(40000, 1000, 0x18, b"GET / HTTP/1.1\r\n\r\n");
+        // Ethernet with a VLAN tag, then IPv6
+        let mut frame = vec![0u8; 12];
+        frame.extend_from_slice(&[0x81, 0x00, 0x00, 0x2a, 0x86, 0xdd]);
+        frame.extend_from_slice(&[0x60, 0, 0, 0]);
+        frame.extend_from_slice(&(tcp.len() as u16).to_be_bytes());
+        frame.extend_from_slice(&[IPPROTO_TCP, 64]);
+        frame.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        frame.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+        frame.extend_from_slice(&tcp);
+        while !frame.len().is_multiple_of(4) {
+            frame.push(0);
+        }
+        // 7.5 seconds, in nanoseconds
+        let ts: u64 = 7_500_000_000;
+        let mut epb = Vec::new();
+        for field in [
+            0,
+            (ts >> 32) as u32,
+            ts as u32,
+            (frame.len() as u32),
+            (frame.len() as u32),
+        ] {
+            epb.extend_from_slice(&field.to_le_bytes());
+        }
+        epb.extend_from_slice(&frame);
+
+        let mut capture = pcapng_block(
+            0x0a0d_0d0a,
+            &[
+                0x4d, 0x3c, 0x2b, 0x1a, 1, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            ],
+        );
+        // Ethernet, with the if_tsresol option set to nanoseconds
+        capture.extend(pcapng_block(
+            1,
+            &[1, 0, 0, 0, 0, 0, 1, 0, 9, 0, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0],
+        ));
+        capture.extend(pcapng_block(6, &epb));
+
+        let segments = tcp_segments(&capture).unwrap();
+        assert_eq!(segments.len(), 1);
+        assert_eq!(segments[0].flow.src_ip, IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(segments[0].flow.dst_port, 80);
+        assert_eq!(segments[0].payload, b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(segments[0].timestamp.timestamp(), 7);
+        assert_eq!(segments[0].timestamp.timestamp_subsec_millis(), 500);
+    }
+
+    #[test]
+    fn decodes_http_bodies() {
+        let stream = TcpStream {
+            data: b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+5\r\nhello\r\n6;ext=1\r\n world\r\n0\r\nX-Trailer: 1\r\n\r\n\
+GET /next HTTP/1.1\r\nCookie: session=abc\r\n\r\n"
+                .to_vec(),
+            ..Default::default()
+        };
+        let messages = http_messages(&stream);
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].body, b"hello world");
+        assert_eq!(messages[1].start_line, "GET /next HTTP/1.1");
+        assert_eq!(
+            messages[1].headers[0],
+            (String::from("Cookie"), b"session=abc".to_vec())
+        );
+
+        let not_http = TcpStream {
+            data: b"\x16\x03\x01\x02\x00".to_vec(),
+            ..Default::default()
+        };
+        assert!(http_messages(&not_http).is_empty());
+    }
+}
@@ -0,0 +1,344 @@
+//! Scan presets for duroc_hog (`--preset <NAME>`): a list of well-known file locations to scan
+//! instead of a whole directory tree, and rules tailored to the files found there.
+//!
+//! The `workstation` preset is a quick credential hygiene check of a laptop or desktop: shell
+//! histories, `.netrc`, package manager and registry credentials (`.npmrc`, `.pypirc`,
+//! `.docker/config.json`, ...), kube configs, the AWS, Google Cloud and Azure CLI credential
+//! files, SSH keys and the local storage of Chrome, Edge, Brave and Firefox, for Linux, macOS
+//! and Windows home directories.
+//!
+//! The `ci` preset checks a repository's CI configuration (GitHub Actions workflows and actions,
+//! GitLab CI, Jenkinsfiles, CircleCI, Azure Pipelines, Travis, Bitbucket Pipelines, Drone,
+//! Buildkite, CodeBuild and Cloud Build) for secrets set inline in `env:` blocks, tokens echoed
+//! into the build log, passwords on command lines, and `set -x` tracing or debug logging that
+//! would print secrets to the log. Its paths are relative to the repository root.
+//!
+//! ```
+//! use rusty_hogs::presets;
+//! let workstation = presets::find("workstation").unwrap();
+//! assert!(workstation.paths.contains(&".netrc"));
+//! let home = tempfile::tempdir().unwrap();
+//! std::fs::write(home.path().join(".netrc"), "machine example.com password hunter2").unwrap();
+//! assert_eq!(workstation.existing_paths(home.path()), vec![home.path().join(".netrc")]);
+//! ```
+
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Paths relative to a root directory, where a `*` component matches any directory entry
+/// (e.g. a browser profile), and the rules to add for them
+pub struct Preset {
+    pub name: &'static str,
+    /// What `paths` are relative to when no directory is given
+    pub root: Root,
+    pub paths: &'static [&'static str],
+    pub rules_json: &'static str,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Root {
+    /// The current user's home directory
+    Home,
+    /// The current directory, e.g. a repository checkout
+    CurrentDir,
+}
+
+pub const WORKSTATION: Preset = Preset {
+    name: "workstation",
+    root: Root::Home,
+    paths: &[
+        // shell and REPL histories
+        ".bash_history",
+        ".zsh_history",
+        ".sh_history",
+        ".history",
+        ".local/share/fish/fish_history",
+        ".python_history",
+        ".node_repl_history",
+        ".psql_history",
+        ".mysql_history",
+        ".sqlite_history",
+        ".irb_history",
+        ".local/share/powershell/PSReadLine/ConsoleHost_history.txt",
+        "AppData/Roaming/Microsoft/Windows/PowerShell/PSReadLine/ConsoleHost_history.txt",
+        // package managers, registries and git
+        ".netrc",
+        "_netrc",
+        ".npmrc",
+        ".yarnrc",
+        ".yarnrc.yml",
+        ".pypirc",
+        ".pip/pip.conf",
+        ".config/pip/pip.conf",
+        ".gem/credentials",
+        ".cargo/credentials",
+        ".cargo/credentials.toml",
+        ".git-credentials",
+        ".config/git/credentials",
+        ".docker/config.json",
+        ".kube/config",
+        ".ssh",
+        // cloud and SaaS CLIs
+        ".aws/credentials",
+        ".aws/config",
+        ".config/gcloud/application_default_credentials.json",
+        ".config/gcloud/legacy_credentials",
+        "AppData/Roaming/gcloud/application_default_credentials.json",
+        "AppData/Roaming/gcloud/legacy_credentials",
+        ".azure/accessTokens.json",
+        ".azure/msal_token_cache.json",
+        ".azure/service_principal_entries.json",
+        ".config/gh/hosts.yml",
+        "AppData/Roaming/GitHub CLI/hosts.yml",
+        ".config/doctl/config.yaml",
+        ".terraform.d/credentials.tfrc.json",
+        // browser local storage
+        ".config/google-chrome/*/Local Storage/leveldb",
+        ".config/chromium/*/Local Storage/leveldb",
+        ".config/microsoft-edge/*/Local Storage/leveldb",
+        ".config/BraveSoftware/Brave-Browser/*/Local Storage/leveldb",
+        "Library/Application Support/Google/Chrome/*/Local Storage/leveldb",
+        "Library/Application Support/Microsoft Edge/*/Local Storage/leveldb",
+        "Library/Application Support/BraveSoftware/Brave-Browser/*/Local Storage/leveldb",
+        "AppData/Local/Google/Chrome/User Data/*/Local Storage/leveldb",
+        "AppData/Local/Microsoft/Edge/User Data/*/Local Storage/leveldb",
+        "AppData/Local/BraveSoftware/Brave-Browser/User Data/*/Local Storage/leveldb",
+        ".mozilla/firefox/*/webappsstore.sqlite",
+        "Library/Application Support/Firefox/Profiles/*/webappsstore.sqlite",
+        "AppData/Roaming/Mozilla/Firefox/Profiles/*/webappsstore.sqlite",
+    ],
+    rules_json: include_str!("presets/workstation_rules.json"),
+};
+
+pub const CI: Preset = Preset {
+    name: "ci",
+    root: Root::CurrentDir,
+    paths: &[
+        ".github/workflows",
+        ".github/actions",
+        ".gitlab-ci.yml",
+        ".gitlab-ci.yaml",
+        ".gitlab/ci",
+        "Jenkinsfile",
+        "jenkins",
+        ".circleci/config.yml",
+        ".circleci/config.yaml",
+        "azure-pipelines.yml",
+        "azure-pipelines.yaml",
+        ".azure-pipelines",
+        ".travis.yml",
+        "bitbucket-pipelines.yml",
+        ".drone.yml",
+        ".buildkite",
+        "buildspec.yml",
+        "cloudbuild.yaml",
+        "cloudbuild.yml",
+    ],
+    rules_json: include_str!("presets/ci_rules.json"),
+};
+
+pub const PRESETS: &[Preset] = &[WORKSTATION, CI];
+
+/// The preset called `name`
+pub fn find(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|p| p.name == name)
+}
+
+/// The current user's home directory, from `HOME` (or `USERPROFILE` on Windows)
+pub fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .filter(|h| !h.is_empty())
+        .map(PathBuf::from)
+}
+
+impl Preset {
+    /// The directory `paths` are relative to by default
+    pub fn default_root(&self) -> Option<PathBuf> {
+        match self.root {
+            Root::Home => home_dir(),
+            Root::CurrentDir => std::env::current_dir().ok(),
+        }
+    }
+
+    /// The files and directories of the preset that exist under `root`
+    pub fn existing_paths(&self, root: &Path) -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        for path in self.paths {
+            let mut candidates = vec![root.to_path_buf()];
+            for component in path.split('/') {
+                candidates = candidates
+                    .into_iter()
+                    .flat_map(|c| -> Vec<PathBuf> {
+                        if component == "*" {
+                            match c.read_dir() {
+                                Ok(entries) => {
+                                    let mut entries: Vec<PathBuf> =
+                                        entries.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+                                    entries.sort();
+                                    entries
+                                }
+                                Err(_) => Vec::new(),
+                            }
+                        } else {
+                            vec![c.join(component)]
+                        }
+                    })
+                    .collect();
+            }
+            found.extend(candidates.into_iter().filter(|c| c.exists()));
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusty_hog_scanner::SecretScannerBuilder;
+
+    #[test]
+    fn finds_workstation_files() {
+        let home = tempfile::tempdir().unwrap();
+        let storage = home
+            .path()
+            .join(".config/google-chrome/Profile 1/Local Storage/leveldb");
+        std::fs::create_dir_all(&storage).unwrap();
+        std::fs::create_dir_all(home.path().join(".aws")).unwrap();
+        std::fs::write(home.path().join(".aws/credentials"), "").unwrap();
+
+        let paths = WORKSTATION.existing_paths(home.path());
+        assert_eq!(paths, vec![home.path().join(".aws/credentials"), storage]);
+        assert!(find("laptop").is_none());
+    }
+
+    #[test]
+    fn workstation_rules_match_their_files() {
+        let ss = SecretScannerBuilder::new()
+            .add_rules_json_str(WORKSTATION.rules_json)
+            .try_build()
+            .unwrap();
+        let cases: &[(&str, &[u8], &str)] = &[
+            (
+                "/home/u/.netrc",
+                b"machine example.com login u password hunter2",
+                "netrc Password",
+            ),
+            (
+                "/home/u/.npmrc",
+                b"//registry.npmjs.org/:_authToken=npm_abcdef",
+                "npm Auth Token",
+            ),
+            (
+                "/home/u/.kube/config",
+                b"    token: eyJhbGciOi",
+                "Kubeconfig Credential",
+            ),
+            (
+                "/home/u/.aws/credentials",
+                b"aws_secret_access_key = abc/def",
+                "AWS CLI Secret",
+            ),
+            (
+                "/home/u/.bash_history",
+                b"export GITHUB_TOKEN=abc123",
+                "Secret in Shell History",
+            ),
+            (
+                "/home/u/.zsh_history",
+                b"curl -s -u admin:hunter2 https://x",
+                "Password on Command Line",
+            ),
+            (
+                "C:\\Users\\u\\.docker\\config.json",
+                br#""auth": "dXNlcjpwYXNz""#,
+                "Docker Registry Auth",
+            ),
+        ];
+        for (path, line, rule) in cases {
+            assert!(
+                ss.matches_entropy_path(line, path).contains_key(*rule),
+                "{} not found in {}",
+                rule,
+                path
+            );
+        }
+        assert!(!ss
+            .matches_entropy_path(b"password hunter2", "/home/u/notes.txt")
+            .contains_key("netrc Password"));
+    }
+
+    #[test]
+    fn ci_rules_match_their_files() {
+        let ss = SecretScannerBuilder::new()
+            .add_rules_json_str(CI.rules_json)
+            .try_build()
+            .unwrap();
+        let rules: serde_json::Value = serde_json::from_str(CI.rules_json).unwrap();
+        let workflow = "/repo/.github/workflows/deploy.yml";
+        let cases: &[(&str, &str, Option<&str>)] = &[
+            (
+                workflow,
+                "      API_TOKEN: tok_8f3k2m9x",
+                Some("CI Inline Secret Variable"),
+            ),
+            (workflow, "      API_TOKEN: ${{ secrets.API_TOKEN }}", None),
+            (
+                "/repo/.gitlab-ci.yml",
+                "    DB_PASSWORD: \"$DB_PASSWORD\"",
+                None,
+            ),
+            (
+                "/repo/.gitlab-ci.yml",
+                "    - echo \"token is $CI_JOB_TOKEN\"",
+                Some("Secret Echoed in CI Log"),
+            ),
+            (
+                workflow,
+                "        run: echo ${{ secrets.NPM_TOKEN }}",
+                Some("Secret Echoed in CI Log"),
+            ),
+            (
+                "/repo/Jenkinsfile",
+                "    sh 'set -ex'",
+                Some("Shell Tracing in CI"),
+            ),
+            ("/repo/Jenkinsfile", "    sh 'set -e'", None),
+            (
+                "/repo/.circleci/config.yml",
+                "          command: bash -x deploy.sh",
+                Some("Shell Tracing in CI"),
+            ),
+            (
+                workflow,
+                "  ACTIONS_STEP_DEBUG: true",
+                Some("CI Debug Logging Enabled"),
+            ),
+            (
+                "/repo/.travis.yml",
+                "  - docker login -u ci -p hunter22 registry.example.com",
+                Some("Password on CI Command Line"),
+            ),
+            (
+                "/repo/.travis.yml",
+                "  - docker login -u ci -p \"$REGISTRY_PASSWORD\"",
+                None,
+            ),
+            ("/repo/deploy.sh", "set -x", None),
+        ];
+        for (path, line, rule) in cases {
+            let matches = ss.matches_entropy_path(line.as_bytes(), path);
+            let ci_rules: Vec<&String> = matches
+                .keys()
+                .filter(|r| rules[r.as_str()].is_object())
+                .collect();
+            assert_eq!(
+                ci_rules.first().map(|r| r.as_str()),
+                *rule,
+                "{} in {}",
+                line,
+                path
+            );
+        }
+    }
+}
@@ -0,0 +1,289 @@
+//! A minimal FTP client: passive mode, `MLSD` listings and binary `RETR`
+
+use super::{join, Credentials, RemoteEntry, RemoteFs};
+use chrono::NaiveDateTime;
+use rusty_hog_scanner::timestamp;
+use simple_error::SimpleError;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct FtpFs {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    peer: IpAddr,
+}
+
+impl FtpFs {
+    /// Connects and logs in, as `anonymous` if there's no username
+    pub fn connect(host: &str, port: u16, credentials: &Credentials) -> Result<Self, SimpleError> {
+        let stream = TcpStream::connect((host, port))
+            .map_err(|e| SimpleError::with(&format!("failed to connect to {}", host), e))?;
+        stream.set_read_timeout(Some(TIMEOUT)).ok();
+        stream.set_write_timeout(Some(TIMEOUT)).ok();
+        let peer = stream
+            .peer_addr()
+            .map_err(|e| SimpleError::with("not connected", e))?
+            .ip();
+        let writer = stream
+            .try_clone()
+            .map_err(|e| SimpleError::with("failed to clone the control connection", e))?;
+        let mut ftp = FtpFs {
+            reader: BufReader::new(stream),
+            writer,
+            peer,
+        };
+        ftp.expect(&[220])?;
+        let user = credentials.username.as_deref().unwrap_or("anonymous");
+        let (code, _) = ftp.command(&format!("USER {}", user))?;
+        if code == 331 {
+            let password = credentials.password.as_deref().unwrap_or("anonymous@");
+            ftp.send(&format!("PASS {}", password))?;
+            ftp.expect(&[230, 202])
+                .map_err(|e| SimpleError::with("login failed", e))?;
+        } else if code != 230 {
+            return Err(SimpleError::new(format!("login failed with code {}", code)));
+        }
+        ftp.send("TYPE I")?;
+        ftp.expect(&[200])?;
+        Ok(ftp)
+    }
+
+    fn send(&mut self, command: &str) -> Result<(), SimpleError> {
+        self.writer
+            .write_all(format!("{}\r\n", command).as_bytes())
+            .map_err(|e| SimpleError::with("failed to send an FTP command", e))
+    }
+
+    fn command(&mut self, command: &str) -> Result<(u16, String), SimpleError> {
+        self.send(command)?;
+        read_reply(&mut self.reader)
+    }
+
+    /// Reads a reply and checks its code is one of `codes`
+    fn expect(&mut self, codes: &[u16]) -> Result<String, SimpleError> {
+        let (code, text) = read_reply(&mut self.reader)?;
+        if codes.contains(&code) {
+            Ok(text)
+        } else {
+            Err(SimpleError::new(format!(
+                "unexpected FTP reply {} {}",
+                code, text
+            )))
+        }
+    }
+
+    /// Opens a passive data connection, with `EPSV` or else `PASV`. The server's address in a
+    /// `PASV` reply is ignored in favour of the control connection's, as clients usually do.
+    fn data_connection(&mut self) -> Result<TcpStream, SimpleError> {
+        let (code, text) = self.command("EPSV")?;
+        let port = match code {
+            229 => parse_epsv(&text),
+            _ => {
+                let (code, text) = self.command("PASV")?;
+                if code != 227 {
+                    return Err(SimpleError::new(format!("PASV failed: {} {}", code, text)));
+                }
+                parse_pasv(&text)
+            }
+        }
+        .ok_or_else(|| SimpleError::new("couldn't parse the passive mode reply"))?;
+        let stream = TcpStream::connect_timeout(&SocketAddr::new(self.peer, port), TIMEOUT)
+            .map_err(|e| SimpleError::with("failed to open the data connection", e))?;
+        stream.set_read_timeout(Some(TIMEOUT)).ok();
+        Ok(stream)
+    }
+
+    /// Sends a command transferring data and reads up to `limit + 1` bytes of it
+    fn transfer(&mut self, command: &str, limit: u64) -> Result<Vec<u8>, SimpleError> {
+        let data_stream = self.data_connection()?;
+        self.send(command)?;
+        self.expect(&[125, 150])?;
+        let mut data = Vec::new();
+        data_stream
+            .take(limit + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| SimpleError::with("failed to read the data connection", e))?;
+        // the data connection is closed by now; a transfer cut short gets a 426 reply
+        let (code, text) = read_reply(&mut self.reader)?;
+        if data.len() as u64 > limit {
+            return Err(SimpleError::new(format!("bigger than {} bytes", limit)));
+        }
+        if code != 226 && code != 250 {
+            return Err(SimpleError::new(format!(
+                "transfer failed: {} {}",
+                code, text
+            )));
+        }
+        Ok(data)
+    }
+}
+
+impl RemoteFs for FtpFs {
+    fn read_dir(&mut self, path: &str) -> Result<Vec<RemoteEntry>, SimpleError> {
+        let listing = self.transfer(&format!("MLSD {}", path), u64::MAX - 1)?;
+        Ok(String::from_utf8_lossy(&listing)
+            .lines()
+            .filter_map(|line| parse_mlsd_line(path, line))
+            .collect())
+    }
+
+    fn read_file(&mut self, path: &str, limit: u64) -> Result<Vec<u8>, SimpleError> {
+        self.transfer(&format!("RETR {}", path), limit)
+    }
+}
+
+impl Drop for FtpFs {
+    fn drop(&mut self) {
+        let _ = self.send("QUIT");
+    }
+}
+
+/// Reads a reply, joining the lines of multi-line replies (`123-First line` ... `123 Last line`)
+fn read_reply<R: BufRead>(reader: &mut R) -> Result<(u16, String), SimpleError> {
+    let mut text = String::new();
+    let mut code = String::new();
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .read_line(&mut line)
+            .map_err(|e| SimpleError::with("failed to read an FTP reply", e))?;
+        if read == 0 {
+            return Err(SimpleError::new("FTP connection closed"));
+        }
+        let line = line.trim_end_matches(['\r', '\n']);
+        if code.is_empty() {
+            code = String::from(line.get(..3).unwrap_or(line));
+        } else {
+            text.push('\n');
+        }
+        let separator = line
+            .starts_with(code.as_str())
+            .then(|| line.as_bytes().get(3));
+        match separator {
+            Some(Some(b' ')) | Some(None) => {
+                text.push_str(line.get(4..).unwrap_or_default());
+                break;
+            }
+            Some(Some(b'-')) => text.push_str(&line[4..]),
+            // lines in the middle of a multi-line reply may have no code
+            _ => text.push_str(line),
+        }
+    }
+    let code = code
+        .parse()
+        .map_err(|_| SimpleError::new(format!("invalid FTP reply {:?}", text)))?;
+    Ok((code, text))
+}
+
+/// The port of an `EPSV` reply: `Entering Extended Passive Mode (|||6446|)`
+fn parse_epsv(text: &str) -> Option<u16> {
+    let start = text.find("(|||")? + 4;
+    let end = start + text[start..].find('|')?;
+    text[start..end].parse().ok()
+}
+
+/// The port of a `PASV` reply: `Entering Passive Mode (192,168,1,2,25,46)`
+fn parse_pasv(text: &str) -> Option<u16> {
+    let start = text.find('(')? + 1;
+    let end = start + text[start..].find(')')?;
+    let numbers: Vec<u16> = text[start..end]
+        .split(',')
+        .map(|n| n.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    match numbers.as_slice() {
+        [_, _, _, _, high, low] if *high < 256 && *low < 256 => Some(high * 256 + low),
+        _ => None,
+    }
+}
+
+/// An entry of an `MLSD` listing: `type=file;size=1024;modify=20240301120000; name`
+fn parse_mlsd_line(dir: &str, line: &str) -> Option<RemoteEntry> {
+    let (facts, name) = line.split_once(' ')?;
+    let mut is_dir = None;
+    let mut size = None;
+    let mut modified = None;
+    for fact in facts.split(';') {
+        let Some((key, value)) = fact.split_once('=') else {
+            continue;
+        };
+        match key.to_ascii_lowercase().as_str() {
+            "type" => {
+                is_dir = match value.to_ascii_lowercase().as_str() {
+                    "file" => Some(false),
+                    "dir" => Some(true),
+                    // cdir and pdir are the directory itself and its parent; links and devices
+                    // aren't followed
+                    _ => None,
+                }
+            }
+            "size" => size = value.parse().ok(),
+            // YYYYMMDDHHMMSS[.sss] in UTC
+            "modify" => {
+                modified = NaiveDateTime::parse_from_str(value, "%Y%m%d%H%M%S%.f")
+                    .ok()
+                    .map(|t| timestamp::format(t.and_utc()))
+            }
+            _ => {}
+        }
+    }
+    Some(RemoteEntry {
+        path: join(dir, name),
+        is_dir: is_dir?,
+        size,
+        modified,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn parses_replies() {
+        let mut replies = Cursor::new(
+            "220-Welcome\r\n to the server\r\n220 Ready\r\n230 Logged in\r\n".as_bytes(),
+        );
+        assert_eq!(
+            read_reply(&mut replies).unwrap(),
+            (220, String::from("Welcome\n to the server\nReady"))
+        );
+        assert_eq!(
+            read_reply(&mut replies).unwrap(),
+            (230, String::from("Logged in"))
+        );
+        assert!(read_reply(&mut replies).is_err());
+
+        assert_eq!(
+            parse_epsv("Entering Extended Passive Mode (|||6446|)"),
+            Some(6446)
+        );
+        assert_eq!(
+            parse_pasv("Entering Passive Mode (192,168,1,2,25,46)."),
+            Some(25 * 256 + 46)
+        );
+        assert_eq!(parse_pasv("Entering Passive Mode (1,2,3)"), None);
+
+        assert_eq!(
+            parse_mlsd_line(
+                "/pub",
+                "type=file;size=1024;modify=20240301120000; my notes.txt"
+            ),
+            Some(RemoteEntry {
+                path: String::from("/pub/my notes.txt"),
+                is_dir: false,
+                size: Some(1024),
+                modified: Some(String::from("2024-03-01T12:00:00Z")),
+            })
+        );
+        assert!(
+            parse_mlsd_line("/", "Type=dir;perm=el; etc")
+                .unwrap()
+                .is_dir
+        );
+        assert_eq!(parse_mlsd_line("/pub", "type=cdir; ."), None);
+    }
+}
@@ -0,0 +1,217 @@
+//! SFTP through the ssh2 crate, on the libssh2 that git2 is built with
+
+use super::{join, Credentials, RemoteEntry, RemoteFs};
+use base64::{engine::general_purpose as Base64Engine, Engine as _};
+use rusty_hog_scanner::timestamp;
+use simple_error::SimpleError;
+use ssh2::{CheckResult, HashType, KnownHostFileKind, Session, Sftp};
+use std::io::Read;
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+
+/// Milliseconds before a blocking libssh2 call gives up
+const TIMEOUT_MS: u32 = 60_000;
+
+/// An SFTP session. The session owns the TCP stream it runs over.
+pub struct SftpFs {
+    session: Session,
+    sftp: Sftp,
+}
+
+impl SftpFs {
+    /// Connects, checks the host key and authenticates, with `credentials.ssh_key`, else
+    /// `credentials.password`, else the keys of the SSH agent
+    pub fn connect(host: &str, port: u16, credentials: &Credentials) -> Result<Self, SimpleError> {
+        let stream = TcpStream::connect((host, port))
+            .map_err(|e| SimpleError::with(&format!("failed to connect to {}", host), e))?;
+        let mut session =
+            Session::new().map_err(|e| SimpleError::with("failed to create an SSH session", e))?;
+        session.set_tcp_stream(stream);
+        session.set_timeout(TIMEOUT_MS);
+        session
+            .handshake()
+            .map_err(|e| SimpleError::with("SSH handshake failed", e))?;
+        check_host_key(
+            &session,
+            host,
+            port,
+            credentials.host_fingerprint.as_deref(),
+        )?;
+
+        let user = match &credentials.username {
+            Some(u) => u.clone(),
+            None => std::env::var("USER")
+                .or_else(|_| std::env::var("USERNAME"))
+                .map_err(|_| SimpleError::new("no SFTP username, give one in the URL"))?,
+        };
+        authenticate(&session, &user, credentials)?;
+
+        let sftp = session
+            .sftp()
+            .map_err(|e| SimpleError::with("failed to start the SFTP subsystem", e))?;
+        Ok(SftpFs { session, sftp })
+    }
+}
+
+/// Accepts the server's host key if its fingerprint is `fingerprint`, or, without one, if it is
+/// in `~/.ssh/known_hosts`
+fn check_host_key(
+    session: &Session,
+    host: &str,
+    port: u16,
+    fingerprint: Option<&str>,
+) -> Result<(), SimpleError> {
+    let (key, hash) = match (session.host_key(), session.host_key_hash(HashType::Sha256)) {
+        (Some((key, _)), Some(hash)) => (key, hash),
+        _ => return Err(SimpleError::new("the server sent no host key")),
+    };
+    let actual = fingerprint_string(hash);
+    if let Some(expected) = fingerprint {
+        return if expected.trim_end_matches('=') == actual {
+            Ok(())
+        } else {
+            Err(SimpleError::new(format!(
+                "host key of {} is {}, not {}",
+                host, actual, expected
+            )))
+        };
+    }
+
+    let known_hosts_path = home_dir()
+        .map(|h| h.join(".ssh").join("known_hosts"))
+        .ok_or_else(|| SimpleError::new("couldn't find ~/.ssh/known_hosts"))?;
+    let mut hosts = session
+        .known_hosts()
+        .map_err(|e| SimpleError::with("failed to read known hosts", e))?;
+    // a missing file is the same as an unknown host
+    let _ = hosts.read_file(&known_hosts_path, KnownHostFileKind::OpenSSH);
+    match hosts.check_port(host, port, key) {
+        CheckResult::Match => Ok(()),
+        CheckResult::Mismatch => Err(SimpleError::new(format!(
+            "host key of {} ({}) doesn't match the one in {:?}",
+            host, actual, known_hosts_path
+        ))),
+        _ => Err(SimpleError::new(format!(
+            "{} isn't in {:?}; check its host key fingerprint is {} and pass it with --ssh-host-key",
+            host, known_hosts_path, actual
+        ))),
+    }
+}
+
+fn authenticate(
+    session: &Session,
+    user: &str,
+    credentials: &Credentials,
+) -> Result<(), SimpleError> {
+    if let Some(key) = &credentials.ssh_key {
+        return session
+            .userauth_pubkey_file(user, None, key, credentials.password.as_deref())
+            .map_err(|e| SimpleError::with("SSH key authentication failed", e));
+    }
+    if let Some(password) = &credentials.password {
+        return session
+            .userauth_password(user, password)
+            .map_err(|e| SimpleError::with("SSH password authentication failed", e));
+    }
+    authenticate_with_agent(session, user)
+}
+
+/// Tries each key of the SSH agent in turn
+fn authenticate_with_agent(session: &Session, user: &str) -> Result<(), SimpleError> {
+    let mut agent = session
+        .agent()
+        .map_err(|e| SimpleError::with("failed to initialize the SSH agent client", e))?;
+    let mut authenticated = false;
+    if agent.connect().is_ok() && agent.list_identities().is_ok() {
+        let identities = agent.identities().unwrap_or_default();
+        authenticated = identities
+            .iter()
+            .any(|identity| agent.userauth(user, identity).is_ok());
+        let _ = agent.disconnect();
+    }
+    if authenticated {
+        Ok(())
+    } else {
+        Err(SimpleError::new(
+            "SSH agent authentication failed; give a password or --ssh-key",
+        ))
+    }
+}
+
+impl RemoteFs for SftpFs {
+    fn read_dir(&mut self, path: &str) -> Result<Vec<RemoteEntry>, SimpleError> {
+        let listing = self
+            .sftp
+            .readdir(Path::new(path))
+            .map_err(|e| SimpleError::with(&format!("failed to list {}", path), e))?;
+        let mut entries = Vec::new();
+        for (entry_path, stat) in listing {
+            let entry_name = match entry_path.file_name() {
+                Some(name) => name.to_string_lossy().into_owned(),
+                None => continue,
+            };
+            // links and special files are skipped
+            let is_dir = if stat.is_dir() {
+                true
+            } else if stat.is_file() {
+                false
+            } else {
+                continue;
+            };
+            entries.push(RemoteEntry {
+                path: join(path, &entry_name),
+                is_dir,
+                size: stat.size,
+                modified: stat
+                    .mtime
+                    .and_then(|t| timestamp::from_unix(i64::try_from(t).ok()?)),
+            });
+        }
+        Ok(entries)
+    }
+
+    fn read_file(&mut self, path: &str, limit: u64) -> Result<Vec<u8>, SimpleError> {
+        let file = self
+            .sftp
+            .open(Path::new(path))
+            .map_err(|e| SimpleError::with(&format!("failed to open {}", path), e))?;
+        let mut data = Vec::new();
+        file.take(limit.saturating_add(1))
+            .read_to_end(&mut data)
+            .map_err(|e| SimpleError::with(&format!("failed to read {}", path), e))?;
+        if data.len() as u64 > limit {
+            return Err(SimpleError::new(format!("bigger than {} bytes", limit)));
+        }
+        Ok(data)
+    }
+}
+
+impl Drop for SftpFs {
+    fn drop(&mut self) {
+        let _ = self.session.disconnect(None, "done", None);
+    }
+}
+
+fn home_dir() -> Option<PathBuf> {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(|h| Path::new(&h).to_path_buf())
+}
+
+/// A SHA256 host key fingerprint in OpenSSH's format: `SHA256:` and unpadded base64
+fn fingerprint_string(hash: &[u8]) -> String {
+    format!("SHA256:{}", Base64Engine::STANDARD_NO_PAD.encode(hash))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_fingerprints() {
+        assert_eq!(
+            fingerprint_string(&[0; 32]),
+            "SHA256:AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA"
+        );
+    }
+}
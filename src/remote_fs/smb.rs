@@ -0,0 +1,728 @@
+//! A minimal SMB2/SMB3 client (MS-SMB2) for reading Windows file shares: dialects 2.0.2 to 3.0.2,
+//! NTLMv2 authentication and message signing. Shares that require encryption aren't supported.
+
+mod ntlm;
+
+use super::{join, Credentials, RemoteEntry, RemoteFs};
+use aes::cipher::{BlockEncrypt, KeyInit};
+use aes::Aes128;
+use hmac::{Hmac, Mac};
+use log::{self, debug};
+use rusty_hog_scanner::timestamp;
+use sha2::Sha256;
+use simple_error::SimpleError;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+const HEADER_SIZE: usize = 64;
+const DIALECTS: [u16; 4] = [0x0202, 0x0210, 0x0300, 0x0302];
+/// Bytes read or listed per request; bigger ones would take more than one credit
+const CHUNK_SIZE: u32 = 65536;
+const CREDIT_REQUEST: u16 = 64;
+/// Seconds from the FILETIME epoch, 1601-01-01, to the Unix epoch
+const FILETIME_UNIX_EPOCH: i64 = 11_644_473_600;
+
+const NEGOTIATE: u16 = 0x00;
+const SESSION_SETUP: u16 = 0x01;
+const LOGOFF: u16 = 0x02;
+const TREE_CONNECT: u16 = 0x03;
+const TREE_DISCONNECT: u16 = 0x04;
+const CREATE: u16 = 0x05;
+const CLOSE: u16 = 0x06;
+const READ: u16 = 0x08;
+const QUERY_DIRECTORY: u16 = 0x0e;
+
+const FLAG_ASYNC: u32 = 0x02;
+const FLAG_SIGNED: u32 = 0x08;
+const SIGNING_ENABLED: u16 = 0x01;
+const SIGNING_REQUIRED: u16 = 0x02;
+const SESSION_GUEST_OR_NULL: u16 = 0x03;
+const SESSION_ENCRYPT_DATA: u16 = 0x04;
+const SHARE_ENCRYPT_DATA: u32 = 0x8000;
+
+const STATUS_SUCCESS: u32 = 0;
+const STATUS_PENDING: u32 = 0x0000_0103;
+const STATUS_NO_MORE_FILES: u32 = 0x8000_0006;
+const STATUS_END_OF_FILE: u32 = 0xc000_0011;
+const STATUS_MORE_PROCESSING_REQUIRED: u32 = 0xc000_0016;
+
+const ATTRIBUTE_DIRECTORY: u32 = 0x10;
+const ATTRIBUTE_REPARSE_POINT: u32 = 0x400;
+
+pub struct SmbFs {
+    stream: TcpStream,
+    share: String,
+    dialect: u16,
+    message_id: u64,
+    session_id: u64,
+    tree_id: u32,
+    /// Key to sign requests with, if the server requires signing
+    signing_key: Option<[u8; 16]>,
+}
+
+/// A file or directory opened on the share
+struct Open {
+    file_id: [u8; 16],
+    size: u64,
+}
+
+impl SmbFs {
+    /// Connects to `share` and logs in, as `guest` if there's no username. The username may
+    /// carry the domain, as `DOMAIN\user` or `DOMAIN;user`.
+    pub fn connect(
+        host: &str,
+        port: u16,
+        share: &str,
+        credentials: &Credentials,
+    ) -> Result<Self, SimpleError> {
+        let address = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| SimpleError::with(&format!("failed to resolve {}", host), e))?
+            .next()
+            .ok_or_else(|| SimpleError::new(format!("failed to resolve {}", host)))?;
+        let stream = TcpStream::connect_timeout(&address, TIMEOUT)
+            .map_err(|e| SimpleError::with(&format!("failed to connect to {}", host), e))?;
+        stream.set_read_timeout(Some(TIMEOUT)).ok();
+        stream.set_write_timeout(Some(TIMEOUT)).ok();
+        let mut smb = SmbFs {
+            stream,
+            share: String::from(share),
+            dialect: 0,
+            message_id: 0,
+            session_id: 0,
+            tree_id: 0,
+            signing_key: None,
+        };
+        let signing_required = smb.negotiate()?;
+        let (domain, user) = split_domain(
+            credentials.username.as_deref().unwrap_or("guest"),
+            credentials.domain.as_deref(),
+        );
+        let password = credentials.password.as_deref().unwrap_or_default();
+        smb.session_setup(&user, &domain, password, signing_required)?;
+        smb.tree_connect(host)?;
+        Ok(smb)
+    }
+
+    /// Negotiates the dialect; returns whether the server requires signing
+    fn negotiate(&mut self) -> Result<bool, SimpleError> {
+        let mut body = Vec::new();
+        body.extend(36u16.to_le_bytes());
+        body.extend((DIALECTS.len() as u16).to_le_bytes());
+        body.extend(SIGNING_ENABLED.to_le_bytes());
+        // reserved, capabilities, client GUID and client start time
+        body.extend([0; 2 + 4 + 16 + 8]);
+        for dialect in DIALECTS {
+            body.extend(dialect.to_le_bytes());
+        }
+        let response = self.call(NEGOTIATE, &body, &[STATUS_SUCCESS])?;
+        let security_mode = u16_at(&response, HEADER_SIZE + 2)?;
+        self.dialect = u16_at(&response, HEADER_SIZE + 4)?;
+        if !DIALECTS.contains(&self.dialect) {
+            return Err(SimpleError::new(format!(
+                "the server chose unsupported SMB dialect 0x{:04x}",
+                self.dialect
+            )));
+        }
+        debug!("negotiated SMB dialect 0x{:04x}", self.dialect);
+        Ok(security_mode & SIGNING_REQUIRED != 0)
+    }
+
+    /// Authenticates with NTLMv2 in two `SESSION_SETUP` round trips
+    fn session_setup(
+        &mut self,
+        user: &str,
+        domain: &str,
+        password: &str,
+        signing_required: bool,
+    ) -> Result<(), SimpleError> {
+        let ntlm = ntlm::Ntlm::new(user, domain, password);
+        let response = self
+            .call(
+                SESSION_SETUP,
+                &session_setup_body(&ntlm.negotiate_token()),
+                &[STATUS_MORE_PROCESSING_REQUIRED],
+            )
+            .map_err(|e| SimpleError::with("SMB login failed", e))?;
+        self.session_id = u64_at(&response, 40)?;
+        let (token, session_key) = ntlm.authenticate_token(security_buffer(&response)?)?;
+        let response = self
+            .call(
+                SESSION_SETUP,
+                &session_setup_body(&token),
+                &[STATUS_SUCCESS],
+            )
+            .map_err(|e| SimpleError::with(&format!("SMB login as {} failed", user), e))?;
+        let session_flags = u16_at(&response, HEADER_SIZE + 2)?;
+        if session_flags & SESSION_ENCRYPT_DATA != 0 {
+            return Err(SimpleError::new("the server requires SMB encryption"));
+        }
+        if signing_required && session_flags & SESSION_GUEST_OR_NULL == 0 {
+            self.signing_key = Some(if self.dialect >= 0x0300 {
+                smb3_signing_key(&session_key)
+            } else {
+                session_key
+            });
+        }
+        Ok(())
+    }
+
+    fn tree_connect(&mut self, host: &str) -> Result<(), SimpleError> {
+        let path = ntlm::utf16le(&format!("\\\\{}\\{}", host, self.share));
+        let mut body = Vec::new();
+        body.extend(9u16.to_le_bytes());
+        body.extend([0; 2]);
+        body.extend(((HEADER_SIZE + 8) as u16).to_le_bytes());
+        body.extend((path.len() as u16).to_le_bytes());
+        body.extend(path);
+        let response = self
+            .call(TREE_CONNECT, &body, &[STATUS_SUCCESS])
+            .map_err(|e| SimpleError::with(&format!("failed to open share {}", self.share), e))?;
+        self.tree_id = u32_at(&response, 36)?;
+        if u32_at(&response, HEADER_SIZE + 4)? & SHARE_ENCRYPT_DATA != 0 {
+            return Err(SimpleError::new(format!(
+                "share {} requires SMB encryption",
+                self.share
+            )));
+        }
+        Ok(())
+    }
+
+    /// Opens the file or directory at `path`, relative to the share
+    fn create(&mut self, path: &str, directory: bool) -> Result<Open, SimpleError> {
+        // FILE_LIST_DIRECTORY or FILE_READ_DATA, FILE_READ_ATTRIBUTES and SYNCHRONIZE
+        let desired_access: u32 = 0x0010_0081;
+        // FILE_DIRECTORY_FILE or FILE_NON_DIRECTORY_FILE
+        let create_options: u32 = if directory { 0x01 } else { 0x40 };
+        let name = ntlm::utf16le(path);
+        let mut body = Vec::new();
+        body.extend(57u16.to_le_bytes());
+        // security flags and oplock level
+        body.extend([0; 2]);
+        // impersonation level: Impersonation
+        body.extend(2u32.to_le_bytes());
+        // create flags and reserved
+        body.extend([0; 16]);
+        body.extend(desired_access.to_le_bytes());
+        // file attributes
+        body.extend([0; 4]);
+        // share read, write and delete
+        body.extend(7u32.to_le_bytes());
+        // FILE_OPEN
+        body.extend(1u32.to_le_bytes());
+        body.extend(create_options.to_le_bytes());
+        body.extend(((HEADER_SIZE + 56) as u16).to_le_bytes());
+        body.extend((name.len() as u16).to_le_bytes());
+        // no create contexts
+        body.extend([0; 8]);
+        body.extend(name);
+        // the buffer can't be empty, even for the root of the share
+        body.push(0);
+        let response = self.call(CREATE, &body, &[STATUS_SUCCESS])?;
+        Ok(Open {
+            file_id: file_id_at(&response, HEADER_SIZE + 64)?,
+            size: u64_at(&response, HEADER_SIZE + 48)?,
+        })
+    }
+
+    fn close(&mut self, open: &Open) -> Result<(), SimpleError> {
+        let mut body = Vec::new();
+        body.extend(24u16.to_le_bytes());
+        body.extend([0; 6]);
+        body.extend(open.file_id);
+        self.call(CLOSE, &body, &[STATUS_SUCCESS]).map(|_| ())
+    }
+
+    /// The entries of an open directory, `dir` being its path in the share's file tree
+    fn query_directory(&mut self, open: &Open, dir: &str) -> Result<Vec<RemoteEntry>, SimpleError> {
+        let pattern = ntlm::utf16le("*");
+        let mut entries = Vec::new();
+        let mut first = true;
+        loop {
+            let mut body = Vec::new();
+            body.extend(33u16.to_le_bytes());
+            // FileDirectoryInformation, restarting the scan the first time
+            body.push(0x01);
+            body.push(if first { 0x01 } else { 0x00 });
+            body.extend([0; 4]);
+            body.extend(open.file_id);
+            body.extend(((HEADER_SIZE + 32) as u16).to_le_bytes());
+            body.extend((pattern.len() as u16).to_le_bytes());
+            body.extend(CHUNK_SIZE.to_le_bytes());
+            body.extend(&pattern);
+            let response = self.call(
+                QUERY_DIRECTORY,
+                &body,
+                &[STATUS_SUCCESS, STATUS_NO_MORE_FILES],
+            )?;
+            if u32_at(&response, 8)? == STATUS_NO_MORE_FILES {
+                return Ok(entries);
+            }
+            let offset = u16_at(&response, HEADER_SIZE + 2)? as usize;
+            let len = u32_at(&response, HEADER_SIZE + 4)? as usize;
+            let buffer = response
+                .get(offset..offset + len)
+                .ok_or_else(|| SimpleError::new("invalid QUERY_DIRECTORY response"))?;
+            entries.extend(parse_directory_entries(dir, buffer)?);
+            first = false;
+        }
+    }
+
+    /// Reads up to `CHUNK_SIZE` bytes of an open file from `offset`; `None` at its end
+    fn read_chunk(&mut self, open: &Open, offset: u64) -> Result<Option<Vec<u8>>, SimpleError> {
+        let mut body = Vec::new();
+        body.extend(49u16.to_le_bytes());
+        // padding (where the data goes in the response) and flags
+        body.extend([0x50, 0]);
+        body.extend(CHUNK_SIZE.to_le_bytes());
+        body.extend(offset.to_le_bytes());
+        body.extend(open.file_id);
+        // minimum count, channel, remaining bytes and read channel info
+        body.extend([0; 16]);
+        body.push(0);
+        let response = self.call(READ, &body, &[STATUS_SUCCESS, STATUS_END_OF_FILE])?;
+        if u32_at(&response, 8)? == STATUS_END_OF_FILE {
+            return Ok(None);
+        }
+        let data_offset = *response
+            .get(HEADER_SIZE + 2)
+            .ok_or_else(|| SimpleError::new("truncated SMB message"))?
+            as usize;
+        let len = u32_at(&response, HEADER_SIZE + 4)? as usize;
+        match response.get(data_offset..data_offset + len) {
+            Some(chunk) if !chunk.is_empty() => Ok(Some(chunk.to_vec())),
+            Some(_) => Ok(None),
+            None => Err(SimpleError::new("invalid READ response")),
+        }
+    }
+
+    /// Sends a request and reads its response, failing unless its status is one of `expected`
+    fn call(
+        &mut self,
+        command: u16,
+        body: &[u8],
+        expected: &[u32],
+    ) -> Result<Vec<u8>, SimpleError> {
+        let message_id = self.message_id;
+        self.message_id += 1;
+        let mut message = Vec::with_capacity(HEADER_SIZE + body.len());
+        message.extend(b"\xfeSMB");
+        message.extend((HEADER_SIZE as u16).to_le_bytes());
+        // credit charge, reserved in 2.0.2
+        let credit_charge: u16 = if self.dialect >= 0x0210 { 1 } else { 0 };
+        message.extend(credit_charge.to_le_bytes());
+        // status
+        message.extend([0; 4]);
+        message.extend(command.to_le_bytes());
+        message.extend(CREDIT_REQUEST.to_le_bytes());
+        // the final SESSION_SETUP isn't signed; the key only exists after it
+        let flags = if self.signing_key.is_some() {
+            FLAG_SIGNED
+        } else {
+            0
+        };
+        message.extend(flags.to_le_bytes());
+        // next command
+        message.extend([0; 4]);
+        message.extend(message_id.to_le_bytes());
+        // process ID
+        message.extend(0xfeffu32.to_le_bytes());
+        message.extend(self.tree_id.to_le_bytes());
+        message.extend(self.session_id.to_le_bytes());
+        message.extend([0; 16]);
+        message.extend(body);
+        if let Some(key) = &self.signing_key {
+            let signature = sign(self.dialect, key, &message);
+            message[48..64].copy_from_slice(&signature);
+        }
+
+        let mut frame = (message.len() as u32).to_be_bytes().to_vec();
+        frame.extend(message);
+        self.stream
+            .write_all(&frame)
+            .map_err(|e| SimpleError::with("failed to send an SMB request", e))?;
+        loop {
+            let response = self.receive()?;
+            if response.get(..4) != Some(b"\xfeSMB".as_slice()) {
+                return Err(SimpleError::new("invalid SMB2 response"));
+            }
+            let status = u32_at(&response, 8)?;
+            let async_reply = u32_at(&response, 16)? & FLAG_ASYNC != 0;
+            if u64_at(&response, 24)? != message_id || (status == STATUS_PENDING && async_reply) {
+                // an interim response; the final one follows
+                continue;
+            }
+            if !expected.contains(&status) {
+                return Err(status_error(status));
+            }
+            return Ok(response);
+        }
+    }
+
+    /// Reads a message framed by the 4 byte header of SMB over TCP
+    fn receive(&mut self) -> Result<Vec<u8>, SimpleError> {
+        let mut header = [0u8; 4];
+        self.stream
+            .read_exact(&mut header)
+            .map_err(|e| SimpleError::with("failed to read an SMB response", e))?;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+        let mut message = vec![0; len];
+        self.stream
+            .read_exact(&mut message)
+            .map_err(|e| SimpleError::with("failed to read an SMB response", e))?;
+        Ok(message)
+    }
+
+    /// The path of `path` in the share: `/share/dir/file` is `dir\file`
+    fn share_path(&self, path: &str) -> Result<String, SimpleError> {
+        let path = path.trim_start_matches('/');
+        let (share, rest) = path.split_once('/').unwrap_or((path, ""));
+        if !share.eq_ignore_ascii_case(&self.share) {
+            return Err(SimpleError::new(format!(
+                "{} isn't on share {}",
+                path, self.share
+            )));
+        }
+        Ok(rest.trim_matches('/').replace('/', "\\"))
+    }
+}
+
+impl RemoteFs for SmbFs {
+    fn read_dir(&mut self, path: &str) -> Result<Vec<RemoteEntry>, SimpleError> {
+        let open = self.create(&self.share_path(path)?, true)?;
+        let entries = self.query_directory(&open, path);
+        self.close(&open)?;
+        entries
+    }
+
+    fn read_file(&mut self, path: &str, limit: u64) -> Result<Vec<u8>, SimpleError> {
+        let open = self.create(&self.share_path(path)?, false)?;
+        if open.size > limit {
+            self.close(&open)?;
+            return Err(SimpleError::new(format!("bigger than {} bytes", limit)));
+        }
+        let mut data = Vec::with_capacity(open.size as usize);
+        let result = loop {
+            if data.len() as u64 >= open.size {
+                break Ok(());
+            }
+            match self.read_chunk(&open, data.len() as u64) {
+                Ok(Some(chunk)) => data.extend(chunk),
+                Ok(None) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.close(&open)?;
+        result.map(|_| data)
+    }
+}
+
+impl Drop for SmbFs {
+    fn drop(&mut self) {
+        let body = [4, 0, 0, 0];
+        if self.tree_id != 0 {
+            let _ = self.call(TREE_DISCONNECT, &body, &[STATUS_SUCCESS]);
+        }
+        if self.session_id != 0 {
+            let _ = self.call(LOGOFF, &body, &[STATUS_SUCCESS]);
+        }
+    }
+}
+
+fn session_setup_body(token: &[u8]) -> Vec<u8> {
+    let mut body = Vec::new();
+    body.extend(25u16.to_le_bytes());
+    // flags
+    body.push(0);
+    body.push(SIGNING_ENABLED as u8);
+    // capabilities and channel
+    body.extend([0; 8]);
+    body.extend(((HEADER_SIZE + 24) as u16).to_le_bytes());
+    body.extend((token.len() as u16).to_le_bytes());
+    // previous session
+    body.extend([0; 8]);
+    body.extend(token);
+    body
+}
+
+/// The security token of a `SESSION_SETUP` response
+fn security_buffer(response: &[u8]) -> Result<&[u8], SimpleError> {
+    let offset = u16_at(response, HEADER_SIZE + 4)? as usize;
+    let len = u16_at(response, HEADER_SIZE + 6)? as usize;
+    response
+        .get(offset..offset + len)
+        .ok_or_else(|| SimpleError::new("invalid SESSION_SETUP response"))
+}
+
+/// The `source_timestamp` of a FILETIME, in 100 nanosecond intervals since 1601, or None if it
+/// is unset
+fn filetime(time: u64) -> Option<String> {
+    (time != 0)
+        .then(|| timestamp::from_unix((time / 10_000_000) as i64 - FILETIME_UNIX_EPOCH))
+        .flatten()
+}
+
+/// The entries of a `FileDirectoryInformation` buffer, without `.`, `..` and reparse points
+/// (symbolic links and junctions)
+fn parse_directory_entries(dir: &str, buffer: &[u8]) -> Result<Vec<RemoteEntry>, SimpleError> {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+    loop {
+        let entry = &buffer[offset..];
+        let next = u32_at(entry, 0)? as usize;
+        let last_write = u64_at(entry, 24)?;
+        let size = u64_at(entry, 40)?;
+        let attributes = u32_at(entry, 56)?;
+        let name_len = u32_at(entry, 60)? as usize;
+        let name: Vec<u16> = entry
+            .get(64..64 + name_len)
+            .ok_or_else(|| SimpleError::new("invalid directory entry"))?
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], *c.get(1).unwrap_or(&0)]))
+            .collect();
+        let name = String::from_utf16_lossy(&name);
+        if name != "." && name != ".." && attributes & ATTRIBUTE_REPARSE_POINT == 0 {
+            let is_dir = attributes & ATTRIBUTE_DIRECTORY != 0;
+            entries.push(RemoteEntry {
+                path: join(dir, &name),
+                is_dir,
+                size: (!is_dir).then_some(size),
+                modified: filetime(last_write),
+            });
+        }
+        if next == 0 || offset + next >= buffer.len() {
+            return Ok(entries);
+        }
+        offset += next;
+    }
+}
+
+/// The domain and user of a username of the form `DOMAIN\user` or `DOMAIN;user`, or else
+/// `domain`
+fn split_domain(username: &str, domain: Option<&str>) -> (String, String) {
+    match username.split_once(['\\', ';']) {
+        Some((domain, user)) => (String::from(domain), String::from(user)),
+        None => (
+            String::from(domain.unwrap_or_default()),
+            String::from(username),
+        ),
+    }
+}
+
+/// The signature of a message: HMAC-SHA256 before SMB 3, AES-CMAC from it
+fn sign(dialect: u16, key: &[u8; 16], message: &[u8]) -> [u8; 16] {
+    if dialect >= 0x0300 {
+        aes_cmac(key, message)
+    } else {
+        let mut mac =
+            <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC takes keys of any size");
+        mac.update(message);
+        let mut signature = [0u8; 16];
+        signature.copy_from_slice(&mac.finalize().into_bytes()[..16]);
+        signature
+    }
+}
+
+/// The SMB 3.0 signing key, derived from the session key with the SP800-108 counter mode KDF
+fn smb3_signing_key(session_key: &[u8; 16]) -> [u8; 16] {
+    let mut mac =
+        <Hmac<Sha256> as Mac>::new_from_slice(session_key).expect("HMAC takes keys of any size");
+    mac.update(&1u32.to_be_bytes());
+    mac.update(b"SMB2AESCMAC\0");
+    mac.update(&[0]);
+    mac.update(b"SmbSign\0");
+    mac.update(&128u32.to_be_bytes());
+    let mut key = [0u8; 16];
+    key.copy_from_slice(&mac.finalize().into_bytes()[..16]);
+    key
+}
+
+/// AES-CMAC (RFC 4493)
+fn aes_cmac(key: &[u8; 16], data: &[u8]) -> [u8; 16] {
+    let cipher = Aes128::new(key.into());
+    let encrypt = |block: [u8; 16]| -> [u8; 16] {
+        let mut block = block.into();
+        cipher.encrypt_block(&mut block);
+        block.into()
+    };
+    let double = |block: [u8; 16]| -> [u8; 16] {
+        let value = u128::from_be_bytes(block);
+        let carry = if value >> 127 == 1 { 0x87 } else { 0 };
+        ((value << 1) ^ carry).to_be_bytes()
+    };
+    let k1 = double(encrypt([0; 16]));
+    let k2 = double(k1);
+
+    let blocks = data.len().div_ceil(16).max(1);
+    let mut state = [0u8; 16];
+    for block in data.chunks(16).take(blocks - 1) {
+        state.iter_mut().zip(block).for_each(|(s, b)| *s ^= b);
+        state = encrypt(state);
+    }
+    let rest = &data[(blocks - 1) * 16..];
+    let mut last = [0u8; 16];
+    last[..rest.len()].copy_from_slice(rest);
+    let subkey = if rest.len() == 16 {
+        k1
+    } else {
+        last[rest.len()] = 0x80;
+        k2
+    };
+    for i in 0..16 {
+        state[i] ^= last[i] ^ subkey[i];
+    }
+    encrypt(state)
+}
+
+fn status_error(status: u32) -> SimpleError {
+    let name = match status {
+        0xc000_000f => "no such file",
+        0xc000_0022 => "access denied",
+        0xc000_0034 => "object name not found",
+        0xc000_003a => "path not found",
+        0xc000_0043 => "sharing violation",
+        0xc000_006d => "logon failure",
+        0xc000_006e => "account restriction",
+        0xc000_0071 => "password expired",
+        0xc000_0072 => "account disabled",
+        0xc000_00cc => "bad network name",
+        0xc000_00ba => "file is a directory",
+        0xc000_0103 => "not a directory",
+        0xc000_0224 => "password must change",
+        _ => "error",
+    };
+    SimpleError::new(format!("SMB {} (0x{:08x})", name, status))
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Result<u16, SimpleError> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| SimpleError::new("truncated SMB message"))
+}
+
+fn u32_at(data: &[u8], offset: usize) -> Result<u32, SimpleError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| SimpleError::new("truncated SMB message"))
+}
+
+fn u64_at(data: &[u8], offset: usize) -> Result<u64, SimpleError> {
+    data.get(offset..offset + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_le_bytes)
+        .ok_or_else(|| SimpleError::new("truncated SMB message"))
+}
+
+fn file_id_at(data: &[u8], offset: usize) -> Result<[u8; 16], SimpleError> {
+    data.get(offset..offset + 16)
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| SimpleError::new("truncated SMB message"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn directory_entry(name: &str, attributes: u32, size: u64, last: bool) -> Vec<u8> {
+        let name = ntlm::utf16le(name);
+        let len = (64 + name.len()).div_ceil(8) * 8;
+        let mut entry = vec![0u8; len];
+        if !last {
+            entry[..4].copy_from_slice(&(len as u32).to_le_bytes());
+        }
+        entry[40..48].copy_from_slice(&size.to_le_bytes());
+        entry[56..60].copy_from_slice(&attributes.to_le_bytes());
+        entry[60..64].copy_from_slice(&(name.len() as u32).to_le_bytes());
+        entry[64..64 + name.len()].copy_from_slice(&name);
+        entry
+    }
+
+    #[test]
+    fn parses_directory_listings() {
+        let mut web_config = directory_entry("web.config", 0x20, 1024, false);
+        web_config[24..32].copy_from_slice(&133_486_382_450_000_000u64.to_le_bytes());
+        let buffer = [
+            directory_entry(".", ATTRIBUTE_DIRECTORY, 0, false),
+            web_config,
+            directory_entry("Scripts", ATTRIBUTE_DIRECTORY, 0, false),
+            directory_entry(
+                "link",
+                ATTRIBUTE_REPARSE_POINT | ATTRIBUTE_DIRECTORY,
+                0,
+                true,
+            ),
+        ]
+        .concat();
+        assert_eq!(
+            parse_directory_entries("/IT/Deploy", &buffer).unwrap(),
+            vec![
+                RemoteEntry {
+                    path: String::from("/IT/Deploy/web.config"),
+                    is_dir: false,
+                    size: Some(1024),
+                    modified: Some(String::from("2024-01-02T03:04:05Z")),
+                },
+                RemoteEntry {
+                    path: String::from("/IT/Deploy/Scripts"),
+                    is_dir: true,
+                    size: None,
+                    modified: None,
+                },
+            ]
+        );
+        assert!(parse_directory_entries("/IT", &[0; 10]).is_err());
+
+        assert_eq!(
+            split_domain("CORP\\jo", None),
+            (String::from("CORP"), String::from("jo"))
+        );
+        assert_eq!(
+            split_domain("jo", Some("CORP")),
+            (String::from("CORP"), String::from("jo"))
+        );
+        assert_eq!(
+            status_error(0xc000_006d).to_string(),
+            "SMB logon failure (0xc000006d)"
+        );
+    }
+
+    #[test]
+    fn signs_messages() {
+        // RFC 4493 examples
+        let key = [
+            0x2b, 0x7e, 0x15, 0x16, 0x28, 0xae, 0xd2, 0xa6, 0xab, 0xf7, 0x15, 0x88, 0x09, 0xcf,
+            0x4f, 0x3c,
+        ];
+        let message = [
+            0x6b, 0xc1, 0xbe, 0xe2, 0x2e, 0x40, 0x9f, 0x96, 0xe9, 0x3d, 0x7e, 0x11, 0x73, 0x93,
+            0x17, 0x2a, 0xae, 0x2d, 0x8a, 0x57, 0x1e, 0x03, 0xac, 0x9c, 0x9e, 0xb7, 0x6f, 0xac,
+            0x45, 0xaf, 0x8e, 0x51, 0x30, 0xc8, 0x1c, 0x46, 0xa3, 0x5c, 0xe4, 0x11,
+        ];
+        let hex =
+            |bytes: [u8; 16]| -> String { bytes.iter().map(|b| format!("{:02x}", b)).collect() };
+        assert_eq!(hex(aes_cmac(&key, &[])), "bb1d6929e95937287fa37d129b756746");
+        assert_eq!(
+            hex(aes_cmac(&key, &message[..16])),
+            "070a16b46b4d4144f79bdd9dd04a287c"
+        );
+        assert_eq!(
+            hex(aes_cmac(&key, &message)),
+            "dfa66747de9ae63030ca32611497c827"
+        );
+        assert_eq!(sign(0x0300, &key, &message), aes_cmac(&key, &message));
+
+        // HMAC-SHA256 truncated to 16 bytes before SMB 3, and the SP800-108 KDF of MS-SMB2
+        // 3.1.4.2 with the "SMB2AESCMAC" label and "SmbSign" context, checked against Python's
+        // hmac module
+        assert_eq!(
+            hex(sign(0x0210, &key, &[])),
+            "25e4b0882a43a647500a85fe8108f063"
+        );
+        assert_eq!(
+            hex(smb3_signing_key(&key)),
+            "82f4cd5c08f148e2506cb8b530dafdd9"
+        );
+    }
+}
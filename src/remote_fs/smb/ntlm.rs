@@ -0,0 +1,529 @@
+//! NTLMv2 authentication (MS-NLMP), wrapped in SPNEGO for SMB's `SESSION_SETUP`
+//!
+//! The key and response computations are tested against the NTLMv2 example of MS-NLMP 4.2.4.
+
+use simple_error::SimpleError;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const SIGNATURE: &[u8] = b"NTLMSSP\0";
+/// The OID of NTLMSSP, 1.3.6.1.4.1.311.2.2.10, DER encoded
+const NTLMSSP_OID: &[u8] = &[
+    0x06, 0x0a, 0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0x37, 0x02, 0x02, 0x0a,
+];
+/// The OID of SPNEGO, 1.3.6.1.5.5.2, DER encoded
+const SPNEGO_OID: &[u8] = &[0x06, 0x06, 0x2b, 0x06, 0x01, 0x05, 0x05, 0x02];
+
+// NEGOTIATE_UNICODE | REQUEST_TARGET | NEGOTIATE_SIGN | NEGOTIATE_NTLM | ALWAYS_SIGN |
+// EXTENDED_SESSIONSECURITY | NEGOTIATE_TARGET_INFO | NEGOTIATE_128 | NEGOTIATE_56. Without
+// KEY_EXCH the session key is the NTLMv2 session base key.
+const FLAGS: u32 = 0x0000_0001
+    | 0x0000_0004
+    | 0x0000_0010
+    | 0x0000_0200
+    | 0x0000_8000
+    | 0x0008_0000
+    | 0x0080_0000
+    | 0x2000_0000
+    | 0x8000_0000;
+
+const AV_EOL: u16 = 0;
+const AV_FLAGS: u16 = 6;
+const AV_TIMESTAMP: u16 = 7;
+/// Set in `MsvAvFlags` when the AUTHENTICATE message has a MIC
+const AV_FLAG_MIC: u32 = 0x2;
+/// Offset of the MIC in the AUTHENTICATE message
+const MIC_OFFSET: usize = 72;
+
+/// An NTLM handshake in progress
+pub struct Ntlm {
+    user: String,
+    domain: String,
+    password: String,
+    negotiate: Vec<u8>,
+}
+
+/// The challenge of the server
+struct Challenge {
+    flags: u32,
+    server_challenge: [u8; 8],
+    target_info: Vec<u8>,
+}
+
+/// The responses to a challenge and the session key they establish
+pub struct Responses {
+    pub nt_response: Vec<u8>,
+    pub lm_response: Vec<u8>,
+    pub session_key: [u8; 16],
+}
+
+impl Ntlm {
+    pub fn new(user: &str, domain: &str, password: &str) -> Self {
+        let mut negotiate = Vec::with_capacity(32);
+        negotiate.extend(SIGNATURE);
+        negotiate.extend(1u32.to_le_bytes());
+        negotiate.extend(FLAGS.to_le_bytes());
+        // no domain or workstation
+        negotiate.extend([0; 16]);
+        Ntlm {
+            user: String::from(user),
+            domain: String::from(domain),
+            password: String::from(password),
+            negotiate,
+        }
+    }
+
+    /// The first security token: the NEGOTIATE message in a SPNEGO `NegTokenInit`
+    pub fn negotiate_token(&self) -> Vec<u8> {
+        let mech_types = der(0xa0, &der(0x30, NTLMSSP_OID));
+        let mech_token = der(0xa2, &der(0x04, &self.negotiate));
+        let init = der(0xa0, &der(0x30, &[mech_types, mech_token].concat()));
+        der(0x60, &[SPNEGO_OID, &init].concat())
+    }
+
+    /// The answer to the server's security token: the AUTHENTICATE message in a SPNEGO
+    /// `NegTokenResp`, and the session key
+    pub fn authenticate_token(
+        &self,
+        server_token: &[u8],
+    ) -> Result<(Vec<u8>, [u8; 16]), SimpleError> {
+        let challenge_message = find_ntlm_message(server_token)
+            .ok_or_else(|| SimpleError::new("no NTLM challenge in the server's response"))?;
+        let challenge = parse_challenge(challenge_message)?;
+        let (authenticate, session_key) = self.authenticate(challenge_message, &challenge);
+        let response_token = der(0xa2, &der(0x04, &authenticate));
+        Ok((der(0xa1, &der(0x30, &response_token)), session_key))
+    }
+
+    /// The AUTHENTICATE message, with a MIC when the server sent a timestamp
+    fn authenticate(&self, challenge_message: &[u8], challenge: &Challenge) -> (Vec<u8>, [u8; 16]) {
+        let mut pairs = av_pairs(&challenge.target_info);
+        let server_time = pairs
+            .iter()
+            .find(|(id, _)| *id == AV_TIMESTAMP)
+            .and_then(|(_, value)| value.as_slice().try_into().ok())
+            .map(u64::from_le_bytes);
+        if server_time.is_some() {
+            let flags = pairs
+                .iter()
+                .find(|(id, _)| *id == AV_FLAGS)
+                .and_then(|(_, value)| value.as_slice().try_into().ok())
+                .map(u32::from_le_bytes)
+                .unwrap_or(0);
+            pairs.retain(|(id, _)| *id != AV_FLAGS);
+            pairs.push((AV_FLAGS, (flags | AV_FLAG_MIC).to_le_bytes().to_vec()));
+        }
+        let mut target_info = Vec::new();
+        for (id, value) in pairs {
+            target_info.extend(id.to_le_bytes());
+            target_info.extend((value.len() as u16).to_le_bytes());
+            target_info.extend(value);
+        }
+        target_info.extend([0; 4]);
+
+        let time = server_time.unwrap_or_else(filetime_now);
+        let mut responses = ntlmv2_responses(
+            &self.user,
+            &self.domain,
+            &self.password,
+            &challenge.server_challenge,
+            &random_bytes(),
+            time,
+            &target_info,
+        );
+        if server_time.is_some() {
+            // with a MIC, the LMv2 response is left empty
+            responses.lm_response = vec![0; 24];
+        }
+
+        let flags = challenge.flags & FLAGS;
+        let domain = utf16le(&self.domain);
+        let user = utf16le(&self.user);
+        let fields: [&[u8]; 5] = [
+            &responses.lm_response,
+            &responses.nt_response,
+            &domain,
+            &user,
+            // workstation
+            &[],
+        ];
+        let mut message = Vec::new();
+        message.extend(SIGNATURE);
+        message.extend(3u32.to_le_bytes());
+        let mut payload: Vec<u8> = Vec::new();
+        let payload_offset = MIC_OFFSET + 16;
+        for field in fields {
+            message.extend((field.len() as u16).to_le_bytes());
+            message.extend((field.len() as u16).to_le_bytes());
+            message.extend(((payload_offset + payload.len()) as u32).to_le_bytes());
+            payload.extend(field);
+        }
+        // no encrypted random session key
+        message.extend([0; 4]);
+        message.extend(((payload_offset + payload.len()) as u32).to_le_bytes());
+        message.extend(flags.to_le_bytes());
+        // version, then the MIC
+        message.extend([0; 8 + 16]);
+        message.extend(payload);
+        if server_time.is_some() {
+            let mic = hmac_md5(
+                &responses.session_key,
+                &[self.negotiate.as_slice(), challenge_message, &message].concat(),
+            );
+            message[MIC_OFFSET..MIC_OFFSET + 16].copy_from_slice(&mic);
+        }
+        (message, responses.session_key)
+    }
+}
+
+/// The NTLMv2 and LMv2 responses to `server_challenge` and the session base key (MS-NLMP
+/// 3.3.2), for a target info that already has the client's additions
+pub fn ntlmv2_responses(
+    user: &str,
+    domain: &str,
+    password: &str,
+    server_challenge: &[u8; 8],
+    client_challenge: &[u8; 8],
+    time: u64,
+    target_info: &[u8],
+) -> Responses {
+    let response_key = ntowfv2(user, domain, password);
+
+    let mut temp = vec![1, 1, 0, 0, 0, 0, 0, 0];
+    temp.extend(time.to_le_bytes());
+    temp.extend(client_challenge);
+    temp.extend([0; 4]);
+    temp.extend(target_info);
+    temp.extend([0; 4]);
+    let nt_proof = hmac_md5(
+        &response_key,
+        &[server_challenge.as_slice(), &temp].concat(),
+    );
+    let session_key = hmac_md5(&response_key, &nt_proof);
+
+    let mut lm_response = hmac_md5(
+        &response_key,
+        &[server_challenge.as_slice(), client_challenge].concat(),
+    )
+    .to_vec();
+    lm_response.extend(client_challenge);
+    Responses {
+        nt_response: [nt_proof.as_slice(), &temp].concat(),
+        lm_response,
+        session_key,
+    }
+}
+
+/// The NTLMv2 response key, NTOWFv2 of MS-NLMP 3.3.2: the HMAC-MD5 of the upper-cased user and
+/// the domain, keyed with the MD4 hash of the password
+fn ntowfv2(user: &str, domain: &str, password: &str) -> [u8; 16] {
+    let nt_hash = md4(&utf16le(password));
+    let identity = utf16le(&format!("{}{}", user.to_uppercase(), domain));
+    hmac_md5(&nt_hash, &identity)
+}
+
+/// The NTLM message in a SPNEGO token from the server
+fn find_ntlm_message(token: &[u8]) -> Option<&[u8]> {
+    let start = token
+        .windows(SIGNATURE.len())
+        .position(|window| window == SIGNATURE)?;
+    Some(&token[start..])
+}
+
+fn parse_challenge(message: &[u8]) -> Result<Challenge, SimpleError> {
+    let invalid = || SimpleError::new("invalid NTLM challenge");
+    let u16_at = |offset: usize| -> Option<usize> {
+        Some(u16::from_le_bytes(message.get(offset..offset + 2)?.try_into().ok()?) as usize)
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(
+            message.get(offset..offset + 4)?.try_into().ok()?,
+        ))
+    };
+    if u32_at(8) != Some(2) {
+        return Err(invalid());
+    }
+    let flags = u32_at(20).ok_or_else(invalid)?;
+    let server_challenge = message
+        .get(24..32)
+        .and_then(|c| c.try_into().ok())
+        .ok_or_else(invalid)?;
+    let info_len = u16_at(40).ok_or_else(invalid)?;
+    let info_offset = u32_at(44).ok_or_else(invalid)? as usize;
+    let target_info = message
+        .get(info_offset..info_offset + info_len)
+        .ok_or_else(invalid)?
+        .to_vec();
+    Ok(Challenge {
+        flags,
+        server_challenge,
+        target_info,
+    })
+}
+
+/// The AV pairs of a target info, without the terminating `MsvAvEOL`
+fn av_pairs(target_info: &[u8]) -> Vec<(u16, Vec<u8>)> {
+    let mut pairs = Vec::new();
+    let mut rest = target_info;
+    while rest.len() >= 4 {
+        let id = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        if id == AV_EOL || rest.len() < 4 + len {
+            break;
+        }
+        pairs.push((id, rest[4..4 + len].to_vec()));
+        rest = &rest[4 + len..];
+    }
+    pairs
+}
+
+/// A DER element with a tag and a definite length
+fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut element = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        element.push(len as u8);
+    } else {
+        let bytes: Vec<u8> = len
+            .to_be_bytes()
+            .into_iter()
+            .skip_while(|&b| b == 0)
+            .collect();
+        element.push(0x80 | bytes.len() as u8);
+        element.extend(bytes);
+    }
+    element.extend(content);
+    element
+}
+
+pub fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+/// The current time in 100ns intervals since 1601, as Windows keeps it
+fn filetime_now() -> u64 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    (since_epoch.as_secs() + 11_644_473_600) * 10_000_000
+        + u64::from(since_epoch.subsec_nanos() / 100)
+}
+
+/// Unpredictable bytes for the client challenge, from the randomly keyed hasher of the standard
+/// library
+fn random_bytes() -> [u8; 8] {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(filetime_now());
+    hasher.finish().to_le_bytes()
+}
+
+pub fn hmac_md5(key: &[u8], data: &[u8]) -> [u8; 16] {
+    let mut block = [0u8; 64];
+    if key.len() > 64 {
+        block[..16].copy_from_slice(&md5::compute(key).0);
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner: Vec<u8> = block
+        .iter()
+        .map(|b| b ^ 0x36)
+        .chain(data.iter().copied())
+        .collect();
+    let inner = md5::compute(inner).0;
+    let outer: Vec<u8> = block.iter().map(|b| b ^ 0x5c).chain(inner).collect();
+    md5::compute(outer).0
+}
+
+/// MD4 (RFC 1320), which NTLM hashes passwords with
+pub fn md4(data: &[u8]) -> [u8; 16] {
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend(((data.len() as u64).wrapping_mul(8)).to_le_bytes());
+
+    let f = |x: u32, y: u32, z: u32| (x & y) | (!x & z);
+    let g = |x: u32, y: u32, z: u32| (x & y) | (x & z) | (y & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let mut state: [u32; 4] = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
+    for block in message.chunks(64) {
+        let x: Vec<u32> = block
+            .chunks(4)
+            .map(|w| u32::from_le_bytes([w[0], w[1], w[2], w[3]]))
+            .collect();
+        let [mut a, mut b, mut c, mut d] = state;
+        for i in [0, 4, 8, 12] {
+            a = a.wrapping_add(f(b, c, d)).wrapping_add(x[i]).rotate_left(3);
+            d = d
+                .wrapping_add(f(a, b, c))
+                .wrapping_add(x[i + 1])
+                .rotate_left(7);
+            c = c
+                .wrapping_add(f(d, a, b))
+                .wrapping_add(x[i + 2])
+                .rotate_left(11);
+            b = b
+                .wrapping_add(f(c, d, a))
+                .wrapping_add(x[i + 3])
+                .rotate_left(19);
+        }
+        let k = 0x5a82_7999u32;
+        for i in 0..4 {
+            a = a
+                .wrapping_add(g(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(k)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(g(a, b, c))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(k)
+                .rotate_left(5);
+            c = c
+                .wrapping_add(g(d, a, b))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(k)
+                .rotate_left(9);
+            b = b
+                .wrapping_add(g(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(k)
+                .rotate_left(13);
+        }
+        let k = 0x6ed9_eba1u32;
+        for i in [0, 2, 1, 3] {
+            a = a
+                .wrapping_add(h(b, c, d))
+                .wrapping_add(x[i])
+                .wrapping_add(k)
+                .rotate_left(3);
+            d = d
+                .wrapping_add(h(a, b, c))
+                .wrapping_add(x[i + 8])
+                .wrapping_add(k)
+                .rotate_left(9);
+            c = c
+                .wrapping_add(h(d, a, b))
+                .wrapping_add(x[i + 4])
+                .wrapping_add(k)
+                .rotate_left(11);
+            b = b
+                .wrapping_add(h(c, d, a))
+                .wrapping_add(x[i + 12])
+                .wrapping_add(k)
+                .rotate_left(15);
+        }
+        for (s, v) in state.iter_mut().zip([a, b, c, d]) {
+            *s = s.wrapping_add(v);
+        }
+    }
+    let mut digest = [0u8; 16];
+    for (chunk, s) in digest.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&s.to_le_bytes());
+    }
+    digest
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    #[test]
+    fn computes_ntlmv2_responses() {
+        assert_eq!(hex(&md4(b"")), "31d6cfe0d16ae931b73c59d7e0c089c0");
+        assert_eq!(hex(&md4(b"abc")), "a448017aaf21d8525fc10ae87aa6729d");
+        assert_eq!(
+            hex(&md4(&utf16le("Password"))),
+            "a4f49c406510bdcab6824ee7c30fd852"
+        );
+        assert_eq!(
+            hex(&hmac_md5(&[0x0b; 16], b"Hi There")),
+            "9294727a3638bb1c13f48ef8158bfc9d"
+        );
+
+        // the NTLMv2 example of MS-NLMP 4.2.4: NTOWFv1 (4.2.2.1.2), ResponseKeyNT (4.2.4.1.1),
+        // the LMv2 and NTLMv2 responses (4.2.4.2.1, 4.2.4.2.2) and SessionBaseKey (4.2.4.1.2)
+        assert_eq!(
+            hex(&ntowfv2("User", "Domain", "Password")),
+            "0c868a403bfd7a93a3001ef22ef02e3f"
+        );
+        let target_info = [
+            0x02, 0x00, 0x0c, 0x00, 0x44, 0x00, 0x6f, 0x00, 0x6d, 0x00, 0x61, 0x00, 0x69, 0x00,
+            0x6e, 0x00, 0x01, 0x00, 0x0c, 0x00, 0x53, 0x00, 0x65, 0x00, 0x72, 0x00, 0x76, 0x00,
+            0x65, 0x00, 0x72, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        let responses = ntlmv2_responses(
+            "User",
+            "Domain",
+            "Password",
+            &[0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef],
+            &[0xaa; 8],
+            0,
+            &target_info,
+        );
+        assert_eq!(
+            hex(&responses.nt_response),
+            [
+                "68cd0ab851e51c96aabc927bebef6a1c",
+                "01010000000000000000000000000000aaaaaaaaaaaaaaaa00000000",
+                "02000c0044006f006d00610069006e0001000c005300650072007600650072000000000000000000",
+            ]
+            .concat()
+        );
+        assert_eq!(
+            hex(&responses.lm_response),
+            "86c35097ac9cec102554764a57cccc19aaaaaaaaaaaaaaaa"
+        );
+        assert_eq!(
+            hex(&responses.session_key),
+            "8de40ccadbc14a82f15cb0ad0de95ca3"
+        );
+        assert_eq!(av_pairs(&target_info).len(), 2);
+    }
+
+    #[test]
+    fn builds_spnego_tokens() {
+        let ntlm = Ntlm::new("jo", "CORP", "hunter2");
+        let token = ntlm.negotiate_token();
+        assert_eq!(token[0], 0x60);
+        assert_eq!(token[1] as usize, token.len() - 2);
+        assert_eq!(find_ntlm_message(&token), Some(ntlm.negotiate.as_slice()));
+        assert_eq!(der(0x04, &[0; 300])[..4], [0x04, 0x82, 0x01, 0x2c]);
+
+        let mut challenge = Vec::from(SIGNATURE);
+        challenge.extend(2u32.to_le_bytes());
+        challenge.extend([0; 8]);
+        challenge.extend(FLAGS.to_le_bytes());
+        challenge.extend([1, 2, 3, 4, 5, 6, 7, 8]);
+        challenge.extend([0; 8]);
+        // target info: a timestamp and the EOL
+        challenge.extend(16u16.to_le_bytes());
+        challenge.extend(16u16.to_le_bytes());
+        challenge.extend(48u32.to_le_bytes());
+        challenge.extend([7, 0, 8, 0, 1, 2, 3, 4, 5, 6, 7, 8, 0, 0, 0, 0]);
+        let (token, session_key) = ntlm
+            .authenticate_token(&[&[0xa1, 0x81, 0x00][..], &challenge].concat())
+            .unwrap();
+        let authenticate = find_ntlm_message(&token).unwrap();
+        assert_eq!(authenticate[8], 3);
+        // the LMv2 response is zeroed and the MIC set
+        assert_eq!(&authenticate[12..14], &[24, 0]);
+        // the MIC is the HMAC-MD5 of the three messages, its own bytes zeroed (MS-NLMP 3.1.5.1.2)
+        let mut unsigned = authenticate.to_vec();
+        unsigned[MIC_OFFSET..MIC_OFFSET + 16].fill(0);
+        assert_eq!(
+            authenticate[MIC_OFFSET..MIC_OFFSET + 16],
+            hmac_md5(
+                &session_key,
+                &[ntlm.negotiate.as_slice(), &challenge, &unsigned].concat()
+            )
+        );
+        assert!(authenticate.ends_with(&utf16le("jo")));
+    }
+}
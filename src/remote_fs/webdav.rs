@@ -0,0 +1,217 @@
+//! WebDAV over HTTP(S): `PROPFIND` listings and `GET` downloads
+
+use super::{percent_decode, Credentials, RemoteEntry, RemoteFs};
+use attohttpc::{Method, RequestBuilder, StatusCode};
+use base64::{engine::general_purpose as Base64Engine, Engine as _};
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use rusty_hog_scanner::timestamp;
+use simple_error::SimpleError;
+use std::io::Read;
+use std::time::Duration;
+use url::Url;
+
+const TIMEOUT: Duration = Duration::from_secs(60);
+const PROPFIND_BODY: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<propfind xmlns="DAV:"><prop><resourcetype/><getcontentlength/><getlastmodified/></prop></propfind>"#;
+
+pub struct WebDavFs {
+    /// The server's URL, with http or https for `dav` and `davs`
+    base: Url,
+    credentials: Credentials,
+}
+
+impl WebDavFs {
+    pub fn new(url: &Url, credentials: &Credentials) -> Result<Self, SimpleError> {
+        let scheme = if url.scheme() == "davs" {
+            "https"
+        } else {
+            "http"
+        };
+        let mut base = Url::parse(&format!("{}://{}/", scheme, url.host_str().unwrap_or("")))
+            .map_err(|e| SimpleError::with("invalid WebDAV URL", e))?;
+        base.set_port(url.port())
+            .map_err(|_| SimpleError::new("invalid WebDAV port"))?;
+        Ok(WebDavFs {
+            base,
+            credentials: credentials.clone(),
+        })
+    }
+
+    fn request(&self, method: Method, path: &str) -> Result<RequestBuilder, SimpleError> {
+        let mut url = self.base.clone();
+        url.set_path(path);
+        let mut request = RequestBuilder::try_new(method, url.as_str())
+            .map_err(|e| SimpleError::with("invalid WebDAV request", e))?
+            .timeout(TIMEOUT);
+        if let Some(user) = &self.credentials.username {
+            let user_password = format!(
+                "{}:{}",
+                user,
+                self.credentials.password.as_deref().unwrap_or_default()
+            );
+            request = request.header(
+                "Authorization",
+                format!("Basic {}", Base64Engine::STANDARD.encode(user_password)),
+            );
+        }
+        Ok(request)
+    }
+}
+
+impl RemoteFs for WebDavFs {
+    fn read_dir(&mut self, path: &str) -> Result<Vec<RemoteEntry>, SimpleError> {
+        let method = Method::from_bytes(b"PROPFIND").unwrap();
+        let response = self
+            .request(method, path)?
+            .header("Depth", "1")
+            .header("Content-Type", "application/xml")
+            .text(PROPFIND_BODY)
+            .send()
+            .map_err(|e| SimpleError::with(&format!("PROPFIND {} failed", path), e))?;
+        if response.status() != StatusCode::MULTI_STATUS {
+            return Err(SimpleError::new(format!(
+                "PROPFIND {} failed with code {}",
+                path,
+                response.status()
+            )));
+        }
+        let text = response
+            .text()
+            .map_err(|e| SimpleError::with("failed to read the PROPFIND response", e))?;
+        let entries = parse_multistatus(&text)?;
+        // the directory itself is listed with its entries
+        let dir = path.trim_end_matches('/');
+        Ok(entries
+            .into_iter()
+            .filter(|e| e.path.trim_end_matches('/') != dir)
+            .collect())
+    }
+
+    fn read_file(&mut self, path: &str, limit: u64) -> Result<Vec<u8>, SimpleError> {
+        let response = self
+            .request(Method::GET, path)?
+            .send()
+            .map_err(|e| SimpleError::with(&format!("GET {} failed", path), e))?;
+        if !response.is_success() {
+            return Err(SimpleError::new(format!(
+                "GET {} failed with code {}",
+                path,
+                response.status()
+            )));
+        }
+        let mut data = Vec::new();
+        response
+            .take(limit + 1)
+            .read_to_end(&mut data)
+            .map_err(|e| SimpleError::with(&format!("failed to read {}", path), e))?;
+        if data.len() as u64 > limit {
+            return Err(SimpleError::new(format!("bigger than {} bytes", limit)));
+        }
+        Ok(data)
+    }
+}
+
+/// The entries of a `PROPFIND` response. Hrefs may be full URLs or paths; entries get their
+/// decoded path.
+fn parse_multistatus(xml: &str) -> Result<Vec<RemoteEntry>, SimpleError> {
+    let mut reader = Reader::from_str(xml);
+    reader.trim_text(true);
+    let mut entries = Vec::new();
+    let mut current: Option<RemoteEntry> = None;
+    let mut element = Vec::new();
+    loop {
+        let event = reader
+            .read_event()
+            .map_err(|e| SimpleError::with("invalid PROPFIND response", e))?;
+        match event {
+            Event::Start(e) | Event::Empty(e) => {
+                let name = e.local_name().as_ref().to_ascii_lowercase();
+                match name.as_slice() {
+                    b"response" => {
+                        current = Some(RemoteEntry {
+                            path: String::new(),
+                            is_dir: false,
+                            size: None,
+                            modified: None,
+                        })
+                    }
+                    b"collection" => {
+                        if let Some(entry) = current.as_mut() {
+                            entry.is_dir = true;
+                        }
+                    }
+                    _ => {}
+                }
+                element = name;
+            }
+            Event::Text(text) => {
+                let text = text
+                    .unescape()
+                    .map_err(|e| SimpleError::with("invalid PROPFIND response", e))?;
+                match (current.as_mut(), element.as_slice()) {
+                    (Some(entry), b"href") => {
+                        let path = match Url::parse(&text) {
+                            Ok(url) => String::from(url.path()),
+                            Err(_) => text.into_owned(),
+                        };
+                        entry.path = percent_decode(&path);
+                    }
+                    (Some(entry), b"getcontentlength") => entry.size = text.trim().parse().ok(),
+                    (Some(entry), b"getlastmodified") => {
+                        entry.modified = timestamp::normalize(&text)
+                    }
+                    _ => {}
+                }
+            }
+            Event::End(e) => {
+                if e.local_name().as_ref().eq_ignore_ascii_case(b"response") {
+                    entries.extend(current.take().filter(|e| !e.path.is_empty()));
+                }
+                element.clear();
+            }
+            Event::Eof => return Ok(entries),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_propfind_responses() {
+        let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<d:multistatus xmlns:d="DAV:">
+  <d:response>
+    <d:href>/remote.php/dav/files/jo/</d:href>
+    <d:propstat><d:prop><d:resourcetype><d:collection/></d:resourcetype></d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+  </d:response>
+  <d:response>
+    <d:href>https://cloud.example.com/remote.php/dav/files/jo/My%20Notes.txt</d:href>
+    <d:propstat><d:prop><d:resourcetype/><d:getcontentlength>42</d:getcontentlength>
+      <d:getlastmodified>Tue, 02 Jan 2024 03:04:05 GMT</d:getlastmodified></d:prop>
+      <d:status>HTTP/1.1 200 OK</d:status></d:propstat>
+  </d:response>
+</d:multistatus>"#;
+        assert_eq!(
+            parse_multistatus(xml).unwrap(),
+            vec![
+                RemoteEntry {
+                    path: String::from("/remote.php/dav/files/jo/"),
+                    is_dir: true,
+                    size: None,
+                    modified: None,
+                },
+                RemoteEntry {
+                    path: String::from("/remote.php/dav/files/jo/My Notes.txt"),
+                    is_dir: false,
+                    size: Some(42),
+                    modified: Some(String::from("2024-01-02T03:04:05Z")),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,196 @@
+//! The `--state-file` of the hogs that scan web APIs (hante_hog, gottingen_hog and essex_hog),
+//! which lets an interrupted scan (a network blip, a rate-limit ban, ctrl-C) resume where it
+//! stopped instead of walking the whole history again.
+//!
+//! Each hog keeps its own state in the file, such as the cursor of the next page of each scan in
+//! progress, and rewrites it with `save` after every page. The state never holds findings: those
+//! of the pages scanned so far are appended to a `FindingsSpool`, a JSON Lines file next to the
+//! state file that only the user can read, which is removed once the findings are output.
+//!
+//! ```
+//! use rusty_hogs::scan_state::FindingsSpool;
+//! let dir = tempfile::tempdir().unwrap();
+//! let state_file = dir.path().join("state.json");
+//! let spool = FindingsSpool::new(state_file.to_str().unwrap(), "C12345");
+//! spool.append(&[String::from("a finding")]).unwrap();
+//! spool.append(&[String::from("another")]).unwrap();
+//! assert_eq!(spool.read::<String>().unwrap().len(), 2);
+//! spool.remove().unwrap();
+//! assert!(spool.read::<String>().unwrap().is_empty());
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use simple_error::SimpleError;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use tempfile::NamedTempFile;
+
+/// Reads a state file, or starts a new state if it doesn't exist yet
+pub fn load<T: DeserializeOwned + Default>(path: &str) -> Result<T, SimpleError> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|e| SimpleError::with(&format!("invalid state file {}", path), e)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(SimpleError::with(
+            &format!("failed to read state file {}", path),
+            e,
+        )),
+    }
+}
+
+/// Writes the state next to `path` first and renames it over, so an interrupted run can't leave
+/// a truncated state file behind
+pub fn save<T: Serialize>(path: &str, state: &T) -> Result<(), SimpleError> {
+    let err = |e| SimpleError::with(&format!("failed to write state file {}", path), e);
+    let mut file = NamedTempFile::new_in(parent_dir(path)).map_err(err)?;
+    serde_json::to_writer_pretty(&mut file, state)
+        .map_err(|e| SimpleError::with("failed to serialize state", e))?;
+    file.persist(path).map_err(|e| err(e.error))?;
+    Ok(())
+}
+
+fn parent_dir(path: &str) -> &Path {
+    Path::new(path)
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+/// The findings of the pages a scan in progress has scanned so far, one JSON object per line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FindingsSpool {
+    path: PathBuf,
+}
+
+impl FindingsSpool {
+    /// The spool of the scan of `target` (a channel, issue or page) that the state file at
+    /// `state_path` keeps the progress of
+    pub fn new(state_path: &str, target: &str) -> Self {
+        let hash = hex::encode(Sha256::digest(target.as_bytes()));
+        let name = match Path::new(state_path).file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => String::from("state"),
+        };
+        Self {
+            path: parent_dir(state_path).join(format!("{}.{}.findings", name, &hash[..16])),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Appends findings, creating the spool readable and writable by the user only. A line cut
+    /// short by an interrupted write is ended first, so that it doesn't run into the findings.
+    pub fn append<T: Serialize>(&self, findings: &[T]) -> Result<(), SimpleError> {
+        let err = |e| SimpleError::with(&format!("failed to write {}", self.path.display()), e);
+        let mut options = OpenOptions::new();
+        options.create(true).read(true).append(true);
+        #[cfg(unix)]
+        options.mode(0o600);
+        let mut file = options.open(&self.path).map_err(err)?;
+        let mut lines = Vec::new();
+        if file.seek(SeekFrom::End(0)).map_err(err)? > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1)).map_err(err)?;
+            file.read_exact(&mut last).map_err(err)?;
+            if last[0] != b'\n' {
+                lines.push(b'\n');
+            }
+        }
+        for finding in findings {
+            serde_json::to_writer(&mut lines, finding)
+                .map_err(|e| SimpleError::with("failed to serialize finding", e))?;
+            lines.push(b'\n');
+        }
+        file.write_all(&lines).map_err(err)?;
+        file.sync_data().map_err(err)
+    }
+
+    /// The findings appended so far, none if the spool doesn't exist. A line cut short by an
+    /// interrupted write is skipped.
+    pub fn read<T: DeserializeOwned>(&self) -> Result<Vec<T>, SimpleError> {
+        let err = |e| SimpleError::with(&format!("failed to read {}", self.path.display()), e);
+        let file = match fs::File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(err(e)),
+        };
+        let mut findings = Vec::new();
+        for line in BufReader::new(file).lines() {
+            match serde_json::from_str(&line.map_err(err)?) {
+                Ok(finding) => findings.push(finding),
+                Err(e) => log::warn!("skipping a finding of {}: {}", self.path.display(), e),
+            }
+        }
+        Ok(findings)
+    }
+
+    /// Removes the spool, if it exists
+    pub fn remove(&self) -> Result<(), SimpleError> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(SimpleError::with(
+                &format!("failed to remove {}", self.path.display()),
+                e,
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn state_and_spool_are_kept_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let path = path.to_str().unwrap();
+        let mut state: BTreeMap<String, String> = load(path).unwrap();
+        assert!(state.is_empty());
+        state.insert(String::from("C12345"), String::from("cursor"));
+        save(path, &state).unwrap();
+        assert_eq!(load::<BTreeMap<String, String>>(path).unwrap(), state);
+        fs::write(path, "not json").unwrap();
+        assert!(load::<BTreeMap<String, String>>(path).is_err());
+
+        let spool = FindingsSpool::new(path, "C12345");
+        assert_eq!(spool.path().parent(), Some(dir.path()));
+        assert_ne!(spool, FindingsSpool::new(path, "C67890"));
+        spool.append(&[1, 2]).unwrap();
+        spool.append::<u32>(&[]).unwrap();
+        // a line cut short when the hog was killed
+        let mut file = OpenOptions::new().append(true).open(spool.path()).unwrap();
+        file.write_all(b"3\n{\"trunc").unwrap();
+        assert_eq!(spool.read::<u32>().unwrap(), vec![1, 2, 3]);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(spool.path()).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        spool.remove().unwrap();
+        spool.remove().unwrap();
+        assert!(spool.read::<u32>().unwrap().is_empty());
+    }
+
+    #[test]
+    fn appends_after_a_line_cut_short() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.json");
+        let spool = FindingsSpool::new(path.to_str().unwrap(), "C12345");
+        spool.append(&[1]).unwrap();
+        let mut file = OpenOptions::new().append(true).open(spool.path()).unwrap();
+        file.write_all(b"{\"trunc").unwrap();
+        spool.append(&[2]).unwrap();
+        spool.append(&[3]).unwrap();
+        assert_eq!(spool.read::<u32>().unwrap(), vec![1, 2, 3]);
+    }
+}
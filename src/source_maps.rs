@@ -0,0 +1,277 @@
+//! Source maps (version 3) for the JavaScript bundles scanned by duroc_hog and saddleback_hog.
+//!
+//! A finding in a minified bundle like `main.8f3a2c.js` says little about where the secret lives
+//! in the code base. `SourceMap::lookup` translates a line and column of the bundle back to the
+//! original source file and line the bundler built it from, and `SourceMap::sources` gives the
+//! original sources embedded in the map (`sourcesContent`), so they can be scanned file by file.
+//! `source_mapping_url` finds the map of a bundle from its `//# sourceMappingURL=` comment, and
+//! `data_url` decodes maps inlined as `data:` URLs.
+//!
+//! Columns are counted in UTF-16 code units, as the source map format does; `utf16_column`
+//! converts a byte offset in a line. Indexed maps (with `sections`) aren't supported.
+//!
+//! ```
+//! use rusty_hogs::source_maps::SourceMap;
+//! let map = SourceMap::parse(br#"{"version":3,"sources":["src/config.js"],"mappings":"AAAA;AACA,IAAI"}"#).unwrap();
+//! let position = map.lookup(2, 10).unwrap();
+//! assert_eq!(position.source, "src/config.js");
+//! assert_eq!(position.line, 2);
+//! ```
+
+use base64::{engine::general_purpose as Base64Engine, Engine as _};
+use serde_json::Value;
+use simple_error::SimpleError;
+
+const BASE64_DIGITS: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// A parsed source map
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// Paths of the original sources, with the `sourceRoot` prepended
+    sources: Vec<String>,
+    sources_content: Vec<Option<String>>,
+    /// The segments of each generated line, by column
+    lines: Vec<Vec<Segment>>,
+}
+
+/// A mapping from a column of a generated line to a position in an original source
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Segment {
+    column: usize,
+    /// Index of the source, line and column in it, unless the segment maps to no source
+    original: Option<(usize, usize, usize)>,
+}
+
+/// A position in an original source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OriginalPosition {
+    pub source: String,
+    /// Line in the source, from 1
+    pub line: usize,
+    /// Column in the line, from 0
+    pub column: usize,
+}
+
+impl SourceMap {
+    pub fn parse(data: &[u8]) -> Result<Self, SimpleError> {
+        let json: Value =
+            serde_json::from_slice(data).map_err(|e| SimpleError::with("invalid source map", e))?;
+        if json.get("sections").is_some() {
+            return Err(SimpleError::new("indexed source maps aren't supported"));
+        }
+        if json.get("version").and_then(Value::as_u64) != Some(3) {
+            return Err(SimpleError::new("not a version 3 source map"));
+        }
+        let root = json
+            .get("sourceRoot")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        let strings = |key: &str| -> Vec<Option<String>> {
+            json.get(key)
+                .and_then(Value::as_array)
+                .map(|a| a.iter().map(|v| v.as_str().map(String::from)).collect())
+                .unwrap_or_default()
+        };
+        let sources = strings("sources")
+            .into_iter()
+            .map(|s| match (root, s) {
+                ("", s) => s.unwrap_or_default(),
+                (root, s) => format!("{}/{}", root.trim_end_matches('/'), s.unwrap_or_default()),
+            })
+            .collect();
+        let mappings = json
+            .get("mappings")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        Ok(SourceMap {
+            sources,
+            sources_content: strings("sourcesContent"),
+            lines: decode_mappings(mappings)?,
+        })
+    }
+
+    /// The original position of the generated `line` (from 1) and `column` (from 0, in UTF-16
+    /// code units): that of the last segment of the line starting at or before the column
+    pub fn lookup(&self, line: usize, column: usize) -> Option<OriginalPosition> {
+        let segments = self.lines.get(line.checked_sub(1)?)?;
+        let index = segments.partition_point(|s| s.column <= column);
+        let (source, line, column) = segments.get(index.checked_sub(1)?)?.original?;
+        Some(OriginalPosition {
+            source: self.sources.get(source)?.clone(),
+            line: line + 1,
+            column,
+        })
+    }
+
+    /// The original sources embedded in the map, as (path, content)
+    pub fn sources(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.sources
+            .iter()
+            .zip(self.sources_content.iter())
+            .filter_map(|(path, content)| Some((path.as_str(), content.as_deref()?)))
+    }
+}
+
+/// The URL in the last `//# sourceMappingURL=` (or the older `//@`) comment of a script
+pub fn source_mapping_url(script: &[u8]) -> Option<String> {
+    let text = String::from_utf8_lossy(script);
+    text.lines().rev().find_map(|line| {
+        let line = line.trim();
+        let rest = line
+            .strip_prefix("//#")
+            .or_else(|| line.strip_prefix("//@"))
+            .or_else(|| line.strip_prefix("/*#"))?;
+        let url = rest.trim_start().strip_prefix("sourceMappingURL=")?;
+        let url = url.trim_end_matches("*/").trim();
+        (!url.is_empty()).then(|| String::from(url))
+    })
+}
+
+/// The content of a base64 `data:` URL, like the inline source maps of development builds
+pub fn data_url(url: &str) -> Option<Vec<u8>> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    if header.ends_with(";base64") {
+        Base64Engine::STANDARD.decode(data.trim()).ok()
+    } else {
+        Some(data.as_bytes().to_vec())
+    }
+}
+
+/// The column, in UTF-16 code units, of the byte `offset` of `line`
+pub fn utf16_column(line: &[u8], offset: usize) -> usize {
+    String::from_utf8_lossy(&line[..offset.min(line.len())])
+        .encode_utf16()
+        .count()
+}
+
+/// Decodes the `mappings` of a source map: lines separated by `;`, segments by `,`, each a
+/// list of base64 VLQ numbers relative to the previous segment
+fn decode_mappings(mappings: &str) -> Result<Vec<Vec<Segment>>, SimpleError> {
+    let invalid = || SimpleError::new("invalid source map mappings");
+    let mut lines = Vec::new();
+    // source index, original line and original column carry over from line to line
+    let (mut source, mut original_line, mut original_column) = (0i64, 0i64, 0i64);
+    for line in mappings.split(';') {
+        let mut segments = Vec::new();
+        let mut column = 0i64;
+        for segment in line.split(',').filter(|s| !s.is_empty()) {
+            let fields = decode_vlq(segment).ok_or_else(invalid)?;
+            column += fields[0];
+            let original = if fields.len() >= 4 {
+                source += fields[1];
+                original_line += fields[2];
+                original_column += fields[3];
+                Some((
+                    usize::try_from(source).map_err(|_| invalid())?,
+                    usize::try_from(original_line).map_err(|_| invalid())?,
+                    usize::try_from(original_column).map_err(|_| invalid())?,
+                ))
+            } else {
+                None
+            };
+            segments.push(Segment {
+                column: usize::try_from(column).map_err(|_| invalid())?,
+                original,
+            });
+        }
+        segments.sort_by_key(|s| s.column);
+        lines.push(segments);
+    }
+    Ok(lines)
+}
+
+/// The numbers of a base64 VLQ segment: 5 bits per digit, least significant first, with a
+/// continuation bit, and the sign in the lowest bit of the result
+fn decode_vlq(segment: &str) -> Option<Vec<i64>> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0i64, 0u32);
+    for c in segment.bytes() {
+        let digit = BASE64_DIGITS.iter().position(|&d| d == c)? as i64;
+        value += (digit & 31) << shift;
+        if digit & 32 != 0 {
+            shift += 5;
+            if shift > 55 {
+                return None;
+            }
+        } else {
+            values.push(if value & 1 == 1 {
+                -(value >> 1)
+            } else {
+                value >> 1
+            });
+            value = 0;
+            shift = 0;
+        }
+    }
+    (shift == 0 && !values.is_empty()).then_some(values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_generated_positions() {
+        assert_eq!(decode_vlq("AAAA"), Some(vec![0, 0, 0, 0]));
+        assert_eq!(decode_vlq("SAAS"), Some(vec![9, 0, 0, 9]));
+        assert_eq!(decode_vlq("D"), Some(vec![-1]));
+        assert_eq!(decode_vlq("2HAAC"), Some(vec![123, 0, 0, 1]));
+        assert_eq!(decode_vlq("g"), None);
+
+        let map = SourceMap::parse(
+            br#"{"version":3,"sourceRoot":"webpack://app/","sources":["./src/api.js","./src/config.js"],
+            "sourcesContent":["export const url = '/api';",null],
+            "mappings":"AAAA,SAAS,ECET;;AAAA,E"}"#,
+        )
+        .unwrap();
+        let position = |line, column| {
+            map.lookup(line, column)
+                .map(|p| (p.source, p.line, p.column))
+        };
+        assert_eq!(
+            position(1, 3),
+            Some((String::from("webpack://app/./src/api.js"), 1, 0))
+        );
+        assert_eq!(
+            position(1, 10),
+            Some((String::from("webpack://app/./src/api.js"), 1, 9))
+        );
+        assert_eq!(
+            position(1, 40),
+            Some((String::from("webpack://app/./src/config.js"), 3, 0))
+        );
+        assert_eq!(position(2, 0), None);
+        assert_eq!(
+            position(3, 1),
+            Some((String::from("webpack://app/./src/config.js"), 3, 0))
+        );
+        // a segment without a source ends the mapping of the one before it
+        assert_eq!(position(3, 5), None);
+        assert_eq!(position(9, 0), None);
+        assert_eq!(
+            map.sources().collect::<Vec<_>>(),
+            vec![("webpack://app/./src/api.js", "export const url = '/api';")]
+        );
+        assert!(SourceMap::parse(br#"{"version":3,"sections":[]}"#).is_err());
+        assert!(SourceMap::parse(b"console.log(1)").is_err());
+    }
+
+    #[test]
+    fn finds_source_map_urls() {
+        assert_eq!(
+            source_mapping_url(b"console.log(1);\n//# sourceMappingURL=main.js.map\n"),
+            Some(String::from("main.js.map"))
+        );
+        assert_eq!(
+            source_mapping_url(b"a{}\n/*# sourceMappingURL=app.css.map */"),
+            Some(String::from("app.css.map"))
+        );
+        assert_eq!(source_mapping_url(b"var sourceMappingURL = 1;"), None);
+        assert_eq!(
+            data_url("data:application/json;charset=utf-8;base64,eyJ2ZXJzaW9uIjozfQ=="),
+            Some(b"{\"version\":3}".to_vec())
+        );
+        assert_eq!(data_url("main.js.map"), None);
+        assert_eq!(utf16_column("const é = 'x'".as_bytes(), 9), 8);
+    }
+}
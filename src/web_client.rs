@@ -0,0 +1,162 @@
+//! HTTP client shared by the hogs that scan web APIs (hante_hog, gottingen_hog and essex_hog).
+//!
+//! `WebClient` wraps a hyper `Client` with a token-bucket `RateLimiter`, so a large scan spreads
+//! its requests out instead of getting the service account throttled or banned. Each hog has a
+//! default rate suited to its API, which `--rate-limit <req/s>` overrides. Responses with a 429
+//! status are retried after the `Retry-After` the server asks for (or an exponential backoff when
+//! it doesn't say), up to `DEFAULT_MAX_RETRIES` times.
+//!
+//! ```
+//! use rusty_hogs::web_client::RateLimiter;
+//! let limiter = RateLimiter::new(2.0);
+//! assert!(limiter.reserve().is_zero());
+//! assert!(limiter.reserve().is_zero());
+//! assert!(!limiter.reserve().is_zero());
+//! ```
+
+use hyper::client::connect::Connect;
+use hyper::header::{HeaderMap, RETRY_AFTER};
+use hyper::http::{Request, Response, StatusCode};
+use hyper::{Body, Client};
+use log::{self, warn};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Number of times a request that got a 429 response is retried
+pub const DEFAULT_MAX_RETRIES: u32 = 5;
+const MAX_RETRY_DELAY_SECS: u64 = 120;
+
+#[derive(Debug)]
+/// Token bucket that allows `requests_per_second` on average, with bursts of up to one second's
+/// worth of requests
+pub struct RateLimiter {
+    requests_per_second: f64,
+    burst: f64,
+    // (available tokens, last refill); tokens go negative when requests are queued
+    state: Mutex<(f64, Instant)>,
+}
+
+impl RateLimiter {
+    /// A `requests_per_second` of 0 (or less) doesn't limit requests
+    pub fn new(requests_per_second: f64) -> Self {
+        let burst = requests_per_second.max(1.0);
+        Self {
+            requests_per_second,
+            burst,
+            state: Mutex::new((burst, Instant::now())),
+        }
+    }
+
+    /// Takes a token and returns how long to wait before using it
+    pub fn reserve(&self) -> Duration {
+        if self.requests_per_second <= 0.0 {
+            return Duration::ZERO;
+        }
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        let refill = now.duration_since(state.1).as_secs_f64() * self.requests_per_second;
+        state.0 = (state.0 + refill).min(self.burst) - 1.0;
+        state.1 = now;
+        if state.0 >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.0 / self.requests_per_second)
+        }
+    }
+
+    /// Waits until the next request is allowed
+    pub async fn acquire(&self) {
+        let wait = self.reserve();
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Reads a `Retry-After` header given in seconds
+pub fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    headers
+        .get(RETRY_AFTER)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.trim().parse::<u64>().ok())
+        .map(|s| Duration::from_secs(s.min(MAX_RETRY_DELAY_SECS)))
+}
+
+#[derive(Debug)]
+/// A hyper `Client` that is rate limited and retries 429 responses
+pub struct WebClient<C> {
+    client: Client<C>,
+    limiter: RateLimiter,
+    max_retries: u32,
+}
+
+impl<C> WebClient<C>
+where
+    C: Connect + Clone + Send + Sync + 'static,
+{
+    pub fn new(client: Client<C>, requests_per_second: f64) -> Self {
+        Self {
+            client,
+            limiter: RateLimiter::new(requests_per_second),
+            max_retries: DEFAULT_MAX_RETRIES,
+        }
+    }
+
+    /// Sends the request made by `request`, waiting for the rate limiter first. A 429 response is
+    /// retried with a new request from `request` until the retries run out, then returned.
+    pub async fn send(
+        &self,
+        request: impl Fn() -> Request<Body>,
+    ) -> Result<Response<Body>, hyper::Error> {
+        let mut attempt = 0;
+        loop {
+            self.limiter.acquire().await;
+            let response = self.client.request(request()).await?;
+            if response.status() != StatusCode::TOO_MANY_REQUESTS || attempt >= self.max_retries {
+                return Ok(response);
+            }
+            let delay = retry_after(response.headers())
+                .unwrap_or_else(|| Duration::from_secs(1 << attempt.min(6)));
+            attempt += 1;
+            warn!(
+                "Rate limited by the API, retry {} of {} in {:?}",
+                attempt, self.max_retries, delay
+            );
+            tokio::time::sleep(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    #[test]
+    fn limits_requests_per_second() {
+        let limiter = RateLimiter::new(4.0);
+        for _ in 0..4 {
+            assert!(limiter.reserve().is_zero());
+        }
+        let wait = limiter.reserve();
+        assert!(wait > Duration::from_millis(200) && wait <= Duration::from_millis(250));
+        // queued requests wait in turn
+        assert!(limiter.reserve() > wait);
+
+        let unlimited = RateLimiter::new(0.0);
+        assert!((0..100).all(|_| unlimited.reserve().is_zero()));
+    }
+
+    #[test]
+    fn reads_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("30"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(30)));
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+}